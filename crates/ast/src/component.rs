@@ -1,27 +1,31 @@
-use super::{Command, Id, Signature};
+use super::{Command, FnDef, Id, Signature, TypeDef};
 use fgen::GenConfig;
 use fil_gen as fgen;
 use fil_utils::{self as utils, AttrCtx};
 use std::path::PathBuf;
 
-#[derive(Default)]
+#[derive(Clone)]
 /// A external or generate definition in Filament
 pub struct Extern {
     pub path: String,
     pub comps: Vec<Signature>,
     /// name of the tool that generates this module
     pub gen_tool: Option<String>,
+    /// Location of the header of the definition
+    pub pos: utils::GPosIdx,
 }
 impl Extern {
     pub fn new(
         path: String,
         comps: Vec<Signature>,
         gen_tool: Option<String>,
+        pos: utils::GPosIdx,
     ) -> Self {
         Self {
             path,
             comps,
             gen_tool,
+            pos,
         }
     }
 
@@ -34,7 +38,7 @@ impl Extern {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 /// A component in Filament
 pub struct Component {
     // Signature of this component
@@ -49,7 +53,7 @@ impl Component {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Namespace {
     /// Imported files
     pub imports: Vec<String>,
@@ -57,6 +61,10 @@ pub struct Namespace {
     pub externs: Vec<Extern>,
     /// Components defined in this file
     pub components: Vec<Component>,
+    /// Record and enum types defined in this file
+    pub types: Vec<TypeDef>,
+    /// Functions over parameters defined in this file
+    pub functions: Vec<FnDef>,
     /// Top level bindings
     pub bindings: Vec<u64>,
 }
@@ -73,25 +81,46 @@ impl Namespace {
     /// REQUIRES: The tools definitions must be in files with absolute paths.
     /// The folder containing the generated files is deleted when the destructor
     /// for GenExec runs.
+    /// Tools that cannot be registered are reported as errors at their
+    /// generate definitions.
+    /// Failing to create the output directory is also reported as an error.
     pub fn init_gen(
         &self,
         out_dir: Option<PathBuf>,
         config: GenConfig,
-    ) -> fgen::GenExec {
-        let mut gen_exec = fgen::GenExec::new(false, out_dir, config);
-        for Extern { path, gen_tool, .. } in &self.externs {
+    ) -> Result<fgen::GenExec, u64> {
+        let mut diag = utils::Diagnostics::default();
+        let mut gen_exec = match fgen::GenExec::new(false, out_dir, config) {
+            Ok(gen_exec) => gen_exec,
+            Err(e) => {
+                diag.add_error(utils::Error::misc(e.msg));
+                return Err(diag.report_all().unwrap());
+            }
+        };
+        for Extern {
+            path,
+            gen_tool,
+            pos,
+            ..
+        } in &self.externs
+        {
             let Some(tool_name) = gen_tool else {
                 continue;
             };
-            let tool = gen_exec.register_tool_from_file(path.into());
-            assert!(
-                &tool.name == tool_name,
-                "Generate definition for tool `{}` does not match the tool name `{}`",
-                tool_name,
-                tool.name
-            );
+            let msg = match gen_exec.register_tool_from_file(path.into()) {
+                Ok(tool) if &tool.name == tool_name => continue,
+                Ok(tool) => {
+                    format!("tool defined in `{path}' is named `{}'", tool.name)
+                }
+                Err(e) => e.msg,
+            };
+            let err = utils::Error::misc(format!(
+                "failed to register generator tool `{tool_name}'"
+            ))
+            .add_note(diag.add_info(msg, *pos));
+            diag.add_error(err);
         }
-        gen_exec
+        diag.report_all().map_or(Ok(gen_exec), Err)
     }
 
     /// External signatures associated with the namespace
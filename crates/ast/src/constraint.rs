@@ -149,6 +149,95 @@ impl OrderConstraint<TimeSub> {
     }
 }
 
+/// A boolean combination of ordering constraints
+#[derive(Clone, Hash, Debug)]
+pub enum Prop<T> {
+    Cmp(OrderConstraint<T>),
+    Not(Box<Prop<T>>),
+    And(Box<Prop<T>>, Box<Prop<T>>),
+    Or(Box<Prop<T>>, Box<Prop<T>>),
+}
+
+impl<T> Prop<T> {
+    pub fn and(self, other: Self) -> Self {
+        Prop::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Self) -> Self {
+        Prop::Or(Box::new(self), Box::new(other))
+    }
+
+    /// The ordering constraints mentioned in this proposition
+    pub fn cmps(&self) -> Vec<&OrderConstraint<T>> {
+        match self {
+            Prop::Cmp(c) => vec![c],
+            Prop::Not(p) => p.cmps(),
+            Prop::And(l, r) | Prop::Or(l, r) => {
+                let mut cmps = l.cmps();
+                cmps.extend(r.cmps());
+                cmps
+            }
+        }
+    }
+
+    /// Mutable references to the ordering constraints in this proposition
+    pub fn cmps_mut(&mut self) -> Vec<&mut OrderConstraint<T>> {
+        match self {
+            Prop::Cmp(c) => vec![c],
+            Prop::Not(p) => p.cmps_mut(),
+            Prop::And(l, r) | Prop::Or(l, r) => {
+                let mut cmps = l.cmps_mut();
+                cmps.extend(r.cmps_mut());
+                cmps
+            }
+        }
+    }
+}
+
+impl Prop<Expr> {
+    pub fn resolve_expr(self, binding: &Binding<Expr>) -> Self {
+        match self {
+            Prop::Cmp(c) => Prop::Cmp(c.resolve_expr(binding)),
+            Prop::Not(p) => !p.resolve_expr(binding),
+            Prop::And(l, r) => {
+                l.resolve_expr(binding).and(r.resolve_expr(binding))
+            }
+            Prop::Or(l, r) => {
+                l.resolve_expr(binding).or(r.resolve_expr(binding))
+            }
+        }
+    }
+
+    pub fn exprs(&self) -> Vec<&Expr> {
+        self.cmps().into_iter().flat_map(|c| c.exprs()).collect()
+    }
+}
+
+impl<T> std::ops::Not for Prop<T> {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Prop::Not(Box::new(self))
+    }
+}
+
+impl<T> From<OrderConstraint<T>> for Prop<T> {
+    fn from(con: OrderConstraint<T>) -> Self {
+        Prop::Cmp(con)
+    }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for Prop<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Prop::Cmp(c) => write!(f, "{} {} {}", c.left, c.op, c.right),
+            Prop::Not(p) => write!(f, "!({p})"),
+            Prop::And(l, r) => write!(f, "({l} && {r})"),
+            Prop::Or(l, r) => write!(f, "({l} || {r})"),
+        }
+    }
+}
+
 /// A ordering constraint over time expressions or time ranges.
 #[derive(Clone, Hash)]
 pub enum Constraint {
@@ -1,5 +1,5 @@
 use super::{
-    Binding, Expr, Id, Implication, Loc, OrderConstraint, Range, Time,
+    Binding, Expr, Id, Implication, Interp, Loc, Prop, Range, Time, TypeApp,
 };
 use fil_utils::PortAttrs;
 use struct_variant::struct_variant;
@@ -47,6 +47,8 @@ pub struct Port {
     pub base: PortRef,
     /// Optional array/bundle access
     pub access: Vec<Loc<Access>>,
+    /// Fields accessed on a record-typed port
+    pub fields: Vec<Loc<Id>>,
     /// Source location for error reporting
     pub loc: fil_utils::GPosIdx,
 }
@@ -67,6 +69,7 @@ impl Port {
         Port {
             base: PortRef::This { port },
             access: Vec::new(),
+            fields: Vec::new(),
             loc,
         }
     }
@@ -77,6 +80,7 @@ impl Port {
         Port {
             base: PortRef::Instance { instance, port },
             access: Vec::new(),
+            fields: Vec::new(),
             loc,
         }
     }
@@ -87,6 +91,12 @@ impl Port {
         self
     }
 
+    /// Access fields of a record-typed port
+    pub fn with_fields(mut self, fields: Vec<Loc<Id>>) -> Self {
+        self.fields = fields;
+        self
+    }
+
     /// Check if this is a bundle access
     pub fn is_bundle(&self) -> bool {
         !self.access.is_empty()
@@ -154,6 +164,10 @@ impl std::fmt::Display for Port {
             write!(f, "{{{}}}", access)?;
         }
 
+        for field in &self.fields {
+            write!(f, ".{}", field)?;
+        }
+
         Ok(())
     }
 }
@@ -332,14 +346,14 @@ impl ForLoop {
 /// The `then` branch is checked assuming that the condition is true and the `else` branch is checked
 /// assuming that the condition is false.
 pub struct If {
-    pub cond: OrderConstraint<Expr>,
+    pub cond: Prop<Expr>,
     pub then: Vec<Command>,
     pub alt: Vec<Command>,
 }
 
 impl If {
     pub fn new(
-        cond: OrderConstraint<Expr>,
+        cond: Prop<Expr>,
         then: Vec<Command>,
         alt: Vec<Command>,
     ) -> Self {
@@ -359,8 +373,12 @@ pub struct BundleType {
     pub len: Vec<Loc<Expr>>,
     /// Availability interval for the bundle
     pub liveness: Loc<Range>,
-    /// Bitwidth of the bundle
+    /// Bitwidth of the bundle. A bare name refers to a declared type.
     pub bitwidth: Loc<Expr>,
+    /// The record type of the bundle if it was given arguments
+    pub record: Option<Loc<TypeApp>>,
+    /// Numeric interpretation of the bundle's bits
+    pub interp: Option<Loc<Interp>>,
 }
 
 impl BundleType {
@@ -375,6 +393,8 @@ impl BundleType {
             len,
             liveness,
             bitwidth,
+            record: None,
+            interp: None,
         }
     }
 
@@ -388,6 +408,17 @@ impl BundleType {
                 .collect(),
             liveness: self.liveness.map(|e| e.resolve_exprs(binding)),
             bitwidth: self.bitwidth.map(|e| e.resolve(binding)),
+            record: self.record.map(|r| {
+                r.map(|r| TypeApp {
+                    args: r
+                        .args
+                        .into_iter()
+                        .map(|e| e.map(|e| e.resolve(binding)))
+                        .collect(),
+                    ..r
+                })
+            }),
+            interp: self.interp.map(|i| i.map(|i| i.resolve(binding))),
         }
     }
 
@@ -1,5 +1,4 @@
-use crate::OrderConstraint;
-use crate::OrderOp;
+use crate::Prop;
 
 use super::{Binding, Id, Loc};
 use fil_utils::Error;
@@ -53,6 +52,18 @@ impl std::fmt::Display for Fn {
 }
 
 impl Fn {
+    /// The builtin function with the given name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pow2" => Some(Fn::Pow2),
+            "log2" => Some(Fn::Log2),
+            "sin_bits" => Some(Fn::SinB),
+            "cos_bits" => Some(Fn::CosB),
+            "bit_rev" => Some(Fn::BitRev),
+            _ => None,
+        }
+    }
+
     pub fn eval(self, args: Vec<u64>) -> u64 {
         match (self, &*args) {
             (Fn::Pow2, &[n]) => 2u64.pow(n as u32),
@@ -99,13 +110,18 @@ pub enum Expr {
         func: Fn,
         args: Vec<Expr>,
     },
+    /// Call to a user-defined function
+    Call {
+        func: Loc<Id>,
+        args: Vec<Expr>,
+    },
     Op {
         op: Op,
         left: Box<Expr>,
         right: Box<Expr>,
     },
     If {
-        cond: OrderConstraint<Box<Expr>>,
+        cond: Box<Prop<Expr>>,
         then: Box<Expr>,
         alt: Box<Expr>,
     },
@@ -152,6 +168,11 @@ impl Expr {
         Expr::App { func, args }
     }
 
+    /// Call to a user-defined function
+    pub fn call(func: Loc<Id>, args: Vec<Expr>) -> Self {
+        Expr::Call { func, args }
+    }
+
     pub fn op(op: Op, l: Expr, r: Expr) -> Self {
         match op {
             Op::Add => l + r,
@@ -170,14 +191,9 @@ impl Expr {
         }
     }
 
-    pub fn if_expr(cond: OrderConstraint<Expr>, then: Expr, alt: Expr) -> Self {
-        let cond = OrderConstraint {
-            left: Box::new(cond.left),
-            right: Box::new(cond.right),
-            op: cond.op,
-        };
+    pub fn if_expr(cond: Prop<Expr>, then: Expr, alt: Expr) -> Self {
         Expr::If {
-            cond,
+            cond: Box::new(cond),
             then: Box::new(then),
             alt: Box::new(alt),
         }
@@ -192,6 +208,10 @@ impl Expr {
                 func,
                 args: args.into_iter().map(|arg| arg.resolve(bind)).collect(),
             },
+            Expr::Call { func, args } => Expr::Call {
+                func,
+                args: args.into_iter().map(|arg| arg.resolve(bind)).collect(),
+            },
             Expr::Op { op, left, right } => {
                 let l = left.resolve(bind);
                 let r = right.resolve(bind);
@@ -204,17 +224,57 @@ impl Expr {
                 }
             }
             Expr::If { cond, then, alt } => {
-                // let OrderConstraint {left, right, op} = cond;
-                // let l = Box::new(left.resolve(bind));
-                // let r = Box::new(right.resolve(bind));
-                // let cond = OrderConstraint{left: l, right: r, op};
-                let cond = cond.resolve_expr(bind);
+                let cond = Box::new(cond.resolve_expr(bind));
                 let then = Box::new(then.resolve(bind));
                 let alt = Box::new(alt.resolve(bind));
                 Expr::If { cond, then, alt }
             }
         }
     }
+
+    /// Apply `f` to this expression and all its subexpressions
+    fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        f(self);
+        match self {
+            Expr::Concrete(_)
+            | Expr::Abstract(_)
+            | Expr::ParamAccess { .. } => {}
+            Expr::App { args, .. } | Expr::Call { args, .. } => {
+                args.iter().for_each(|a| a.walk(f))
+            }
+            Expr::Op { left, right, .. } => {
+                left.walk(f);
+                right.walk(f);
+            }
+            Expr::If { cond, then, alt } => {
+                cond.exprs().into_iter().for_each(|e| e.walk(f));
+                then.walk(f);
+                alt.walk(f);
+            }
+        }
+    }
+
+    /// Abstract variables used in this expression
+    pub fn vars(&self) -> Vec<&Loc<Id>> {
+        let mut vars = Vec::new();
+        self.walk(&mut |e| {
+            if let Expr::Abstract(v) = e {
+                vars.push(v)
+            }
+        });
+        vars
+    }
+
+    /// User-defined functions called in this expression
+    pub fn calls(&self) -> Vec<&Loc<Id>> {
+        let mut calls = Vec::new();
+        self.walk(&mut |e| {
+            if let Expr::Call { func, .. } = e {
+                calls.push(func)
+            }
+        });
+        calls
+    }
 }
 
 impl std::ops::Add for Expr {
@@ -348,6 +408,13 @@ impl ECtx {
                     args.iter().map(|arg| Self::Func.print(arg)).join(", ")
                 )
             }
+            Expr::Call { func, args } => {
+                format!(
+                    "{}({})",
+                    func,
+                    args.iter().map(|arg| Self::Func.print(arg)).join(", ")
+                )
+            }
             Expr::Op { op, left, right } => {
                 let inner = Self::from(*op);
                 let left = inner.print(left);
@@ -359,19 +426,9 @@ impl ECtx {
                 }
             }
             Expr::If { cond, then, alt } => {
-                let cond_l = self.print(&cond.left);
-                let cond_r = self.print(&cond.right);
-                let op = match &cond.op {
-                    OrderOp::Gt => ">",
-                    OrderOp::Gte => ">=",
-                    OrderOp::Eq => "=",
-                };
                 let then = self.print(then);
                 let alt = self.print(alt);
-                format!(
-                    "if {} {} {} {{{}}} else {{{}}}",
-                    cond_l, op, cond_r, then, alt
-                )
+                format!("if {} {{{}}} else {{{}}}", cond, then, alt)
             }
         }
     }
@@ -0,0 +1,877 @@
+//! Canonical formatter for Filament programs.
+//!
+//! The formatter works on the parse tree instead of the AST because the AST
+//! does not retain the exact syntax of the program (parentheses, port
+//! shorthands, etc.).
+//! Comments are not part of the parse tree. They are collected separately
+//! and attached to the line that precedes them if they start on the same line
+//! and are emitted on their own line otherwise.
+use crate::parser::{FilamentParser, Rule};
+use fil_utils::{self as utils, FilamentResult};
+use itertools::Itertools;
+use pest::{
+    Parser,
+    iterators::{Pair, Pairs},
+};
+use std::iter::Peekable;
+
+/// Number of spaces for each level of indentation
+const INDENT: usize = 2;
+
+/// Location of a comment in the source
+struct Comment {
+    start: usize,
+    end: usize,
+}
+
+/// Find all the comments in the source
+fn comments(src: &str) -> Vec<Comment> {
+    let mut comments = vec![];
+    let mut i = 0;
+    while i < src.len() {
+        let rest = &src[i..];
+        if let Some(str) = rest.strip_prefix('"') {
+            // Strings cannot contain escaped quotes
+            i = str.find('"').map_or(src.len(), |e| i + e + 2);
+        } else if rest.starts_with("//") {
+            let end = rest.find('\n').map_or(src.len(), |e| i + e);
+            comments.push(Comment { start: i, end });
+            i = end;
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            let end = body.find("*/").map_or(src.len(), |e| i + e + 4);
+            comments.push(Comment { start: i, end });
+            i = end;
+        } else {
+            i += rest.chars().next().unwrap().len_utf8();
+        }
+    }
+    comments
+}
+
+/// Formats Filament programs.
+/// ```text
+/// let formatted = Formatter::format("main.fil", &src)?;
+/// ```
+pub struct Formatter<'a> {
+    src: &'a str,
+    comments: Vec<Comment>,
+    /// Index of the next comment that needs to be emitted
+    next: usize,
+    /// Offset in the source up to which everything has been emitted
+    last: usize,
+    /// Emit a blank line before the next line
+    blank: bool,
+    indent: usize,
+    lines: Vec<String>,
+}
+
+impl<'a> Formatter<'a> {
+    /// Format the program in `src`. The `name` is only used in error messages.
+    pub fn format(name: &str, src: &'a str) -> FilamentResult<String> {
+        let mut file = FilamentParser::parse(Rule::file, src).map_err(|e| {
+            utils::Error::misc(format!("Failed to parse {}", e.with_path(name)))
+        })?;
+        let mut fmt = Formatter {
+            src,
+            comments: comments(src),
+            next: 0,
+            last: 0,
+            blank: false,
+            indent: 0,
+            lines: vec![],
+        };
+        fmt.file(file.next().unwrap());
+
+        let mut out = fmt.lines.join("\n");
+        out.push('\n');
+        // The formatter should only change whitespace and trailing commas
+        if significant(src) != significant(&out) {
+            return Err(utils::Error::misc(format!(
+                "Formatting {name} changed the meaning of the program. This is a bug in the formatter."
+            )));
+        }
+        Ok(out)
+    }
+
+    // ================ Layout ================
+
+    /// Source offset of the first `tok` at or after `pos` that is not in a
+    /// comment.
+    fn find(&self, mut pos: usize, tok: char) -> usize {
+        loop {
+            if let Some(c) =
+                self.comments.iter().find(|c| c.start <= pos && pos < c.end)
+            {
+                pos = c.end;
+                continue;
+            }
+            let Some(ch) = self.src[pos..].chars().next() else {
+                unreachable!("expected `{tok}' in a well-formed program")
+            };
+            if ch == tok {
+                return pos;
+            }
+            pos += ch.len_utf8();
+        }
+    }
+
+    /// Whether there are comments that have not been emitted before `pos`
+    fn has_comment(&self, pos: usize) -> bool {
+        self.comments.get(self.next).is_some_and(|c| c.start < pos)
+    }
+
+    /// Emit all the comments that start before `pos`
+    fn comments_before(&mut self, pos: usize) {
+        while let Some(&Comment { start, end }) =
+            self.comments.get(self.next).filter(|c| c.start < pos)
+        {
+            self.next += 1;
+            let text = self.src[start..end].trim_end().to_string();
+            let trailing = self.last <= start
+                && !self.src[self.last..start].contains('\n');
+            match self.lines.last_mut() {
+                Some(line) if trailing => {
+                    line.push(' ');
+                    line.push_str(&text);
+                }
+                _ => {
+                    let blank = self.blank_before(start);
+                    self.push(text, blank)
+                }
+            }
+            self.last = self.last.max(end);
+        }
+    }
+
+    /// Whether a blank line should be emitted before a line that starts at
+    /// `start` in the source. Blank lines in the source are preserved.
+    fn blank_before(&self, start: usize) -> bool {
+        let gap = &self.src[self.last.min(start)..start];
+        self.blank || gap.matches('\n').count() > 1
+    }
+
+    fn push(&mut self, text: String, blank: bool) {
+        let at_open = self.lines.last().is_none_or(|l| {
+            l.is_empty() || l.ends_with('{') || l.ends_with('(')
+        });
+        if blank && !at_open {
+            self.lines.push(String::new());
+        }
+        self.blank = false;
+        let indent = self.indent * INDENT;
+        self.lines.push(format!("{:indent$}{text}", ""));
+    }
+
+    /// Emit a line for the source in `start..end` after the comments before it
+    fn emit(&mut self, start: usize, end: usize, text: String) {
+        self.comments_before(start);
+        let blank = self.blank_before(start);
+        // Comments within a line are moved before it unless the line closes
+        // a block, in which case they are moved after it so that they stay
+        // outside the closed block.
+        let closing = text.starts_with('}') || text.starts_with(')');
+        if closing {
+            let opens = text.ends_with('{') || text.ends_with('(');
+            self.push(text, false);
+            self.indent += opens as usize;
+            self.moved_comments(end);
+            self.indent -= opens as usize;
+        } else {
+            let moved = self.moved_comments(end);
+            self.push(text, blank && !moved);
+        }
+        self.last = end;
+    }
+
+    /// Emit the comments before `end` on their own lines and return whether
+    /// there were any.
+    fn moved_comments(&mut self, end: usize) -> bool {
+        let mut moved = false;
+        while let Some(&Comment { start, end: c_end }) =
+            self.comments.get(self.next).filter(|c| c.start < end)
+        {
+            self.next += 1;
+            let comment = self.src[start..c_end].trim_end().to_string();
+            let blank = !moved && self.blank_before(start);
+            self.push(comment, blank);
+            moved = true;
+        }
+        moved
+    }
+
+    /// Emit an indented list of commands and the closing brace at `close`.
+    /// Assumes that the opening brace has already been emitted.
+    fn block(&mut self, mut cmds: Peekable<Pairs<'a, Rule>>, close: usize) {
+        if cmds.peek().is_none() && !self.has_comment(close) {
+            self.lines.last_mut().unwrap().push('}');
+            self.last = close + 1;
+            return;
+        }
+        self.body(cmds, close);
+        self.emit(close, close + 1, "}".to_string());
+    }
+
+    /// Emit an indented list of commands that ends at `close`
+    fn body(
+        &mut self,
+        cmds: impl Iterator<Item = Pair<'a, Rule>>,
+        close: usize,
+    ) {
+        self.indent += 1;
+        for cmd in cmds {
+            self.command(cmd);
+        }
+        self.comments_before(close);
+        self.indent -= 1;
+    }
+
+    fn file(&mut self, file: Pair<'a, Rule>) {
+        for part in file.into_inner() {
+            match part.as_rule() {
+                Rule::imports => {
+                    for imp in part.into_inner() {
+                        let end = self.find(imp.as_span().end(), ';') + 1;
+                        let text = format!("import {};", imp.as_str());
+                        self.emit(imp.as_span().start(), end, text);
+                    }
+                }
+                Rule::comp_or_ext => {
+                    let item = part.into_inner().next().unwrap();
+                    // Type and function definitions are only separated by
+                    // blank lines present in the source.
+                    self.blank = !matches!(
+                        item.as_rule(),
+                        Rule::type_def | Rule::fn_def
+                    ) && !self.lines.is_empty();
+                    match item.as_rule() {
+                        Rule::component => self.component(item),
+                        Rule::external | Rule::generate => self.external(item),
+                        Rule::type_def => {
+                            let span = item.as_span();
+                            let text = Self::type_def(item);
+                            self.emit(span.start(), span.end(), text);
+                        }
+                        Rule::fn_def => {
+                            let span = item.as_span();
+                            let text = Self::fn_def(item);
+                            self.emit(span.start(), span.end(), text);
+                        }
+                        r => unreachable!("unexpected rule: {r:?}"),
+                    }
+                }
+                _ => (),
+            }
+        }
+        self.comments_before(self.src.len());
+    }
+
+    fn component(&mut self, comp: Pair<'a, Rule>) {
+        let close = comp.as_span().end() - 1;
+        let mut inner = comp.into_inner();
+        let sig = inner.next().unwrap();
+        let open = self.find(sig.as_span().end(), '{');
+        self.signature(sig, " {", open);
+        self.block(inner.peekable(), close);
+    }
+
+    fn external(&mut self, ext: Pair<'a, Rule>) {
+        let start = ext.as_span().start();
+        let close = ext.as_span().end() - 1;
+        let header = match ext.as_rule() {
+            Rule::external => "extern".to_string(),
+            _ => format!(
+                "generate({}) using",
+                ext.clone().into_inner().next().unwrap().as_str()
+            ),
+        };
+        let mut inner = ext
+            .into_inner()
+            .skip_while(|p| p.as_rule() != Rule::string_lit);
+        let path = inner.next().unwrap();
+        let open = self.find(path.as_span().end(), '{');
+        self.emit(start, open + 1, format!("{header} {} {{", path.as_str()));
+        self.indent += 1;
+        for sig in inner {
+            let semi = self.find(sig.as_span().end(), ';');
+            self.signature(sig, ";", semi);
+        }
+        self.comments_before(close);
+        self.indent -= 1;
+        self.emit(close, close + 1, "}".to_string());
+    }
+
+    /// Emit a signature followed by the terminator `term` at `term_pos`
+    fn signature(&mut self, sig: Pair<'a, Rule>, term: &str, term_pos: usize) {
+        let mut inner = sig.clone().into_inner();
+        let attrs = inner.next().unwrap();
+        let mut start = sig.as_span().start();
+        if attrs.clone().into_inner().next().is_some() {
+            start = attrs.as_span().end();
+            self.emit(attrs.as_span().start(), start, Self::attributes(attrs));
+        }
+        let name = inner.next().unwrap();
+        let params = inner.next().unwrap();
+        let mut cur = format!("comp {}{}", name.as_str(), Self::params(params));
+        let mut next = inner.next().unwrap();
+        if next.as_rule() == Rule::abstract_var {
+            cur += &Self::abstract_var(next);
+            next = inner.next().unwrap();
+        }
+
+        // Inputs and outputs
+        let io_open = next.as_span().start();
+        let mut io = next.into_inner().peekable();
+        let ins = io.next_if(|p| p.as_rule() == Rule::ports);
+        let arrow = io.next().unwrap();
+        let outs = io.next();
+        match ins {
+            Some(ins) => {
+                start = self.ports(start, cur, io_open, ins);
+                cur = ")".to_string();
+            }
+            None => cur += "()",
+        }
+        cur += " -> ";
+        match outs {
+            Some(outs) => {
+                let open = self.find(arrow.as_span().end(), '(');
+                start = self.ports(start, cur, open, outs);
+                cur = ")".to_string();
+            }
+            None => cur += "()",
+        }
+
+        // Signature bindings
+        let binds = inner.next().unwrap();
+        if binds.clone().into_inner().next().is_some() {
+            let open = self.find(binds.as_span().start(), '{');
+            let close = binds.as_span().end() - 1;
+            self.emit(start, open + 1, format!("{cur} with {{"));
+            self.indent += 1;
+            for bind in binds.into_inner() {
+                let sp = bind.as_span();
+                self.emit(sp.start(), sp.end(), Self::sig_bind(bind));
+            }
+            self.comments_before(close);
+            self.indent -= 1;
+            start = close;
+            cur = "}".to_string();
+        }
+
+        cur += &Self::constraints(inner.next().unwrap());
+        cur += term;
+        self.emit(start, term_pos + 1, cur);
+    }
+
+    /// Emit the opening line `cur(`, one line for each port, and return the
+    /// position of the closing parenthesis.
+    fn ports(
+        &mut self,
+        start: usize,
+        cur: String,
+        open: usize,
+        ports: Pair<'a, Rule>,
+    ) -> usize {
+        let close = self.find(ports.as_span().end(), ')');
+        self.emit(start, open + 1, format!("{cur}("));
+        self.indent += 1;
+        for port in ports.into_inner() {
+            let sp = port.as_span();
+            self.emit(
+                sp.start(),
+                sp.end(),
+                format!("{},", Self::port_def(port)),
+            );
+        }
+        self.comments_before(close);
+        self.indent -= 1;
+        close
+    }
+
+    fn command(&mut self, cmd: Pair<'a, Rule>) {
+        let cmd = cmd.into_inner().next().unwrap();
+        let sp = cmd.as_span();
+        match cmd.as_rule() {
+            Rule::for_loop => {
+                let mut inner = cmd.into_inner();
+                let idx = inner.next().unwrap();
+                let lo = inner.next().unwrap();
+                let hi = inner.next().unwrap();
+                let body = inner.next().unwrap();
+                let open = self.find(hi.as_span().end(), '{');
+                let text = format!(
+                    "for {} in {}..{} {{",
+                    idx.as_str(),
+                    Self::expr(lo),
+                    Self::expr(hi)
+                );
+                self.emit(sp.start(), open + 1, text);
+                self.block(body.into_inner().peekable(), sp.end() - 1);
+            }
+            Rule::if_stmt => {
+                let mut inner = cmd.into_inner();
+                let cond = inner.next().unwrap();
+                let then = inner.next().unwrap();
+                let open = self.find(cond.as_span().end(), '{');
+                let text = format!("if {} {{", Self::prop(cond));
+                self.emit(sp.start(), open + 1, text);
+                match inner.next() {
+                    Some(alt) => {
+                        let close = self.find(then.as_span().end(), '}');
+                        self.body(then.into_inner(), close);
+                        let open = self.find(close + 1, '{');
+                        self.emit(close, open + 1, "} else {".to_string());
+                        self.block(alt.into_inner().peekable(), sp.end() - 1);
+                    }
+                    None => {
+                        self.block(then.into_inner().peekable(), sp.end() - 1)
+                    }
+                }
+            }
+            _ => self.emit(sp.start(), sp.end(), Self::simple_command(cmd)),
+        }
+    }
+
+    // ================ Single-line syntax ================
+
+    /// Commands that fit on a single line
+    fn simple_command(cmd: Pair<Rule>) -> String {
+        let rule = cmd.as_rule();
+        let mut inner = cmd.into_inner();
+        match rule {
+            Rule::bundle => {
+                format!("bundle {};", Self::bundle_def(inner.next().unwrap()))
+            }
+            Rule::instance => {
+                let name = inner.next().unwrap().as_str();
+                let comp = inner.next().unwrap().as_str();
+                let params =
+                    Self::list("[", inner.next().unwrap(), Self::expr, "]");
+                let mut out = format!("{name} := new {comp}{params}");
+                for part in inner {
+                    match part.as_rule() {
+                        Rule::invoke_args => out += &Self::invoke_args(part),
+                        Rule::inst_live => {
+                            let lives = part
+                                .into_inner()
+                                .map(Self::interval)
+                                .join(", ");
+                            if !lives.is_empty() {
+                                out += &format!(" in {lives}");
+                            }
+                        }
+                        r => unreachable!("unexpected rule: {r:?}"),
+                    }
+                }
+                out + ";"
+            }
+            Rule::invocation => {
+                let name = inner.next().unwrap().as_str();
+                let inst = inner.next().unwrap().as_str();
+                let args = Self::invoke_args(inner.next().unwrap());
+                format!("{name} := {inst}{args};")
+            }
+            Rule::connect => {
+                let dst = Self::port(inner.next().unwrap());
+                let src = Self::port(inner.next().unwrap());
+                format!("{dst} = {src};")
+            }
+            Rule::fact => {
+                let kind = inner.next().unwrap().as_str();
+                let imp = inner
+                    .next()
+                    .unwrap()
+                    .into_inner()
+                    .map(Self::prop)
+                    .join(" => ");
+                format!("{kind} {imp};")
+            }
+            Rule::param_let => {
+                let name = inner.next().unwrap().as_str();
+                let val = inner.next().map_or("?".to_string(), Self::expr);
+                format!("let {name} = {val};")
+            }
+            Rule::exists => {
+                let name = inner.next().unwrap().as_str();
+                format!("{name} := {};", Self::expr(inner.next().unwrap()))
+            }
+            r => unreachable!("unexpected command: {r:?}"),
+        }
+    }
+
+    /// Format the children of `p` as a comma-separated list surrounded by
+    /// `open` and `close`. Returns an empty string if there are no children.
+    fn list(
+        open: &str,
+        p: Pair<Rule>,
+        f: impl Fn(Pair<Rule>) -> String,
+        close: &str,
+    ) -> String {
+        let items = p.into_inner().map(f).join(", ");
+        if items.is_empty() {
+            items
+        } else {
+            format!("{open}{items}{close}")
+        }
+    }
+
+    fn attributes(attrs: Pair<Rule>) -> String {
+        Self::list(
+            "#[",
+            attrs,
+            |attr| {
+                let mut inner = attr.into_inner();
+                let first = inner.next().unwrap();
+                match (first.as_rule(), inner.next()) {
+                    (Rule::not, Some(name)) => {
+                        format!("not({})", name.as_str())
+                    }
+                    (_, Some(val)) => {
+                        format!("{}={}", first.as_str(), val.as_str())
+                    }
+                    (_, None) => first.as_str().to_string(),
+                }
+            },
+            "]",
+        )
+    }
+
+    fn params(params: Pair<Rule>) -> String {
+        Self::list(
+            "[",
+            params,
+            |bind| {
+                let mut inner = bind.into_inner();
+                let name = inner.next().unwrap().as_str();
+                match inner.next() {
+                    Some(default) => format!("?{name}={}", Self::expr(default)),
+                    None => name.to_string(),
+                }
+            },
+            "]",
+        )
+    }
+
+    fn abstract_var(events: Pair<Rule>) -> String {
+        Self::list(
+            "<",
+            events,
+            |bind| {
+                let mut inner = bind.into_inner();
+                let event = Self::event(inner.next().unwrap());
+                let delay = inner.next().unwrap();
+                let mut parts = delay.into_inner();
+                let first = parts.next().unwrap();
+                let delay = match parts.next() {
+                    Some(end) => {
+                        format!("{}-({})", Self::time(first), Self::time(end))
+                    }
+                    None => Self::expr(first),
+                };
+                match inner.next() {
+                    Some(default) => {
+                        format!("?{event}: {delay}={}", Self::time(default))
+                    }
+                    None => format!("{event}: {delay}"),
+                }
+            },
+            ">",
+        )
+    }
+
+    fn sig_bind(bind: Pair<Rule>) -> String {
+        let mut inner = bind.into_inner();
+        let first = inner.next().unwrap();
+        match first.as_rule() {
+            Rule::param_var => {
+                let val = Self::expr(inner.next().unwrap());
+                format!("let {} = {val};", first.as_str())
+            }
+            _ => {
+                let name = inner.next().unwrap().as_str();
+                let cons = Self::constraints(inner.next().unwrap());
+                format!("{} {name}{cons};", first.as_str())
+            }
+        }
+    }
+
+    fn constraints(cons: Pair<Rule>) -> String {
+        let cons = cons
+            .into_inner()
+            .map(|c| {
+                let mut inner = c.into_inner();
+                let l = inner.next().unwrap();
+                match l.as_rule() {
+                    Rule::time => {
+                        let (op, r) =
+                            (inner.next().unwrap(), inner.next().unwrap());
+                        format!(
+                            "{} {} {}",
+                            Self::time(l),
+                            op.as_str(),
+                            Self::time(r)
+                        )
+                    }
+                    _ => Self::prop(l),
+                }
+            })
+            .join(", ");
+        if cons.is_empty() {
+            cons
+        } else {
+            format!(" where {cons}")
+        }
+    }
+
+    fn port_def(port: Pair<Rule>) -> String {
+        let mut inner = port.into_inner();
+        let first = inner.next().unwrap();
+        match (first.as_rule(), inner.next()) {
+            (Rule::bundle_def, _) => Self::bundle_def(first),
+            (_, Some(width)) if width.as_rule() == Rule::bitwidth => {
+                format!("{}: {}", first.as_str(), width.as_str())
+            }
+            (_, Some(interface)) => {
+                let event = interface.into_inner().next().unwrap();
+                format!("{}: interface[{}]", first.as_str(), Self::event(event))
+            }
+            (r, None) => unreachable!("unexpected port: {r:?}"),
+        }
+    }
+
+    fn bundle_def(def: Pair<Rule>) -> String {
+        let mut out = String::new();
+        for part in def.into_inner() {
+            match part.as_rule() {
+                Rule::attributes => {
+                    let attrs = Self::attributes(part);
+                    if !attrs.is_empty() {
+                        out += &format!("{attrs} ");
+                    }
+                }
+                Rule::identifier => out += part.as_str(),
+                Rule::expr => out += &format!("[{}]", Self::expr(part)),
+                Rule::bundle_typ => {
+                    out += ": ";
+                    for typ in part.into_inner() {
+                        match typ.as_rule() {
+                            Rule::bundle_params => {
+                                out += &Self::list(
+                                    "for<",
+                                    typ,
+                                    |p| p.as_str().to_string(),
+                                    "> ",
+                                )
+                            }
+                            Rule::interval_range => {
+                                out += &format!("{} ", Self::interval(typ))
+                            }
+                            _ => out += &Self::port_typ(typ),
+                        }
+                    }
+                }
+                r => unreachable!("unexpected rule: {r:?}"),
+            }
+        }
+        out
+    }
+
+    fn port_typ(typ: Pair<Rule>) -> String {
+        let mut parts = typ.into_inner();
+        let inner = parts.next().unwrap();
+        match inner.as_rule() {
+            Rule::type_app => {
+                let mut parts = inner.into_inner();
+                let name = parts.next().unwrap().as_str();
+                format!("{name}[{}]", parts.map(Self::expr).join(", "))
+            }
+            Rule::fixed => {
+                format!(
+                    "fixed<{}>",
+                    inner.into_inner().map(Self::expr).join(", ")
+                )
+            }
+            Rule::sign => {
+                format!(
+                    "{} {}",
+                    inner.as_str(),
+                    Self::expr(parts.next().unwrap())
+                )
+            }
+            _ => Self::expr(inner),
+        }
+    }
+
+    fn type_def(def: Pair<Rule>) -> String {
+        let mut inner = def.into_inner();
+        let mut out = format!("type {}", inner.next().unwrap().as_str());
+        for part in inner {
+            match part.as_rule() {
+                Rule::type_params => {
+                    out +=
+                        &Self::list("[", part, |p| p.as_str().to_string(), "]")
+                }
+                Rule::record => {
+                    let mut fields = part.into_inner().map(|f| {
+                        let mut f = f.into_inner();
+                        let name = f.next().unwrap().as_str();
+                        format!("{name}: {}", Self::port_typ(f.next().unwrap()))
+                    });
+                    out += &format!(" = {{ {} }}", fields.join(", "));
+                }
+                Rule::variants => {
+                    let mut vars = part.into_inner().map(|v| v.as_str());
+                    out += &format!(" = enum {{ {} }}", vars.join(", "));
+                }
+                r => unreachable!("unexpected rule: {r:?}"),
+            }
+        }
+        out + ";"
+    }
+
+    fn fn_def(def: Pair<Rule>) -> String {
+        let mut inner = def.into_inner();
+        let name = inner.next().unwrap().as_str();
+        let mut params = inner.next().unwrap().into_inner().map(|p| p.as_str());
+        let body = Self::expr(inner.next().unwrap());
+        format!("fn {name}({}) = {body};", params.join(", "))
+    }
+
+    fn invoke_args(args: Pair<Rule>) -> String {
+        let mut inner = args.into_inner();
+        let times = Self::list("<", inner.next().unwrap(), Self::time, ">");
+        let ports = inner
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(Self::port)
+            .join(", ");
+        format!("{times}({ports})")
+    }
+
+    fn port(port: Pair<Rule>) -> String {
+        let mut out = String::new();
+        for (i, part) in port.into_inner().enumerate() {
+            match part.as_rule() {
+                Rule::access => {
+                    let acc = part
+                        .into_inner()
+                        .map(|p| match p.as_rule() {
+                            Rule::dots => "..".to_string(),
+                            _ => Self::expr(p),
+                        })
+                        .join("");
+                    out += &format!("{{{acc}}}");
+                }
+                _ if i > 0 => out += &format!(".{}", part.as_str()),
+                _ => out += part.as_str(),
+            }
+        }
+        out
+    }
+
+    fn interval(range: Pair<Rule>) -> String {
+        format!("[{}]", range.into_inner().map(Self::time).join(", "))
+    }
+
+    fn event(event: Pair<Rule>) -> String {
+        event.into_inner().map(|p| p.as_str()).join("")
+    }
+
+    fn time(time: Pair<Rule>) -> String {
+        time.into_inner()
+            .map(|p| match p.as_rule() {
+                Rule::event => Self::event(p),
+                _ => Self::expr(p),
+            })
+            .join("+")
+    }
+
+    fn expr_cmp(cmp: Pair<Rule>) -> String {
+        let mut inner = cmp.into_inner();
+        let l = Self::expr(inner.next().unwrap());
+        let op = inner.next().unwrap();
+        let r = Self::expr(inner.next().unwrap());
+        format!("{l} {} {r}", op.as_str())
+    }
+
+    fn prop(prop: Pair<Rule>) -> String {
+        match prop.as_rule() {
+            Rule::prop => prop.into_inner().map(Self::prop).join(" || "),
+            Rule::prop_and => prop.into_inner().map(Self::prop).join(" && "),
+            Rule::prop_not => {
+                format!("!{}", Self::prop(prop.into_inner().next().unwrap()))
+            }
+            Rule::prop_base => {
+                let inner = prop.into_inner().next().unwrap();
+                match inner.as_rule() {
+                    Rule::prop => format!("({})", Self::prop(inner)),
+                    _ => Self::prop(inner),
+                }
+            }
+            _ => Self::expr_cmp(prop),
+        }
+    }
+
+    fn expr(expr: Pair<Rule>) -> String {
+        expr.into_inner()
+            .map(|p| match p.as_rule() {
+                Rule::expr_base => Self::expr_base(p),
+                _ => p.as_str().to_string(),
+            })
+            .join("")
+    }
+
+    fn expr_base(base: Pair<Rule>) -> String {
+        let mut inner = base.into_inner();
+        let first = inner.next().unwrap();
+        match first.as_rule() {
+            Rule::if_expr => {
+                let mut inner = first.into_inner();
+                let cond = Self::prop(inner.next().unwrap());
+                let t = Self::expr(inner.next().unwrap());
+                let f = Self::expr(inner.next().unwrap());
+                format!("if {cond} {{ {t} }} else {{ {f} }}")
+            }
+            Rule::builtin_fn | Rule::user_fn => {
+                format!(
+                    "{}({})",
+                    first.as_str(),
+                    inner.map(Self::expr).join(", ")
+                )
+            }
+            Rule::expr => format!("({})", Self::expr(first)),
+            Rule::identifier => match inner.next() {
+                Some(param) => {
+                    format!("{}::{}", first.as_str(), param.as_str())
+                }
+                None => first.as_str().to_string(),
+            },
+            _ => first.as_str().to_string(),
+        }
+    }
+}
+
+/// The significant parts of a program, which the formatter must preserve.
+/// Removes comments, whitespace, and trailing commas in port lists and types.
+fn significant(src: &str) -> (String, Vec<&str>) {
+    let comments = comments(src);
+    let mut code = String::new();
+    let mut prev = 0;
+    for c in &comments {
+        code.push_str(&src[prev..c.start]);
+        prev = c.end;
+    }
+    code.push_str(&src[prev..]);
+    let code = code
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .replace(",)", ")")
+        .replace(",}", "}");
+    let comments = comments
+        .iter()
+        .map(|c| src[c.start..c.end].trim_end())
+        .collect();
+    (code, comments)
+}
@@ -0,0 +1,33 @@
+use super::{Expr, Id, Loc};
+use fil_utils::GPosIdx;
+
+#[derive(Clone)]
+/// A user-defined function over parameters:
+/// ```fil
+/// fn ceil_div(a, b) = (a + b - 1) / b;
+/// ```
+/// Calls to the function are replaced with its body when the program is
+/// converted to the IR.
+pub struct FnDef {
+    pub name: Loc<Id>,
+    pub params: Vec<Loc<Id>>,
+    pub body: Loc<Expr>,
+    /// Location of the entire definition
+    pub pos: GPosIdx,
+}
+
+impl FnDef {
+    pub fn new(
+        name: Loc<Id>,
+        params: Vec<Loc<Id>>,
+        body: Loc<Expr>,
+        pos: GPosIdx,
+    ) -> Self {
+        Self {
+            name,
+            params,
+            body,
+            pos,
+        }
+    }
+}
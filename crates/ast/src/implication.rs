@@ -1,11 +1,11 @@
-use super::{Binding, Expr, OrderConstraint};
+use super::{Binding, Expr, OrderConstraint, Prop};
 use itertools::Itertools;
 
 /// A type representing the expression a => b
 #[derive(Clone)]
 pub struct Implication<T> {
-    pub guard: Option<OrderConstraint<T>>,
-    pub cons: OrderConstraint<T>,
+    pub guard: Option<Prop<T>>,
+    pub cons: Prop<T>,
 }
 
 impl<T> Implication<T>
@@ -13,23 +13,17 @@ where
     T: Clone,
 {
     /// Creates an [Implication] `guard => cons`
-    pub fn implies(
-        guard: OrderConstraint<T>,
-        cons: OrderConstraint<T>,
-    ) -> Self {
+    pub fn implies(guard: Prop<T>, cons: Prop<T>) -> Self {
         Implication::new_opt(Some(guard), cons)
     }
 
     /// Creates an [Implication] with no guard, something that must always be true
-    pub fn fact(cons: OrderConstraint<T>) -> Self {
+    pub fn fact(cons: Prop<T>) -> Self {
         Implication::new_opt(None, cons)
     }
 
     /// Creates a pair of [Implication]s representing an iff (`a <=> b`) clause
-    pub fn iff(
-        left: OrderConstraint<T>,
-        right: OrderConstraint<T>,
-    ) -> [Self; 2] {
+    pub fn iff(left: Prop<T>, right: Prop<T>) -> [Self; 2] {
         [
             Implication::implies(left.clone(), right.clone()),
             Implication::implies(right, left),
@@ -37,10 +31,7 @@ where
     }
 
     /// Creates a new [Implication] with an optional guard
-    fn new_opt(
-        guard: Option<OrderConstraint<T>>,
-        cons: OrderConstraint<T>,
-    ) -> Self {
+    fn new_opt(guard: Option<Prop<T>>, cons: Prop<T>) -> Self {
         Implication { guard, cons }
     }
 }
@@ -69,6 +60,6 @@ where
     T: Clone,
 {
     fn from(cons: OrderConstraint<T>) -> Self {
-        Implication::fact(cons)
+        Implication::fact(cons.into())
     }
 }
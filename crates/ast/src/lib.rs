@@ -3,6 +3,8 @@ mod component;
 mod constraint;
 mod control;
 mod expr;
+mod format;
+mod function;
 mod implication;
 mod interval;
 mod loc;
@@ -10,16 +12,19 @@ mod parser;
 mod port;
 mod signature;
 mod time;
+mod typedef;
 
 pub use bind_map::Binding;
 pub use component::{Component, Extern, Namespace};
-pub use constraint::{Constraint, OrderConstraint, OrderOp};
+pub use constraint::{Constraint, OrderConstraint, OrderOp, Prop};
 pub use control::{
     Access, Bundle, BundleType, Command, Connect, Exists, Fact, ForLoop, If,
     Instance, Invoke, ParamLet, Port, PortRef,
 };
 pub use expr::{Expr, Fn, Op};
 pub use fil_utils::Id;
+pub use format::Formatter;
+pub use function::FnDef;
 pub use implication::Implication;
 pub use interval::Range;
 pub use loc::Loc;
@@ -27,3 +32,4 @@ pub use parser::FilamentParser;
 pub use port::{InterfaceDef, PortDef};
 pub use signature::{EventBind, ParamBind, SigBind, Signature};
 pub use time::{Time, TimeSub};
+pub use typedef::{Field, Interp, TypeApp, TypeBody, TypeDef};
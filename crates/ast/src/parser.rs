@@ -44,11 +44,13 @@ lazy_static::lazy_static! {
 pub enum BodyEl {
     Ext(Box<ast::Extern>),
     Comp(Box<ast::Component>),
+    Type(Box<ast::TypeDef>),
+    Fn(Box<ast::FnDef>),
 }
 
 #[derive(Clone)]
 pub enum FCons {
-    ExprC(ast::OrderConstraint<ast::Expr>),
+    ExprC(ast::Prop<ast::Expr>),
     TimeC(ast::OrderConstraint<ast::Time>),
 }
 
@@ -108,6 +110,30 @@ impl FilamentParser {
         Ok(out)
     }
 
+    /// Parse a single signature that is optionally followed by `;`.
+    /// `name` identifies the source of the signature in error messages.
+    pub fn parse_signature(
+        name: String,
+        src: String,
+    ) -> FilamentResult<ast::Signature> {
+        let file = GlobalPositionTable::get().add_file(name.clone(), src);
+        let user_data = UserData { file };
+        let (_, content) = GlobalPositionTable::get().get_file_data(file);
+        let err = |e: Error<Rule>| {
+            utils::Error::misc(format!(
+                "Failed to parse signature: {}",
+                e.with_path(&name)
+            ))
+        };
+        let inputs = FilamentParser::parse_with_userdata(
+            Rule::sig_def,
+            content,
+            user_data,
+        )
+        .map_err(err)?;
+        FilamentParser::sig_def(inputs.single().map_err(err)?).map_err(err)
+    }
+
     fn get_span(node: &Node) -> GPosIdx {
         let ud = node.user_data();
         let sp = node.as_span();
@@ -116,6 +142,18 @@ impl FilamentParser {
         GPosIdx(pos)
     }
 
+    /// Span of an external or generate definition up to the path of the
+    /// definition
+    fn extern_header(node: &Node) -> GPosIdx {
+        let ud = node.user_data();
+        let sp = node.as_span();
+        let end = node
+            .children()
+            .find(|c| c.as_rule() == Rule::string_lit)
+            .map_or(sp.end(), |c| c.as_span().end());
+        GPosIdx(GlobalPositionTable::get().add_pos(ud.file, sp.start(), end))
+    }
+
     #[allow(clippy::result_large_err)]
     fn expr_helper(
         ud: UserData,
@@ -274,23 +312,12 @@ impl FilamentParser {
     }
 
     fn builtin_fn(input: Node) -> ParseResult<ast::Fn> {
-        Ok(match input.as_str() {
-            "pow2" => ast::Fn::Pow2,
-            "log2" => ast::Fn::Log2,
-            "sin_bits" => ast::Fn::SinB,
-            "cos_bits" => ast::Fn::CosB,
-            "bit_rev" => ast::Fn::BitRev,
-            _ => unreachable!(),
-        })
-    }
-    fn unknown_fn(input: Node) -> ParseResult<ast::Fn> {
-        Err(input.error("Unknown function"))
+        Ok(ast::Fn::from_name(input.as_str()).unwrap())
     }
-    fn r#fn(input: Node) -> ParseResult<ast::Fn> {
+    fn user_fn(input: Node) -> ParseResult<Loc<ast::Id>> {
         Ok(match_nodes!(
             input.into_children();
-            [builtin_fn(f)] => f,
-            [unknown_fn(_)] => unreachable!(),
+            [identifier(name)] => name,
         ))
     }
 
@@ -301,7 +328,8 @@ impl FilamentParser {
             [if_expr(e)] => e,
             [param_var(id)] => ast::Expr::abs(id),
             [bitwidth(c)] => c.into(),
-            [r#fn(f), expr(exprs)..] => ast::Expr::func(f, exprs.into_iter().map(|e| e.take()).collect()),
+            [builtin_fn(f), expr(exprs)..] => ast::Expr::func(f, exprs.into_iter().map(|e| e.take()).collect()),
+            [user_fn(f), expr(exprs)..] => ast::Expr::call(f, exprs.into_iter().map(|e| e.take()).collect()),
             [expr(e)] => e.take(),
         ))
     }
@@ -309,7 +337,7 @@ impl FilamentParser {
     fn if_expr(input: Node) -> ParseResult<ast::Expr> {
         Ok(match_nodes!(
             input.clone().into_children();
-            [expr_cmp(cond), expr(then), expr(alt)] => ast::Expr::if_expr(cond, then.take(), alt.take()),
+            [prop(cond), expr(then), expr(alt)] => ast::Expr::if_expr(cond, then.take(), alt.take()),
         ))
     }
 
@@ -482,15 +510,31 @@ impl FilamentParser {
 
     fn port(input: Node) -> ParseResult<Loc<ast::Port>> {
         let sp = Self::get_span(&input);
-        match_nodes!(
-            input.clone().into_children();
-            [bitwidth(_)] => Err(input.error("constant ports are not supported. Use the `Const[Width, Val]' primitive instead.")),
-            [identifier(name)] => Ok(Loc::new(ast::Port::this(name), sp)),
-            [identifier(name), access(range)..] => Ok(Loc::new(ast::Port::bundle(name, range.collect()), sp)),
-            [identifier(comp), identifier(name)] => Ok(Loc::new(ast::Port::inv_port(comp, name), sp)),
-            [identifier(invoke), identifier(port), access(access)..] => Ok(Loc::new(ast::Port::inv_bundle(invoke, port, access.collect()), sp)),
-            [identifier(name), expr(idx)] => Ok(Loc::new(ast::Port::bundle(name, vec![idx.map(|x| x.into())]), sp)),
-        )
+        let mut names = vec![];
+        let mut access = vec![];
+        let mut fields = vec![];
+        for node in input.clone().into_children() {
+            match node.as_rule() {
+                Rule::bitwidth => {
+                    return Err(input.error("constant ports are not supported. Use the `Const[Width, Val]' primitive instead."));
+                }
+                Rule::access => access.push(Self::access(node)?),
+                // Names after an access are fields of the port
+                Rule::identifier if access.is_empty() => {
+                    names.push(Self::identifier(node)?)
+                }
+                Rule::identifier => fields.push(Self::identifier(node)?),
+                r => unreachable!("unexpected rule in port: {r:?}"),
+            }
+        }
+        let mut names = names.into_iter();
+        let first = names.next().unwrap();
+        let port = match names.next() {
+            Some(port) => ast::Port::inv_bundle(first, port, access),
+            None => ast::Port::bundle(first, access),
+        };
+        fields.splice(0..0, names);
+        Ok(Loc::new(port.with_fields(fields), sp))
     }
 
     fn arguments(input: Node) -> ParseResult<Vec<Loc<ast::Port>>> {
@@ -575,18 +619,7 @@ impl FilamentParser {
                 };
                 Ok(Loc::new(FCons::TimeC(con), sp))
             },
-            [
-                expr(l),
-                order_op((op, rev)),
-                expr(r)
-            ] => {
-                let con = if !rev {
-                    ast::OrderConstraint::new(l.take(), r.take(), op)
-                } else {
-                    ast::OrderConstraint::new(r.take(), l.take(), op)
-                };
-                Ok(Loc::new(FCons::ExprC(con), sp))
-            }
+            [prop(con)] => Ok(Loc::new(FCons::ExprC(con), sp))
         )
     }
 
@@ -594,7 +627,7 @@ impl FilamentParser {
     fn constraints(
         input: Node,
     ) -> ParseResult<(
-        Vec<Loc<ast::OrderConstraint<ast::Expr>>>,
+        Vec<Loc<ast::Prop<ast::Expr>>>,
         Vec<Loc<ast::OrderConstraint<ast::Time>>>,
     )> {
         Ok(match_nodes!(
@@ -706,8 +739,38 @@ impl FilamentParser {
     fn if_stmt(input: Node) -> ParseResult<ast::If> {
         Ok(match_nodes!(
             input.into_children();
-            [expr_cmp(cond), commands(then), commands(else_)] => ast::If::new(cond, then, else_),
-            [expr_cmp(cond), commands(then)] => ast::If::new(cond, then, vec![])
+            [prop(cond), commands(then), commands(else_)] => ast::If::new(cond, then, else_),
+            [prop(cond), commands(then)] => ast::If::new(cond, then, vec![])
+        ))
+    }
+
+    fn prop_not(input: Node) -> ParseResult<ast::Prop<ast::Expr>> {
+        Ok(match_nodes!(
+            input.into_children();
+            [prop_base(p)] => !p
+        ))
+    }
+
+    fn prop_base(input: Node) -> ParseResult<ast::Prop<ast::Expr>> {
+        Ok(match_nodes!(
+            input.into_children();
+            [prop_not(p)] => p,
+            [prop(p)] => p,
+            [expr_cmp(c)] => c.into()
+        ))
+    }
+
+    fn prop_and(input: Node) -> ParseResult<ast::Prop<ast::Expr>> {
+        Ok(match_nodes!(
+            input.into_children();
+            [prop_base(p), prop_base(rest)..] => rest.fold(p, |acc, p| acc.and(p))
+        ))
+    }
+
+    fn prop(input: Node) -> ParseResult<ast::Prop<ast::Expr>> {
+        Ok(match_nodes!(
+            input.into_children();
+            [prop_and(p), prop_and(rest)..] => rest.fold(p, |acc, p| acc.or(p))
         ))
     }
 
@@ -723,7 +786,7 @@ impl FilamentParser {
     fn bundle_def(input: Node) -> ParseResult<ast::Bundle> {
         match_nodes!(
             input.clone().into_children();
-            [attributes(attrs), identifier(name), expr(sizes).., bundle_typ((params, range, width))] => {
+            [attributes(attrs), identifier(name), expr(sizes).., bundle_typ((params, range, width, record, interp))] => {
                 let sizes = sizes.collect_vec();
                 // If no size is specified, treat this is as one dimensional bundle with size 1.
                 let (sizes, s_len) = if sizes.is_empty() {
@@ -743,7 +806,10 @@ impl FilamentParser {
                     params.push(Loc::unknown(ast::Id::from(format!("_{i}"))));
                 });
 
-                Ok(ast::Bundle::new(name, ast::BundleType::new(params, sizes, range, width), attrs))
+                let mut typ = ast::BundleType::new(params, sizes, range, width);
+                typ.record = record;
+                typ.interp = interp;
+                Ok(ast::Bundle::new(name, typ, attrs))
             }
         )
     }
@@ -755,13 +821,69 @@ impl FilamentParser {
         ))
     }
 
+    fn type_app(input: Node) -> ParseResult<Loc<ast::TypeApp>> {
+        let sp = Self::get_span(&input);
+        Ok(match_nodes!(
+            input.into_children();
+            [identifier(name), expr(args)..] => Loc::new(ast::TypeApp::new(name, args.collect()), sp),
+        ))
+    }
+
+    fn sign(input: Node) -> ParseResult<Loc<ast::Interp>> {
+        let sp = Self::get_span(&input);
+        let interp = match input.as_str() {
+            "signed" => ast::Interp::Signed,
+            "unsigned" => ast::Interp::Unsigned,
+            s => unreachable!("unknown sign: {s}"),
+        };
+        Ok(Loc::new(interp, sp))
+    }
+
+    /// A fixed-point interpretation along with the bitwidth it implies.
+    fn fixed(input: Node) -> ParseResult<(Loc<ast::Interp>, Loc<ast::Expr>)> {
+        let sp = Self::get_span(&input);
+        Ok(match_nodes!(
+            input.into_children();
+            [expr(int), expr(frac)] => {
+                let width = Loc::new(int.inner().clone() + frac.inner().clone(), sp);
+                (Loc::new(ast::Interp::Fixed { int, frac }, sp), width)
+            }
+        ))
+    }
+
+    /// The bitwidth of a port. Record types with arguments also return the
+    /// name of the type as the bitwidth.
+    #[allow(clippy::type_complexity)]
+    fn port_typ(
+        input: Node,
+    ) -> ParseResult<(
+        Loc<ast::Expr>,
+        Option<Loc<ast::TypeApp>>,
+        Option<Loc<ast::Interp>>,
+    )> {
+        Ok(match_nodes!(
+            input.into_children();
+            [expr(width)] => (width, None, None),
+            [sign(interp), expr(width)] => (width, None, Some(interp)),
+            [fixed((interp, width))] => (width, None, Some(interp)),
+            [type_app(app)] => (Loc::new(ast::Expr::abs(app.name.clone()), app.name.pos()), Some(app), None),
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
     fn bundle_typ(
         input: Node,
-    ) -> ParseResult<(Vec<Loc<ast::Id>>, Loc<ast::Range>, Loc<ast::Expr>)> {
+    ) -> ParseResult<(
+        Vec<Loc<ast::Id>>,
+        Loc<ast::Range>,
+        Loc<ast::Expr>,
+        Option<Loc<ast::TypeApp>>,
+        Option<Loc<ast::Interp>>,
+    )> {
         Ok(match_nodes!(
             input.into_children();
-            [bundle_params(params), interval_range(range), expr(width)] => (params, range, width),
-            [interval_range(range), expr(width)] => (vec![Loc::unknown(ast::Id::from("_"))], range, width),
+            [bundle_params(params), interval_range(range), port_typ((width, record, interp))] => (params, range, width, record, interp),
+            [interval_range(range), port_typ((width, record, interp))] => (vec![Loc::unknown(ast::Id::from("_"))], range, width, record, interp),
         ))
     }
 
@@ -775,8 +897,8 @@ impl FilamentParser {
     fn implication(input: Node) -> ParseResult<ast::Implication<ast::Expr>> {
         Ok(match_nodes!(
             input.into_children();
-            [expr_cmp(guard), expr_cmp(e)] => ast::Implication::implies(guard, e),
-            [expr_cmp(e)] => ast::Implication::fact(e)
+            [prop(guard), prop(e)] => ast::Implication::implies(guard, e),
+            [prop(e)] => ast::Implication::fact(e)
         ))
     }
 
@@ -880,27 +1002,104 @@ impl FilamentParser {
     }
 
     fn external(input: Node) -> ParseResult<ast::Extern> {
+        let sp = Self::extern_header(&input);
         Ok(match_nodes!(
             input.into_children();
-            [string_lit(path), signature(sigs)..] => ast::Extern::new(path, sigs.collect(), None),
+            [string_lit(path), signature(sigs)..] => ast::Extern::new(path, sigs.collect(), None, sp),
         ))
     }
 
     fn generate(input: Node) -> ParseResult<ast::Extern> {
+        let sp = Self::extern_header(&input);
         Ok(match_nodes!(
             input.into_children();
             [identifier(name), string_lit(path), signature(sigs)..] => {
-                ast::Extern::new(path, sigs.collect(), Some(name.to_string()))
+                ast::Extern::new(path, sigs.collect(), Some(name.to_string()), sp)
             }
         ))
     }
 
+    fn type_params(input: Node) -> ParseResult<Vec<Loc<ast::Id>>> {
+        Ok(match_nodes!(
+            input.into_children();
+            [param_var(params)..] => params.collect(),
+        ))
+    }
+
+    fn field(input: Node) -> ParseResult<ast::Field> {
+        Ok(match_nodes!(
+            input.into_children();
+            [identifier(name), port_typ((width, record, interp))] => ast::Field { name, width, record, interp },
+        ))
+    }
+
+    fn record(input: Node) -> ParseResult<ast::TypeBody> {
+        Ok(match_nodes!(
+            input.into_children();
+            [field(fields)..] => ast::TypeBody::Record(fields.collect()),
+        ))
+    }
+
+    fn variants(input: Node) -> ParseResult<ast::TypeBody> {
+        Ok(match_nodes!(
+            input.into_children();
+            [identifier(vars)..] => ast::TypeBody::Enum(vars.collect()),
+        ))
+    }
+
+    fn type_def(input: Node) -> ParseResult<ast::TypeDef> {
+        match_nodes!(
+            input.clone().into_children();
+            [identifier(name), type_params(params), record(body)] => Ok(ast::TypeDef::new(name, params, body)),
+            [identifier(name), record(body)] => Ok(ast::TypeDef::new(name, vec![], body)),
+            [identifier(name), variants(body)] => Ok(ast::TypeDef::new(name, vec![], body)),
+            [identifier(_), type_params(_), variants(_)] => Err(input.error("enum types cannot have parameters")),
+        )
+    }
+
+    fn fn_params(input: Node) -> ParseResult<Vec<Loc<ast::Id>>> {
+        Ok(match_nodes!(
+            input.into_children();
+            [param_var(params)..] => params.collect(),
+        ))
+    }
+
+    fn fn_def(input: Node) -> ParseResult<ast::FnDef> {
+        let (name, params, body) = match_nodes!(
+            input.clone().into_children();
+            [identifier(name), fn_params(params), expr(body)] => (name, params, body),
+        );
+        if ast::Fn::from_name(name.as_ref()).is_some() {
+            return Err(input
+                .error(format!("Cannot redefine builtin function `{name}'")));
+        }
+        if let Some(dup) = params
+            .iter()
+            .enumerate()
+            .find(|(i, p)| params[..*i].contains(p))
+            .map(|(_, p)| p)
+        {
+            return Err(input.error(format!(
+                "Parameter `{dup}' of function `{name}' is defined multiple times"
+            )));
+        }
+        if let Some(var) = body.vars().into_iter().find(|v| !params.contains(v))
+        {
+            return Err(input.error(format!(
+                "Function `{name}' uses `{var}' which is not one of its parameters"
+            )));
+        }
+        Ok(ast::FnDef::new(name, params, body, Self::get_span(&input)))
+    }
+
     fn comp_or_ext(input: Node) -> ParseResult<BodyEl> {
         Ok(match_nodes!(
             input.into_children();
             [external(sig)] => BodyEl::Ext(Box::new(sig)),
             [generate(sig)] => BodyEl::Ext(Box::new(sig)),
             [component(comp)] => BodyEl::Comp(Box::new(comp)),
+            [type_def(def)] => BodyEl::Type(Box::new(def)),
+            [fn_def(def)] => BodyEl::Fn(Box::new(def)),
         ))
     }
 
@@ -911,6 +1110,13 @@ impl FilamentParser {
         ))
     }
 
+    fn sig_def(input: Node) -> ParseResult<ast::Signature> {
+        Ok(match_nodes!(
+            input.into_children();
+            [signature(sig), _EOI] => sig,
+        ))
+    }
+
     fn file(input: Node) -> ParseResult<ast::Namespace> {
         Ok(match_nodes!(
             input.into_children();
@@ -924,6 +1130,8 @@ impl FilamentParser {
                     match m {
                         BodyEl::Ext(sig) => namespace.externs.push(*sig),
                         BodyEl::Comp(comp) => namespace.components.push(*comp),
+                        BodyEl::Type(def) => namespace.types.push(*def),
+                        BodyEl::Fn(def) => namespace.functions.push(*def),
                     }
                 }
                 namespace
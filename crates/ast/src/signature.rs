@@ -1,5 +1,5 @@
 use super::{
-    Binding, Expr, Id, InterfaceDef, Loc, OrderConstraint, PortDef, Time,
+    Binding, Expr, Id, InterfaceDef, Loc, OrderConstraint, PortDef, Prop, Time,
     TimeSub,
 };
 use fil_utils::{CompAttrs, GPosIdx};
@@ -86,7 +86,7 @@ pub enum SigBind {
     Exists {
         param: Loc<Id>,
         opaque: bool,
-        cons: Vec<Loc<OrderConstraint<Expr>>>,
+        cons: Vec<Loc<Prop<Expr>>>,
     },
 }
 impl SigBind {
@@ -96,7 +96,7 @@ impl SigBind {
     pub fn exists(
         param: Loc<Id>,
         opaque: bool,
-        cons: Vec<Loc<OrderConstraint<Expr>>>,
+        cons: Vec<Loc<Prop<Expr>>>,
     ) -> Self {
         Self::Exists {
             param,
@@ -125,7 +125,7 @@ pub struct Signature {
     /// Names of abstract variables bound by the component
     pub events: Vec<Loc<EventBind>>,
     /// Constraints over the parameters in the signature
-    pub param_constraints: Vec<Loc<OrderConstraint<Expr>>>,
+    pub param_constraints: Vec<Loc<Prop<Expr>>>,
     /// Constraints over events in the signature
     pub event_constraints: Vec<Loc<OrderConstraint<Time>>>,
     /// All the input/output ports.
@@ -145,7 +145,7 @@ impl Signature {
         interface_signals: Vec<InterfaceDef>,
         mut inputs: Vec<Loc<PortDef>>,
         mut outputs: Vec<Loc<PortDef>>,
-        param_constraints: Vec<Loc<OrderConstraint<Expr>>>,
+        param_constraints: Vec<Loc<Prop<Expr>>>,
         event_constraints: Vec<Loc<OrderConstraint<Time>>>,
         sig_bindings: Vec<Loc<SigBind>>,
     ) -> Self {
@@ -186,4 +186,15 @@ impl Signature {
     pub fn ports(&self) -> &Vec<Loc<PortDef>> {
         &self.ports
     }
+    /// Replace each port with the ports returned by `func`
+    pub fn flat_map_ports<F>(&mut self, mut func: F)
+    where
+        F: FnMut(Loc<PortDef>) -> Vec<Loc<PortDef>>,
+    {
+        let mut ports = std::mem::take(&mut self.ports);
+        let outputs = ports.split_off(self.outputs_idx);
+        self.ports = ports.into_iter().flat_map(&mut func).collect();
+        self.outputs_idx = self.ports.len();
+        self.ports.extend(outputs.into_iter().flat_map(func));
+    }
 }
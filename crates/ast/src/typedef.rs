@@ -0,0 +1,116 @@
+use super::{Binding, Expr, Id, Loc};
+use itertools::Itertools;
+
+#[derive(Clone)]
+/// Use of a record type with arguments for its parameters:
+/// ```fil
+/// Complex[32]
+/// ```
+pub struct TypeApp {
+    /// Name of the record type
+    pub name: Loc<Id>,
+    /// Arguments for the parameters of the type
+    pub args: Vec<Loc<Expr>>,
+}
+
+impl TypeApp {
+    pub fn new(name: Loc<Id>, args: Vec<Loc<Expr>>) -> Self {
+        Self { name, args }
+    }
+}
+
+impl std::fmt::Display for TypeApp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.args.is_empty() {
+            write!(f, "[{}]", self.args.iter().join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+/// Numeric interpretation of the bits of a port:
+/// ```fil
+/// signed W
+/// unsigned W
+/// fixed<I, F>
+/// ```
+pub enum Interp {
+    Unsigned,
+    Signed,
+    /// Fixed-point number with `int` integer bits and `frac` fractional bits
+    Fixed {
+        int: Loc<Expr>,
+        frac: Loc<Expr>,
+    },
+}
+
+impl Interp {
+    pub fn resolve(self, binding: &Binding<Expr>) -> Self {
+        match self {
+            Interp::Fixed { int, frac } => Interp::Fixed {
+                int: int.map(|e| e.resolve(binding)),
+                frac: frac.map(|e| e.resolve(binding)),
+            },
+            i => i,
+        }
+    }
+}
+
+impl std::fmt::Display for Interp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interp::Unsigned => write!(f, "unsigned"),
+            Interp::Signed => write!(f, "signed"),
+            Interp::Fixed { int, frac } => write!(f, "fixed<{int}, {frac}>"),
+        }
+    }
+}
+
+#[derive(Clone)]
+/// A field of a record type. The type of the field is either a bitwidth or
+/// another record type.
+pub struct Field {
+    pub name: Loc<Id>,
+    /// Bitwidth of the field. A bare name refers to a declared type.
+    pub width: Loc<Expr>,
+    /// The record type of the field if it was given arguments
+    pub record: Option<Loc<TypeApp>>,
+    /// Numeric interpretation of the field
+    pub interp: Option<Loc<Interp>>,
+}
+
+#[derive(Clone)]
+/// The definition of a named type
+pub enum TypeBody {
+    /// A record with named fields:
+    /// ```fil
+    /// { re: W, im: W }
+    /// ```
+    Record(Vec<Field>),
+    /// An enumeration of named values:
+    /// ```fil
+    /// enum { Add, Sub, Load }
+    /// ```
+    Enum(Vec<Loc<Id>>),
+}
+
+#[derive(Clone)]
+/// A type declaration:
+/// ```fil
+/// type Complex[W] = { re: W, im: W };
+/// type Opcode = enum { Add, Sub, Load };
+/// ```
+pub struct TypeDef {
+    pub name: Loc<Id>,
+    /// Parameters of the type. Always empty for enums.
+    pub params: Vec<Loc<Id>>,
+    pub body: TypeBody,
+}
+
+impl TypeDef {
+    pub fn new(name: Loc<Id>, params: Vec<Loc<Id>>, body: TypeBody) -> Self {
+        Self { name, params, body }
+    }
+}
@@ -1,3 +1,5 @@
+mod records;
 mod toplevel;
 
+pub use records::Records;
 pub use toplevel::TopLevel;
@@ -0,0 +1,756 @@
+use crate::ast_visitor::{Action, Construct, Visitor};
+use crate::cmdline;
+use fil_ast::{self as ast, Loc};
+use fil_utils::{Diagnostics, Error, GPosIdx, Id, InfoIdx};
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+
+/// A leaf of a flattened record: the path of fields leading to it, its
+/// bitwidth, and its numeric interpretation.
+type Leaf = (Vec<Id>, Loc<ast::Expr>, Option<Loc<ast::Interp>>);
+
+/// Number of bits required to represent an enum with `n` variants
+fn enum_bits(n: usize) -> u64 {
+    (usize::BITS - (n - 1).leading_zeros()).max(1) as u64
+}
+
+/// Name of the port that stores the field at `path` of `port`
+fn field_name(port: &Loc<Id>, path: &[Id]) -> Loc<Id> {
+    Loc::new(
+        Id::new(format!("{}_{}", port, path.iter().join("_"))),
+        port.pos(),
+    )
+}
+
+/// Parameters bound by a signature
+fn sig_params(sig: &ast::Signature) -> HashSet<Id> {
+    sig.params()
+        .map(|p| *p.inner())
+        .chain(sig.sig_bindings.iter().map(|sb| match sb.inner() {
+            ast::SigBind::Let { param, .. }
+            | ast::SigBind::Exists { param, .. } => *param.inner(),
+        }))
+        .collect()
+}
+
+/// Declared record and enum types
+struct Types(HashMap<Id, ast::TypeDef>);
+
+impl Types {
+    /// The type named by a bitwidth. Parameters in scope shadow types with
+    /// the same name.
+    fn lookup<'a>(
+        &'a self,
+        width: &'a ast::Expr,
+        params: &HashSet<Id>,
+    ) -> Option<(&'a Loc<Id>, &'a ast::TypeDef)> {
+        let ast::Expr::Abstract(name) = width else {
+            return None;
+        };
+        if params.contains(name.inner()) {
+            return None;
+        }
+        self.0.get(name.inner()).map(|def| (name, def))
+    }
+
+    /// The record type of a port with the given bitwidth. A bare name refers
+    /// to a record type if one with that name is declared and no parameter
+    /// in `params` has that name.
+    fn record(
+        &self,
+        width: &Loc<ast::Expr>,
+        record: &Option<Loc<ast::TypeApp>>,
+        params: &HashSet<Id>,
+    ) -> Option<Loc<ast::TypeApp>> {
+        if record.is_some() {
+            return record.clone();
+        }
+        match self.lookup(width.inner(), params) {
+            Some((
+                name,
+                ast::TypeDef {
+                    body: ast::TypeBody::Record(_),
+                    ..
+                },
+            )) => Some(Loc::new(
+                ast::TypeApp::new(name.clone(), vec![]),
+                width.pos(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// The bitwidth of a port whose type is an enum
+    fn enum_width(
+        &self,
+        width: &ast::Expr,
+        params: &HashSet<Id>,
+    ) -> Option<u64> {
+        match self.lookup(width, params).map(|(_, def)| &def.body) {
+            Some(ast::TypeBody::Enum(vars)) => Some(enum_bits(vars.len())),
+            _ => None,
+        }
+    }
+
+    /// Flatten a record type into its leaves in declaration order.
+    /// `stack` contains the types currently being flattened.
+    fn flatten(
+        &self,
+        app: &Loc<ast::TypeApp>,
+        diag: &mut Diagnostics,
+        stack: &mut Vec<Id>,
+    ) -> Option<Vec<Leaf>> {
+        let name = *app.name.inner();
+        let Some(def) = self.0.get(&name) else {
+            let err = Error::undefined(name, "type")
+                .add_note(diag.add_info("unknown type", app.name.pos()));
+            diag.add_error(err);
+            return None;
+        };
+        let ast::TypeBody::Record(fields) = &def.body else {
+            let err = Error::malformed(format!(
+                "enum `{name}' cannot be given arguments"
+            ))
+            .add_note(diag.add_info("arguments provided here", app.pos()));
+            diag.add_error(err);
+            return None;
+        };
+        if def.params.len() != app.args.len() {
+            let err = Error::malformed(format!(
+                "type `{name}' expects {} arguments but {} were provided",
+                def.params.len(),
+                app.args.len()
+            ))
+            .add_note(diag.add_info("type used here", app.pos()))
+            .add_note(diag.add_info("type defined here", def.name.pos()));
+            diag.add_error(err);
+            return None;
+        }
+        if stack.contains(&name) {
+            let err = Error::malformed(format!("type `{name}' is recursive"))
+                .add_note(diag.add_info("type used here", app.pos()));
+            diag.add_error(err);
+            return None;
+        }
+
+        let binding = ast::Binding::new(
+            def.params
+                .iter()
+                .map(|p| *p.inner())
+                .zip(app.args.iter().map(|a| a.inner().clone())),
+        );
+
+        let params = def.params.iter().map(|p| *p.inner()).collect();
+
+        stack.push(name);
+        let mut leaves = Vec::new();
+        for field in fields {
+            let fname = *field.name.inner();
+            if let Some(rec) = self.record(&field.width, &field.record, &params)
+            {
+                let rec = rec.map(|r| ast::TypeApp {
+                    args: r
+                        .args
+                        .into_iter()
+                        .map(|a| a.map(|a| a.resolve(&binding)))
+                        .collect(),
+                    ..r
+                });
+                let inner = self.flatten(&rec, diag, stack)?;
+                leaves.extend(inner.into_iter().map(
+                    |(mut path, width, interp)| {
+                        path.insert(0, fname);
+                        (path, width, interp)
+                    },
+                ));
+            } else {
+                let width = match self.enum_width(field.width.inner(), &params)
+                {
+                    Some(w) => field.width.clone().map(|_| w.into()),
+                    None => field.width.clone().map(|w| w.resolve(&binding)),
+                };
+                let interp = field
+                    .interp
+                    .clone()
+                    .map(|i| i.map(|i| i.resolve(&binding)));
+                leaves.push((vec![fname], width, interp));
+            }
+        }
+        stack.pop();
+        Some(leaves)
+    }
+
+    /// The paths of the leaves of a port with the given record type.
+    /// Ports that are not records have a single leaf with an empty path.
+    fn paths(
+        &self,
+        typ: Option<&Loc<ast::TypeApp>>,
+        diag: &mut Diagnostics,
+    ) -> Option<Vec<Vec<Id>>> {
+        match typ {
+            Some(typ) => Some(
+                self.flatten(typ, diag, &mut vec![])?
+                    .into_iter()
+                    .map(|(path, ..)| path)
+                    .collect(),
+            ),
+            None => Some(vec![vec![]]),
+        }
+    }
+}
+
+/// The record-typed ports of a component signature
+struct Sig {
+    /// Names of the inputs in order
+    inputs: Vec<Id>,
+    /// Record types of the ports
+    records: HashMap<Id, Loc<ast::TypeApp>>,
+}
+
+/// Flattens ports and bundles with record types into one port for each field
+/// and replaces enum types with their bitwidth.
+/// A port `p` with the type:
+/// ```fil
+/// type Complex[W] = { re: W, im: W };
+/// ```
+/// is replaced by the ports `p_re` and `p_im`. Accesses to fields like `p.re`
+/// are rewritten to use the corresponding port and connections and
+/// invocation arguments of a record type are expanded for each field.
+/// Enum variants like `Opcode::Add` used in parameter expressions are
+/// replaced by their index.
+pub struct Records {
+    types: Types,
+    /// Signatures of all components before flattening
+    sigs: HashMap<Id, Sig>,
+    /// Record-typed ports and bundles of the current component
+    locals: HashMap<Id, Loc<ast::TypeApp>>,
+    /// Component instantiated by each instance
+    instances: HashMap<Id, Id>,
+    /// Instance invoked by each invocation
+    invokes: HashMap<Id, Id>,
+    /// Parameters in scope in the current component
+    params: HashSet<Id>,
+    /// Ports and bundles of the current component that are not flattened
+    declared: HashMap<Id, GPosIdx>,
+    /// Ports generated for the fields of records and the port and path
+    /// they store
+    fields: HashMap<Id, (Loc<Id>, Vec<Id>)>,
+    diag: Diagnostics,
+}
+
+impl Construct for Records {
+    fn from(_: &cmdline::Opts, ns: &mut ast::Namespace) -> Self {
+        let mut diag = Diagnostics::default();
+        let mut defs: HashMap<Id, ast::TypeDef> = HashMap::new();
+        for def in &ns.types {
+            let name = *def.name.inner();
+            if let Some(prev) = defs.get(&name) {
+                let err = Error::already_bound(name, "type")
+                    .add_note(
+                        diag.add_info("type redefined here", def.name.pos()),
+                    )
+                    .add_note(
+                        diag.add_info(
+                            "previous definition here",
+                            prev.name.pos(),
+                        ),
+                    );
+                diag.add_error(err);
+            } else {
+                defs.insert(name, def.clone());
+            }
+        }
+        let types = Types(defs);
+
+        let sigs = ns
+            .components
+            .iter()
+            .map(|c| &c.sig)
+            .chain(ns.externals().map(|(_, sig)| sig))
+            .map(|sig| {
+                let params = sig_params(sig);
+                let records = sig
+                    .ports()
+                    .iter()
+                    .filter_map(|pd| {
+                        types
+                            .record(&pd.typ.bitwidth, &pd.typ.record, &params)
+                            .map(|r| (*pd.name.inner(), r))
+                    })
+                    .collect();
+                let inputs = sig.inputs().map(|pd| *pd.name.inner()).collect();
+                (*sig.name.inner(), Sig { inputs, records })
+            })
+            .collect();
+
+        let mut records = Self {
+            types,
+            sigs,
+            locals: HashMap::new(),
+            instances: HashMap::new(),
+            invokes: HashMap::new(),
+            params: HashSet::new(),
+            declared: HashMap::new(),
+            fields: HashMap::new(),
+            diag,
+        };
+        // Functions can use enum variants in their bodies
+        for def in &mut ns.functions {
+            records.variants(def.body.inner_mut());
+        }
+        records
+    }
+
+    fn clear_data(&mut self) {
+        self.locals.clear();
+        self.instances.clear();
+        self.invokes.clear();
+        self.params.clear();
+        self.declared.clear();
+        self.fields.clear();
+    }
+}
+
+impl Records {
+    /// Flatten a port or bundle definition with a record type
+    fn bundle_def(&mut self, pd: Loc<ast::Bundle>) -> Vec<Loc<ast::Bundle>> {
+        let pos = pd.pos();
+        let mut pd = pd.take();
+        self.variants(&mut pd.typ.bitwidth);
+        pd.typ.len.iter_mut().for_each(|l| self.variants(l));
+
+        if let Some(w) =
+            self.types.enum_width(pd.typ.bitwidth.inner(), &self.params)
+        {
+            pd.typ.bitwidth = pd.typ.bitwidth.map(|_| w.into());
+        }
+        let Some(rec) =
+            self.types
+                .record(&pd.typ.bitwidth, &pd.typ.record, &self.params)
+        else {
+            if let Some((port, path)) = self.fields.get(pd.name.inner()) {
+                let (port, path) = (port.clone(), path.clone());
+                let err = Error::already_bound(*pd.name.inner(), "port")
+                    .add_note(
+                        self.diag.add_info("port declared here", pd.name.pos()),
+                    )
+                    .add_note(self.field_info(&pd.name, &port, &path));
+                self.diag.add_error(err);
+            }
+            self.declared.insert(*pd.name.inner(), pd.name.pos());
+            return vec![Loc::new(pd, pos)];
+        };
+        if let Some(interp) = &pd.typ.interp {
+            let err = Error::malformed(format!(
+                "record type `{}' cannot have a numeric interpretation",
+                rec.name
+            ))
+            .add_note(
+                self.diag
+                    .add_info("interpretation given here", interp.pos()),
+            )
+            .add_note(self.diag.add_message(
+                "give the fields of the record an interpretation instead",
+            ));
+            self.diag.add_error(err);
+            return vec![];
+        }
+        self.locals.insert(*pd.name.inner(), rec.clone());
+        let Some(leaves) =
+            self.types.flatten(&rec, &mut self.diag, &mut vec![])
+        else {
+            return vec![];
+        };
+
+        leaves
+            .into_iter()
+            .map(|(path, width, interp)| {
+                let typ = ast::BundleType {
+                    bitwidth: width,
+                    record: None,
+                    interp,
+                    ..pd.typ.clone()
+                };
+                let name = field_name(&pd.name, &path);
+                let prev = match self.fields.get(name.inner()) {
+                    Some((port, prev)) => {
+                        let (port, prev) = (port.clone(), prev.clone());
+                        Some(self.field_info(&name, &port, &prev))
+                    }
+                    None => self.declared.get(name.inner()).map(|&decl| {
+                        self.diag.add_info("port declared here", decl)
+                    }),
+                };
+                if let Some(prev) = prev {
+                    let err = Error::already_bound(*name.inner(), "port")
+                        .add_note(self.field_info(&name, &pd.name, &path))
+                        .add_note(prev);
+                    self.diag.add_error(err);
+                }
+                self.fields
+                    .insert(*name.inner(), (pd.name.clone(), path.clone()));
+                Loc::new(ast::Bundle::new(name, typ, pd.attrs.clone()), pos)
+            })
+            .collect()
+    }
+
+    /// Note that the field at `path` of `port` is stored in `name`
+    fn field_info(
+        &mut self,
+        name: &Loc<Id>,
+        port: &Loc<Id>,
+        path: &[Id],
+    ) -> InfoIdx {
+        self.diag.add_info(
+            format!(
+                "field `{}' of `{port}' is stored in `{name}'",
+                path.iter().join(".")
+            ),
+            port.pos(),
+        )
+    }
+
+    /// Record the parameters bound by let bindings and loops in the commands
+    fn scope(&mut self, cmds: &[ast::Command]) {
+        for cmd in cmds {
+            match cmd {
+                ast::Command::ParamLet(pl) => {
+                    self.params.insert(*pl.name.inner());
+                }
+                ast::Command::ForLoop(l) => {
+                    self.params.insert(*l.idx.inner());
+                    self.scope(&l.body);
+                }
+                ast::Command::If(i) => {
+                    self.scope(&i.then);
+                    self.scope(&i.alt);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Record the instances, invocations, and record-typed bundles in the
+    /// commands
+    fn scan(&mut self, cmds: &[ast::Command]) {
+        for cmd in cmds {
+            match cmd {
+                ast::Command::Instance(inst) => {
+                    self.instances
+                        .insert(*inst.name.inner(), *inst.component.inner());
+                }
+                ast::Command::Invoke(inv) => {
+                    self.invokes
+                        .insert(*inv.name.inner(), *inv.instance.inner());
+                }
+                ast::Command::Bundle(bl) => {
+                    if let Some(rec) = self.types.record(
+                        &bl.typ.bitwidth,
+                        &bl.typ.record,
+                        &self.params,
+                    ) {
+                        self.locals.insert(*bl.name.inner(), rec);
+                    }
+                }
+                ast::Command::ForLoop(l) => self.scan(&l.body),
+                ast::Command::If(i) => {
+                    self.scan(&i.then);
+                    self.scan(&i.alt);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Expand a port into the ports of each field it refers to.
+    /// Each port is paired with the path of its field relative to the
+    /// accessed fields.
+    fn expand(
+        &mut self,
+        port: Loc<ast::Port>,
+    ) -> Option<Vec<(Vec<Id>, Loc<ast::Port>)>> {
+        let pos = port.pos();
+        let mut port = port.take();
+
+        // Field accesses on ports of this component are parsed as instance
+        // ports.
+        if let ast::PortRef::Instance {
+            instance,
+            port: field,
+        } = &port.base
+        {
+            let name = instance.inner();
+            if self.locals.contains_key(name)
+                && !self.invokes.contains_key(name)
+                && !self.instances.contains_key(name)
+            {
+                port.fields.insert(0, field.clone());
+                port.base = ast::PortRef::This {
+                    port: instance.clone(),
+                };
+            }
+        }
+
+        let typ = match &port.base {
+            ast::PortRef::This { port } => self.locals.get(port.inner()),
+            ast::PortRef::Instance { instance, port } => self
+                .invokes
+                .get(instance.inner())
+                .and_then(|inst| self.instances.get(inst))
+                .and_then(|comp| self.sigs.get(comp))
+                .and_then(|sig| sig.records.get(port.inner())),
+        }
+        .cloned();
+
+        let Some(typ) = typ else {
+            if let Some(field) = port.fields.first() {
+                let err = Error::malformed(format!(
+                    "port `{}' is not a record",
+                    port.port_name()
+                ))
+                .add_note(
+                    self.diag.add_info("field accessed here", field.pos()),
+                );
+                self.diag.add_error(err);
+                return None;
+            }
+            return Some(vec![(vec![], Loc::new(port, pos))]);
+        };
+
+        let leaves = self.types.flatten(&typ, &mut self.diag, &mut vec![])?;
+        let fields = port.fields.iter().map(|f| *f.inner()).collect_vec();
+        let selected = leaves
+            .into_iter()
+            .filter(|(path, ..)| path.starts_with(&fields))
+            .collect_vec();
+        if selected.is_empty() {
+            let err = Error::malformed(format!(
+                "record type `{}' has no field `{}'",
+                typ.name,
+                fields.iter().join(".")
+            ))
+            .add_note(self.diag.add_info(
+                "unknown field",
+                port.fields.last().map_or(pos, |f| f.pos()),
+            ));
+            self.diag.add_error(err);
+            return None;
+        }
+
+        let out = selected
+            .into_iter()
+            .map(|(path, ..)| {
+                let base = match &port.base {
+                    ast::PortRef::This { port } => ast::PortRef::This {
+                        port: field_name(port, &path),
+                    },
+                    ast::PortRef::Instance { instance, port } => {
+                        ast::PortRef::Instance {
+                            instance: instance.clone(),
+                            port: field_name(port, &path),
+                        }
+                    }
+                };
+                let p = ast::Port {
+                    base,
+                    fields: vec![],
+                    ..port.clone()
+                };
+                (path[fields.len()..].to_vec(), Loc::new(p, pos))
+            })
+            .collect();
+        Some(out)
+    }
+
+    /// Replace enum variants in the expression with their index
+    fn variants(&mut self, e: &mut ast::Expr) {
+        match e {
+            ast::Expr::ParamAccess { inst, param } => {
+                let Some(ast::TypeBody::Enum(vars)) =
+                    self.types.0.get(inst.inner()).map(|def| &def.body)
+                else {
+                    return;
+                };
+                match vars.iter().position(|v| v.inner() == param.inner()) {
+                    Some(idx) => *e = ast::Expr::Concrete(idx as u64),
+                    None => {
+                        let err = Error::malformed(format!(
+                            "enum `{inst}' has no variant `{param}'"
+                        ))
+                        .add_note(
+                            self.diag.add_info("unknown variant", param.pos()),
+                        );
+                        self.diag.add_error(err);
+                    }
+                }
+            }
+            ast::Expr::App { args, .. } | ast::Expr::Call { args, .. } => {
+                args.iter_mut().for_each(|a| self.variants(a))
+            }
+            ast::Expr::Op { left, right, .. } => {
+                self.variants(left);
+                self.variants(right);
+            }
+            ast::Expr::If { cond, then, alt } => {
+                self.order_variants(cond);
+                self.variants(then);
+                self.variants(alt);
+            }
+            ast::Expr::Concrete(_) | ast::Expr::Abstract(_) => (),
+        }
+    }
+
+    fn order_variants(&mut self, prop: &mut ast::Prop<ast::Expr>) {
+        for cons in prop.cmps_mut() {
+            self.variants(&mut cons.left);
+            self.variants(&mut cons.right);
+        }
+    }
+}
+
+impl Visitor for Records {
+    fn name() -> &'static str {
+        "records"
+    }
+
+    fn signature(&mut self, sig: &mut ast::Signature) -> Action {
+        // Signatures in an extern are visited without clearing the data
+        self.params = sig_params(sig);
+        self.declared.clear();
+        self.fields.clear();
+        for pb in &mut sig.params {
+            if let Some(default) = &mut pb.default {
+                self.variants(default);
+            }
+        }
+        for sb in &mut sig.sig_bindings {
+            match sb.inner_mut() {
+                ast::SigBind::Let { bind, .. } => self.variants(bind),
+                ast::SigBind::Exists { cons, .. } => {
+                    cons.iter_mut().for_each(|c| self.order_variants(c))
+                }
+            }
+        }
+        for cons in &mut sig.param_constraints {
+            self.order_variants(cons);
+        }
+        sig.flat_map_ports(|pd| self.bundle_def(pd));
+        Action::Continue
+    }
+
+    fn start_cmds(&mut self, cmds: &mut Vec<ast::Command>) {
+        self.scope(cmds);
+        self.scan(cmds);
+    }
+
+    fn bundle(&mut self, bl: &mut ast::Bundle) -> Action {
+        let bundles = self.bundle_def(Loc::unknown(bl.clone()));
+        Action::Change(bundles.into_iter().map(|b| b.take().into()).collect())
+    }
+
+    fn instance(&mut self, inst: &mut ast::Instance) -> Action {
+        inst.params.iter_mut().for_each(|p| self.variants(p));
+        Action::Continue
+    }
+
+    fn connect(&mut self, con: &mut ast::Connect) -> Action {
+        let dst = self.expand(con.dst.clone());
+        let src = self.expand(con.src.clone());
+        let (Some(dst), Some(src)) = (dst, src) else {
+            return Action::Continue;
+        };
+
+        if !dst.iter().map(|(p, _)| p).eq(src.iter().map(|(p, _)| p)) {
+            let err = Error::malformed(
+                "source and destination of connection have different types",
+            )
+            .add_note(self.diag.add_info("destination", con.dst.pos()))
+            .add_note(self.diag.add_info("source", con.src.pos()));
+            self.diag.add_error(err);
+            return Action::Continue;
+        }
+
+        Action::Change(
+            dst.into_iter()
+                .zip(src)
+                .map(|((_, dst), (_, src))| ast::Connect::new(dst, src).into())
+                .collect(),
+        )
+    }
+
+    fn invoke(&mut self, inv: &mut ast::Invoke) -> Action {
+        // Fields expected for each input of the invoked component
+        let expected = self
+            .instances
+            .get(inv.instance.inner())
+            .and_then(|comp| self.sigs.get(comp).map(|sig| (comp, sig)))
+            .map(|(comp, sig)| {
+                sig.inputs
+                    .iter()
+                    .map(|input| {
+                        let paths = self
+                            .types
+                            .paths(sig.records.get(input), &mut self.diag);
+                        (*comp, *input, paths)
+                    })
+                    .collect_vec()
+            })
+            .unwrap_or_default();
+
+        let mut ports = Vec::with_capacity(inv.ports.len());
+        for (i, arg) in std::mem::take(&mut inv.ports).into_iter().enumerate() {
+            let pos = arg.pos();
+            let Some(leaves) = self.expand(arg) else {
+                continue;
+            };
+            if let Some((comp, input, Some(paths))) = expected.get(i) {
+                if !paths.iter().eq(leaves.iter().map(|(p, _)| p)) {
+                    let err = Error::malformed(format!(
+                        "argument does not match the type of input `{input}' of `{comp}'"
+                    ))
+                    .add_note(self.diag.add_info("argument provided here", pos));
+                    self.diag.add_error(err);
+                }
+            }
+            ports.extend(leaves.into_iter().map(|(_, p)| p));
+        }
+        inv.ports = ports;
+        Action::Continue
+    }
+
+    fn fact(&mut self, fact: &mut ast::Fact) -> Action {
+        let imp = fact.cons.inner_mut();
+        if let Some(guard) = &mut imp.guard {
+            self.order_variants(guard);
+        }
+        self.order_variants(&mut imp.cons);
+        Action::Continue
+    }
+
+    fn param_let(&mut self, pl: &mut ast::ParamLet) -> Action {
+        if let Some(e) = &mut pl.expr {
+            self.variants(e);
+        }
+        Action::Continue
+    }
+
+    fn exists(&mut self, ex: &mut ast::Exists) -> Action {
+        self.variants(ex.bind.inner_mut());
+        Action::Continue
+    }
+
+    fn start_loop(&mut self, l: &mut ast::ForLoop) -> Action {
+        self.variants(&mut l.start);
+        self.variants(&mut l.end);
+        Action::Continue
+    }
+
+    fn start_if(&mut self, i: &mut ast::If) -> Action {
+        self.order_variants(&mut i.cond);
+        Action::Continue
+    }
+
+    fn after_traversal(mut self) -> Option<u64> {
+        self.diag.report_all()
+    }
+}
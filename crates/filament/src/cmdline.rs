@@ -1,4 +1,5 @@
 use argh::FromArgs;
+use fil_utils::MessageFormat;
 use std::{path::PathBuf, str::FromStr};
 
 #[derive(Debug, Default, Clone, Copy)]
@@ -26,11 +27,13 @@ impl FromStr for Solver {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy)]
 pub enum Backend {
     #[default]
     Verilog,
     Calyx,
+    /// SystemVerilog emitted directly from the IR without going through calyx
+    Sv,
 }
 
 impl FromStr for Backend {
@@ -39,14 +42,59 @@ impl FromStr for Backend {
         match s {
             "calyx" => Ok(Backend::Calyx),
             "verilog" => Ok(Backend::Verilog),
+            "sv" => Ok(Backend::Sv),
             _ => Err(format!(
-                "unknown backend: {s}. Known backends are: calyx, verilog"
+                "unknown backend: {s}. Known backends are: calyx, verilog, sv"
             )),
         }
     }
 }
 
-#[derive(FromArgs, Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Reports about the compiled design
+pub enum Report {
+    /// Initiation intervals, latencies, FSM states and instance utilization
+    Timing,
+    /// Estimated registers, arithmetic units, multiplexers and FSM flip-flops
+    Resources,
+}
+
+impl FromStr for Report {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timing" => Ok(Report::Timing),
+            "resources" => Ok(Report::Resources),
+            _ => Err(format!(
+                "unknown report: {s}. Known reports are: timing, resources"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Format of the reports about the compiled design
+pub enum ReportFormat {
+    /// Text meant to be read by users
+    Human,
+    /// JSON meant to be read by other tools
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ReportFormat::Human),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!(
+                "unknown report format: {s}. Known formats are: human, json"
+            )),
+        }
+    }
+}
+
+#[derive(FromArgs, Debug, Clone)]
 /// The Filament pipeline verifier
 pub struct Opts {
     /// path to the primitives library
@@ -65,6 +113,10 @@ pub struct Opts {
     #[argh(switch, long = "show-models")]
     pub show_models: bool,
 
+    /// show the assumptions relevant to constraints that cannot be proven
+    #[argh(switch, long = "explain")]
+    pub explain: bool,
+
     /// path to search for imports
     #[argh(
         option,
@@ -83,10 +135,39 @@ pub struct Opts {
     #[argh(switch, long = "dump-interface")]
     pub dump_interface: bool,
 
+    /// simulate the program on the transactions in the given data file
+    /// instead of compiling it
+    #[argh(option, long = "interpret")]
+    pub interpret: Option<PathBuf>,
+
+    /// emit a self-checking SystemVerilog testbench that runs the transactions
+    /// in the given data file
+    #[argh(option, long = "testbench")]
+    pub testbench: Option<PathBuf>,
+
+    /// print a report about the monomorphized components instead of
+    /// compiling the program: timing, resources
+    #[argh(option, long = "report")]
+    pub report: Vec<Report>,
+
+    /// format of the reports (default: human): human, json. Only the
+    /// resources report can be printed as json
+    #[argh(option, long = "report-format", default = "ReportFormat::Human")]
+    pub report_format: ReportFormat,
+
     /// set log level
     #[argh(option, long = "log", default = "log::LevelFilter::Warn")]
     pub log_level: log::LevelFilter,
 
+    /// format of reported errors (default: human): human, json
+    #[argh(option, long = "message-format", default = "MessageFormat::Human")]
+    pub message_format: MessageFormat,
+
+    /// extend or truncate the sources of connections between ports of
+    /// different widths instead of reporting an error
+    #[argh(switch, long = "auto-resize")]
+    pub auto_resize: bool,
+
     /// skip the discharge pass (unsafe)
     #[argh(switch, long = "unsafe-skip-discharge")]
     pub unsafe_skip_discharge: bool,
@@ -96,12 +177,41 @@ pub struct Opts {
     #[argh(option, long = "out-dir")]
     pub out_dir: Option<PathBuf>,
 
+    /// directory to reuse the outputs of external tools from previous runs
+    /// (default: `<out-dir>/.gen-cache`)
+    #[argh(option, long = "gen-cache")]
+    pub gen_cache: Option<PathBuf>,
+
+    /// always run external tools instead of reusing their outputs
+    #[argh(switch, long = "no-gen-cache")]
+    pub no_gen_cache: bool,
+
+    /// number of external tools run in parallel (default: number of cores)
+    #[argh(option, long = "gen-jobs")]
+    pub gen_jobs: Option<usize>,
+
+    /// check that the parameters of recursive instances decrease
+    /// lexicographically so that monomorphization is guaranteed to terminate
+    #[argh(switch, long = "check-termination")]
+    pub check_termination: bool,
+
+    /// maximum depth of nested instantiations while monomorphizing
+    /// (default: 256)
+    #[argh(option, long = "recursion-limit", default = "256")]
+    pub recursion_limit: usize,
+
     /// provided bindings (gen config and parameter bindings)
     #[argh(option, long = "bindings")]
     pub bindings: Option<PathBuf>,
 
+    /// compile the program for every combination of parameter values in the
+    /// `sweep` table of the bindings and write the outputs to the given
+    /// directory
+    #[argh(option, long = "sweep")]
+    pub sweep: Option<PathBuf>,
+
     // Backend options
-    /// backend to use (default: verilog): calyx, verilog
+    /// backend to use (default: verilog): calyx, verilog, sv
     #[argh(option, long = "backend", default = "Backend::Verilog")]
     pub backend: Backend,
 
@@ -123,10 +233,36 @@ pub struct Opts {
     /// solve assertions separately rather than all at once
     #[argh(switch, long = "discharge-separate")]
     pub discharge_separate: bool,
+    /// number of solver processes used to discharge assertions in parallel
+    /// (default: number of cores)
+    #[argh(option, long = "discharge-jobs")]
+    pub discharge_jobs: Option<usize>,
     /// dump interactions with the solver in the given file
     #[argh(option, long = "dump-solver-log")]
     pub solver_replay_file: Option<String>,
+    /// write each proof obligation to a self-contained SMT-LIB file in the given directory
+    #[argh(option, long = "emit-obligations")]
+    pub emit_obligations: Option<PathBuf>,
     /// use bitvector encoding for proofs
     #[argh(option, long = "solver-bv")]
     pub solver_bv: Option<u8>,
+    /// cache the components verified by the solver in the given directory
+    #[argh(option, long = "cache-dir")]
+    pub cache_dir: Option<PathBuf>,
+}
+
+#[derive(FromArgs, Debug)]
+/// Format Filament programs. Reads from stdin if no files are provided.
+pub struct FmtOpts {
+    /// files to format
+    #[argh(positional)]
+    pub files: Vec<PathBuf>,
+
+    /// write the formatted programs back to the files instead of printing them
+    #[argh(switch, short = 'w', long = "write")]
+    pub write: bool,
+
+    /// report files that are not formatted instead of printing them
+    #[argh(switch, long = "check")]
+    pub check: bool,
 }
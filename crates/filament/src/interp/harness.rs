@@ -0,0 +1,195 @@
+use super::{Sim, Value};
+use crate::ir_passes::{NameGenerator, max_states};
+use fil_ir::{self as ir, Ctx};
+use fil_utils::{self as utils, FilamentResult};
+use itertools::Itertools;
+use std::{collections::HashMap, fs, path::Path};
+
+/// Read a data file that maps the name of each port to its values in every
+/// transaction. Returns the values and the number of transactions.
+pub fn read_data(
+    path: &Path,
+) -> FilamentResult<(HashMap<String, Vec<serde_json::Value>>, usize)> {
+    let data = fs::read_to_string(path).map_err(|e| {
+        utils::Error::invalid_file(format!(
+            "Failed to read {}: {e}",
+            path.display()
+        ))
+    })?;
+    let data: HashMap<String, Vec<serde_json::Value>> =
+        serde_json::from_str(&data).map_err(|e| {
+            utils::Error::misc(format!("Malformed data file: {e}"))
+        })?;
+
+    let txns = data.values().next().map_or(0, |v| v.len());
+    if data.values().any(|v| v.len() != txns) {
+        return Err(utils::Error::misc(
+            "Invalid: Mismatched sizes for inputs".to_string(),
+        ));
+    }
+    Ok((data, txns))
+}
+
+/// A port of the top-level component with the cycles (relative to the start
+/// of a transaction) in which it is live.
+struct PortInfo {
+    name: String,
+    width: u64,
+    start: u64,
+    end: u64,
+}
+
+/// Runs transactions from a data file through the entrypoint of a program.
+/// Mirrors the behavior of the cocotb harness (`fud/harness/utils.py`): the
+/// `i`th transaction provides the `i`th value of every input, transactions
+/// start every `delay` cycles, and outputs are recorded in every cycle that
+/// they are live.
+pub struct Harness {
+    sim: Sim,
+    /// Name of the interface port of the event, if any
+    interface: Option<String>,
+    delay: u64,
+    states: u64,
+    inputs: Vec<PortInfo>,
+    outputs: Vec<PortInfo>,
+}
+
+impl Harness {
+    pub fn new(ctx: &ir::Context) -> FilamentResult<Self> {
+        let sim = Sim::new(ctx)?;
+        let ep = ctx.entrypoint.as_ref().unwrap();
+        let main = ctx.get(ep.comp);
+        let ng = NameGenerator::new(true);
+
+        let Some((ev, event)) = main.events().iter().exactly_one().ok() else {
+            return Err(utils::Error::misc(format!(
+                "Simulation requires exactly one event in the entrypoint, found {}",
+                main.events().len()
+            )));
+        };
+        let ir::TimeSub::Unit(delay) = event.delay else {
+            main.internal_error(
+                "Non-unit delays should have been compiled away.",
+            );
+        };
+        let states = max_states(main).get(&ev).copied().unwrap_or(0);
+
+        let info = |(idx, port): (ir::PortIdx, &ir::Port)| {
+            let range = &port.live.range;
+            PortInfo {
+                name: ng.port_name(idx, ctx, main),
+                width: port.width.concrete(main),
+                start: main.get(range.start).offset.concrete(main),
+                end: main.get(range.end).offset.concrete(main),
+            }
+        };
+
+        Ok(Self {
+            sim,
+            interface: ng.interface_name(ev, main),
+            delay: delay.concrete(main),
+            states,
+            inputs: main.inputs().map(info).collect(),
+            outputs: main.outputs().map(info).collect(),
+        })
+    }
+
+    /// Run all the transactions in the data file and return the outputs
+    /// in the same JSON format as the cocotb harness.
+    pub fn run(&mut self, data: &Path) -> FilamentResult<String> {
+        let (data, txns) = read_data(data)?;
+
+        // Parse all the input values up front
+        let mut inputs = Vec::with_capacity(self.inputs.len());
+        for port in &self.inputs {
+            let Some(vals) = data.get(&port.name) else {
+                return Err(utils::Error::misc(format!(
+                    "Data file does not contain values for input `{}'",
+                    port.name
+                )));
+            };
+            inputs.push(
+                vals.iter()
+                    .map(|v| Value::parse(v, port.width))
+                    .collect::<FilamentResult<Vec<_>>>()?,
+            );
+        }
+
+        // outputs[port][txn] is the list of values produced in the transaction
+        let mut outputs = vec![vec![vec![]; txns]; self.outputs.len()];
+        // Like the cocotb harness, wait for `delay` cycles after starting the
+        // last transaction even if it finishes earlier.
+        let cycles = if txns == 0 {
+            0
+        } else {
+            let last = (txns as u64 - 1) * self.delay;
+            (last + self.states.max(1)).max(last + self.delay)
+        };
+
+        // Like the cocotb harness, the design idles for a cycle after the
+        // reset and inputs are `'x` in cycles where no transaction uses them.
+        self.sim.reset();
+        self.sim.clear_inputs();
+        if let Some(interface) = &self.interface {
+            self.sim.set_input(interface, Value::from_bool(false))?;
+        }
+        self.sim.tick()?;
+
+        for cycle in 0..cycles {
+            self.sim.clear_inputs();
+            // Transactions active in this cycle and their current state
+            let active = (0..txns)
+                .filter_map(|txn| {
+                    let st = cycle.checked_sub(txn as u64 * self.delay)?;
+                    (st < self.states.max(1)).then_some((txn, st))
+                })
+                .collect_vec();
+
+            if let Some(interface) = &self.interface {
+                let go = active.iter().any(|(_, st)| *st == 0);
+                self.sim.set_input(interface, Value::from_bool(go))?;
+            }
+            for (port, vals) in self.inputs.iter().zip(&inputs) {
+                let mut writes = active
+                    .iter()
+                    .filter(|(_, st)| port.start <= *st && *st < port.end);
+                if let Some((txn, _)) = writes.next() {
+                    if writes.next().is_some() {
+                        return Err(utils::Error::misc(format!(
+                            "Invalid: multiple writes to input `{}' in cycle {cycle}",
+                            port.name
+                        )));
+                    }
+                    self.sim.set_input(&port.name, vals[*txn].clone())?;
+                }
+            }
+            for (port, out) in self.outputs.iter().zip(&mut outputs) {
+                for (txn, st) in &active {
+                    if port.start <= *st && *st < port.end {
+                        out[*txn].push(self.sim.get(&port.name)?);
+                    }
+                }
+            }
+            self.sim.tick()?;
+        }
+
+        // Format the outputs like python's `json.dumps`
+        let fmt = self
+            .outputs
+            .iter()
+            .zip(outputs)
+            .map(|(port, txns)| {
+                let txns = txns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, vs)| {
+                        format!("\"{i}\": [{}]", vs.iter().join(", "))
+                    })
+                    .join(", ");
+                format!("\"{}\": {{{txns}}}", port.name)
+            })
+            .chain(std::iter::once(format!("\"cycles\": {cycles}")))
+            .join(", ");
+        Ok(format!("{{{fmt}}}"))
+    }
+}
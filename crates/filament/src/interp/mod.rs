@@ -0,0 +1,13 @@
+//! A cycle-accurate interpreter for Filament programs.
+//! The interpreter runs on the IR after monomorphization and bundle
+//! elimination and uses behavioral models for the primitives in
+//! `primitives/comb.fil` and `primitives/state.fil`.
+mod harness;
+mod prims;
+mod sim;
+mod value;
+
+pub use harness::{Harness, read_data};
+pub use prims::{Params, Prim};
+pub use sim::Sim;
+pub use value::Value;
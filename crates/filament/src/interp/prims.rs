@@ -0,0 +1,296 @@
+use super::Value;
+use fil_utils::{self as utils, FilamentResult};
+use std::{collections::HashMap, str::FromStr};
+
+/// Behavioral models for the primitives defined in `primitives/comb.fil` and
+/// `primitives/state.fil`. The semantics follow the Verilog implementations
+/// in `comb.sv` and `state.sv`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prim {
+    // Combinational
+    Const,
+    Add,
+    Sub,
+    MultComb,
+    And,
+    Or,
+    Xor,
+    Not,
+    Gt,
+    Eq,
+    Neq,
+    Lt,
+    Gte,
+    Lte,
+    SignExtend,
+    ZeroExtend,
+    Extend,
+    Concat,
+    Select,
+    Slice,
+    ReduceAnd,
+    ReduceOr,
+    ShiftLeft,
+    ShiftRight,
+    ArithShiftRight,
+    Mux,
+    // Stateful
+    Register,
+    Delay,
+    PassThroughRegister,
+    Prev,
+    ContPrev,
+}
+
+impl FromStr for Prim {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "Const" => Prim::Const,
+            "Add" => Prim::Add,
+            "Sub" => Prim::Sub,
+            "MultComb" => Prim::MultComb,
+            "And" => Prim::And,
+            "Or" => Prim::Or,
+            "Xor" => Prim::Xor,
+            "Not" => Prim::Not,
+            "Gt" => Prim::Gt,
+            "Eq" => Prim::Eq,
+            "Neq" => Prim::Neq,
+            "Lt" => Prim::Lt,
+            "Gte" => Prim::Gte,
+            "Lte" => Prim::Lte,
+            "SignExtend" => Prim::SignExtend,
+            "ZeroExtend" => Prim::ZeroExtend,
+            "Extend" => Prim::Extend,
+            "Concat" => Prim::Concat,
+            "Select" => Prim::Select,
+            "Slice" => Prim::Slice,
+            "ReduceAnd" => Prim::ReduceAnd,
+            "ReduceOr" => Prim::ReduceOr,
+            "ShiftLeft" => Prim::ShiftLeft,
+            "ShiftRight" => Prim::ShiftRight,
+            "ArithShiftRight" => Prim::ArithShiftRight,
+            "Mux" => Prim::Mux,
+            "Register" => Prim::Register,
+            "Delay" => Prim::Delay,
+            "PassThroughRegister" => Prim::PassThroughRegister,
+            "Prev" => Prim::Prev,
+            "ContPrev" => Prim::ContPrev,
+            _ => return Err(format!("no behavioral model for `{s}'")),
+        })
+    }
+}
+
+/// Parameters of a primitive instance
+pub type Params = HashMap<String, u64>;
+
+fn param(params: &Params, name: &str) -> FilamentResult<u64> {
+    params.get(name).copied().ok_or_else(|| {
+        utils::Error::misc(format!("primitive is missing parameter {name}"))
+    })
+}
+
+impl Prim {
+    /// Does the primitive hold state across cycles?
+    pub fn is_stateful(&self) -> bool {
+        matches!(
+            self,
+            Prim::Register
+                | Prim::Delay
+                | Prim::PassThroughRegister
+                | Prim::Prev
+                | Prim::ContPrev
+        )
+    }
+
+    /// Width of the state held by a stateful primitive.
+    pub fn state_width(&self, params: &Params) -> FilamentResult<u64> {
+        param(params, "WIDTH")
+    }
+
+    /// The state of the primitive after a reset. `Prev` and `ContPrev` reset
+    /// to `'x` unless their `SAFE` parameter is set.
+    pub fn reset_state(&self, params: &Params) -> FilamentResult<Value> {
+        Ok(match self {
+            Prim::Prev | Prim::ContPrev if param(params, "SAFE")? == 0 => {
+                Value::unknown(self.state_width(params)?)
+            }
+            _ if self.is_stateful() => Value::zero(self.state_width(params)?),
+            _ => Value::zero(0),
+        })
+    }
+
+    /// The name of the output port of the primitive.
+    pub fn output_port(&self) -> &'static str {
+        match self {
+            Prim::Prev | Prim::ContPrev => "prev",
+            _ => "out",
+        }
+    }
+
+    /// Computes the value of the output port with width `width`.
+    /// `input` returns the current value of an input port. The output is
+    /// unknown if any of the inputs it depends upon is unknown.
+    pub fn output<F>(
+        &self,
+        width: u64,
+        params: &Params,
+        state: &Value,
+        mut input: F,
+    ) -> FilamentResult<Value>
+    where
+        F: FnMut(&str) -> FilamentResult<Value>,
+    {
+        let mut unknown = false;
+        let mut input = |port: &str| -> FilamentResult<Value> {
+            let v = input(port)?;
+            unknown |= v.is_unknown();
+            Ok(v)
+        };
+        let mut bin = |f: &dyn Fn(&Value, &Value) -> Value| {
+            let l = input("left")?;
+            let r = input("right")?;
+            Ok::<_, utils::Error>(f(&l, &r))
+        };
+        let out = match self {
+            Prim::Const => Value::from_u64(width, param(params, "VALUE")?),
+            Prim::Add => bin(&|l, r| Value::new(width, l.bits() + r.bits()))?,
+            Prim::Sub => bin(&|l, r| {
+                // Subtraction is performed in the widest of the operand sizes
+                let w = width.max(l.width()).max(r.width());
+                let l = l.resize(w);
+                let neg = Value::new(w, Value::ones(w).bits() ^ r.bits())
+                    .bits()
+                    + 1u32;
+                Value::new(width, l.bits() + neg)
+            })?,
+            Prim::MultComb => {
+                bin(&|l, r| Value::new(width, l.bits() * r.bits()))?
+            }
+            Prim::And => bin(&|l, r| Value::new(width, l.bits() & r.bits()))?,
+            Prim::Or => bin(&|l, r| Value::new(width, l.bits() | r.bits()))?,
+            Prim::Xor => bin(&|l, r| Value::new(width, l.bits() ^ r.bits()))?,
+            Prim::Not => {
+                let i = input("in")?;
+                Value::new(width, Value::ones(i.width()).bits() ^ i.bits())
+            }
+            Prim::Gt => bin(&|l, r| Value::from_bool(l.bits() > r.bits()))?,
+            Prim::Eq => bin(&|l, r| Value::from_bool(l.bits() == r.bits()))?,
+            Prim::Neq => bin(&|l, r| Value::from_bool(l.bits() != r.bits()))?,
+            Prim::Lt => bin(&|l, r| Value::from_bool(l.bits() < r.bits()))?,
+            Prim::Gte => bin(&|l, r| Value::from_bool(l.bits() >= r.bits()))?,
+            Prim::Lte => bin(&|l, r| Value::from_bool(l.bits() <= r.bits()))?,
+            Prim::SignExtend => {
+                let i = input("in")?;
+                if i.msb() {
+                    let ext = Value::ones(width).bits()
+                        ^ Value::ones(i.width()).bits();
+                    Value::new(width, ext | i.bits())
+                } else {
+                    i.resize(width)
+                }
+            }
+            Prim::ZeroExtend => input("in")?.resize(width),
+            Prim::Extend => {
+                let i = input("in")?;
+                let mut bits = i.bits().clone();
+                let mut w = i.width().max(1);
+                while w < width {
+                    bits = (bits << i.width()) | i.bits();
+                    w += i.width().max(1);
+                }
+                Value::new(width, bits)
+            }
+            Prim::Concat => bin(&|l, r| {
+                Value::new(width, (l.bits() << r.width()) | r.bits())
+            })?,
+            Prim::Select => {
+                Value::from_bool(input("in")?.bit(param(params, "POS")?))
+            }
+            Prim::Slice => {
+                let lsb = param(params, "LSB")?;
+                Value::new(width, input("in")?.bits() >> lsb)
+            }
+            Prim::ReduceAnd => {
+                let i = input("in")?;
+                Value::from_bool(i == Value::ones(i.width()))
+            }
+            Prim::ReduceOr => Value::from_bool(input("in")?.is_true()),
+            Prim::ShiftLeft => {
+                let i = input("in")?;
+                let s = input("shift")?.as_u64();
+                if s >= width {
+                    Value::zero(width)
+                } else {
+                    Value::new(width, i.bits() << s)
+                }
+            }
+            Prim::ShiftRight => {
+                let i = input("in")?;
+                let s = input("shift")?.as_u64();
+                if s >= i.width() {
+                    Value::zero(width)
+                } else {
+                    Value::new(width, i.bits() >> s)
+                }
+            }
+            Prim::ArithShiftRight => {
+                let i = input("in")?;
+                let s = input("shift")?.as_u64().min(i.width());
+                let shifted = Value::new(width, i.bits() >> s);
+                if i.msb() {
+                    let fill = Value::ones(i.width()).bits()
+                        ^ (Value::ones(i.width()).bits() >> s);
+                    Value::new(width, shifted.bits() | fill)
+                } else {
+                    shifted
+                }
+            }
+            Prim::Mux => {
+                if input("sel")?.is_true() {
+                    input("in0")?.resize(width)
+                } else {
+                    input("in1")?.resize(width)
+                }
+            }
+            Prim::Register | Prim::Delay => state.resize(width),
+            Prim::PassThroughRegister => {
+                if input("write_en")?.is_true() {
+                    input("in")?.resize(width)
+                } else {
+                    state.resize(width)
+                }
+            }
+            Prim::Prev | Prim::ContPrev => state.resize(width),
+        };
+        Ok(if unknown { Value::unknown(width) } else { out })
+    }
+
+    /// Computes the state of a stateful primitive for the next cycle.
+    pub fn next_state<F>(
+        &self,
+        params: &Params,
+        state: &Value,
+        mut input: F,
+    ) -> FilamentResult<Value>
+    where
+        F: FnMut(&str) -> FilamentResult<Value>,
+    {
+        let width = self.state_width(params)?;
+        Ok(match self {
+            Prim::Register | Prim::PassThroughRegister | Prim::Prev => {
+                let en = input("write_en")?;
+                if en.is_unknown() {
+                    Value::unknown(width)
+                } else if en.is_true() {
+                    input("in")?.resize(width)
+                } else {
+                    state.clone()
+                }
+            }
+            Prim::Delay | Prim::ContPrev => input("in")?.resize(width),
+            _ => unreachable!("{self:?} is not a stateful primitive"),
+        })
+    }
+}
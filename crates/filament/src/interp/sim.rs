@@ -0,0 +1,463 @@
+use super::{Params, Prim, Value};
+use crate::ir_passes::{NameGenerator, max_states};
+use fil_ir::{self as ir, Ctx, DisplayCtx};
+use fil_utils::{self as utils, FilamentResult};
+use std::collections::HashMap;
+
+type SigIdx = usize;
+
+/// Source of an assignment
+enum Src {
+    Sig(SigIdx),
+    Const(Value),
+}
+
+/// A guard that is active when the FSM is in one of the states `start..end`.
+struct Guard {
+    fsm: usize,
+    start: u64,
+    end: u64,
+}
+
+/// What drives the value of a signal
+enum Driver {
+    /// The signal is never assigned
+    Undriven,
+    /// Input of the top-level component set by the harness
+    Input,
+    /// Guarded assignments in priority order
+    Assigns(Vec<(Option<Guard>, Src)>),
+    /// Output of a primitive
+    Cell(usize),
+}
+
+struct Signal {
+    /// Hierarchical name of the signal
+    name: String,
+    width: u64,
+    driver: Driver,
+}
+
+/// Tracks the history of an event's interface port.
+/// This models the shift-register FSMs generated during lowering: the FSM is
+/// in state `k` if the event was triggered `k` cycles ago.
+struct Fsm {
+    go: SigIdx,
+    /// `hist[k-1]` is true if the event was triggered `k` cycles ago
+    hist: Vec<bool>,
+}
+
+/// An instance of a primitive
+struct Cell {
+    prim: Prim,
+    params: Params,
+    /// Hierarchical prefix for the ports of the cell
+    prefix: String,
+    /// State after a reset
+    init: Value,
+    state: Value,
+}
+
+/// State of a signal during the evaluation of a cycle.
+#[derive(Clone)]
+enum Memo {
+    Pending,
+    InProgress,
+    Done(Value),
+}
+
+/// A cycle-accurate simulator for a monomorphized, bundle-free [ir::Context].
+/// The component hierarchy is flattened into a netlist of signals where each
+/// signal is driven by guarded assignments or a primitive.
+pub struct Sim {
+    signals: Vec<Signal>,
+    names: HashMap<String, SigIdx>,
+    fsms: Vec<Fsm>,
+    cells: Vec<Cell>,
+    inputs: HashMap<SigIdx, Value>,
+    memo: Vec<Memo>,
+}
+
+impl Sim {
+    /// Construct a simulator for the entrypoint of the context
+    pub fn new(ctx: &ir::Context) -> FilamentResult<Self> {
+        let Some(ep) = &ctx.entrypoint else {
+            return Err(utils::Error::misc(
+                "Program has no entrypoint to simulate".to_string(),
+            ));
+        };
+        let mut sim = Sim {
+            signals: vec![],
+            names: HashMap::new(),
+            fsms: vec![],
+            cells: vec![],
+            inputs: HashMap::new(),
+            memo: vec![],
+        };
+        let ng = NameGenerator::new(true);
+        sim.build(ctx, &ng, ep.comp, "")?;
+
+        // The inputs and interface ports of the top-level component are driven by the harness
+        let main = ctx.get(ep.comp);
+        let inputs = main
+            .inputs()
+            .map(|(idx, _)| ng.port_name(idx, ctx, main))
+            .chain(
+                main.events()
+                    .idx_iter()
+                    .filter_map(|ev| ng.interface_name(ev, main)),
+            )
+            .chain(main.unannotated_ports.iter().map(|(n, _)| n.to_string()))
+            .collect::<Vec<_>>();
+        for name in inputs {
+            if let Some(&sig) = sim.names.get(&name) {
+                sim.signals[sig].driver = Driver::Input;
+            }
+        }
+
+        // Primitive outputs are driven by the primitive
+        for (idx, cell) in sim.cells.iter().enumerate() {
+            let out = format!("{}{}", cell.prefix, cell.prim.output_port());
+            if let Some(&sig) = sim.names.get(&out) {
+                sim.signals[sig].driver = Driver::Cell(idx);
+            }
+        }
+
+        sim.memo = vec![Memo::Pending; sim.signals.len()];
+        Ok(sim)
+    }
+
+    /// Get or create a signal with the given name
+    fn signal(&mut self, name: String, width: u64) -> SigIdx {
+        if let Some(&idx) = self.names.get(&name) {
+            return idx;
+        }
+        let idx = self.signals.len();
+        self.names.insert(name.clone(), idx);
+        self.signals.push(Signal {
+            name,
+            width,
+            driver: Driver::Undriven,
+        });
+        idx
+    }
+
+    /// Add an assignment to a signal
+    fn assign(&mut self, dst: SigIdx, guard: Option<Guard>, src: Src) {
+        let sig = &mut self.signals[dst];
+        match &mut sig.driver {
+            Driver::Assigns(assigns) => assigns.push((guard, src)),
+            d @ Driver::Undriven => *d = Driver::Assigns(vec![(guard, src)]),
+            Driver::Input | Driver::Cell(_) => {
+                unreachable!("signal `{}' has multiple drivers", sig.name)
+            }
+        }
+    }
+
+    /// Name of the signal representing a port of the component
+    fn port_name(
+        ctx: &ir::Context,
+        ng: &NameGenerator,
+        comp: &ir::Component,
+        prefix: &str,
+        idx: ir::PortIdx,
+    ) -> String {
+        let name = ng.port_name(idx, ctx, comp);
+        match &comp.get(idx).owner {
+            ir::PortOwner::Sig { .. } => format!("{prefix}{name}"),
+            ir::PortOwner::Inv { inv, .. } => {
+                let inst = ng.instance_name(comp.get(*inv).inst, comp);
+                format!("{prefix}{inst}.{name}")
+            }
+            ir::PortOwner::Local => {
+                unreachable!("Local ports should have been eliminated.")
+            }
+        }
+    }
+
+    /// Flatten the component into the netlist with the given hierarchical prefix.
+    fn build(
+        &mut self,
+        ctx: &ir::Context,
+        ng: &NameGenerator,
+        idx: ir::CompIdx,
+        prefix: &str,
+    ) -> FilamentResult<()> {
+        let comp = ctx.get(idx);
+
+        // Track the events with interface ports
+        let mut fsms = HashMap::new();
+        for (ev, states) in max_states(comp) {
+            if let Some(name) = ng.interface_name(ev, comp) {
+                let go = self.signal(format!("{prefix}{name}"), 1);
+                fsms.insert(ev, self.fsms.len());
+                self.fsms.push(Fsm {
+                    go,
+                    hist: vec![false; states as usize],
+                });
+            }
+        }
+        let guard = |range: &ir::Range| {
+            let start = comp.get(range.start);
+            let end = comp.get(range.end);
+            assert!(
+                start.event == end.event,
+                "Range `{}` cannot be represented as a simple offset",
+                comp.display(range)
+            );
+            fsms.get(&start.event).map(|&fsm| Guard {
+                fsm,
+                start: start.offset.concrete(comp),
+                end: end.offset.concrete(comp),
+            })
+        };
+
+        // Flatten the instances
+        for (iidx, inst) in comp.instances().iter() {
+            let child = format!("{prefix}{}.", ng.instance_name(iidx, comp));
+            let callee = ctx.get(inst.comp);
+            if callee.is_ext() {
+                let name = ng.comp_name(inst.comp, ctx);
+                let prim: Prim = name.parse().map_err(|e| {
+                    utils::Error::misc(format!(
+                        "Cannot simulate instance `{}': {e}",
+                        child.trim_end_matches('.')
+                    ))
+                })?;
+                let params: Params = callee
+                    .sig_params()
+                    .zip(&inst.args)
+                    .map(|(p, arg)| {
+                        (ng.param_name(p, callee), arg.concrete(comp))
+                    })
+                    .collect();
+                let state = prim.reset_state(&params)?;
+                self.cells.push(Cell {
+                    prim,
+                    params,
+                    prefix: child,
+                    init: state.clone(),
+                    state,
+                });
+            } else {
+                self.build(ctx, ng, inst.comp, &child)?;
+            }
+        }
+
+        // Define signals for all the ports
+        for (pidx, port) in comp.ports().iter() {
+            let name = Self::port_name(ctx, ng, comp, prefix, pidx);
+            self.signal(name, port.width.concrete(comp));
+        }
+
+        // Trigger the interface ports of invoked instances
+        for (_, inv) in comp.invocations().iter() {
+            let inst = ng.instance_name(inv.inst, comp);
+            for eb in &inv.events {
+                let Some(dst) = eb.base.apply(
+                    |ev: ir::EventIdx, c: &ir::Component| {
+                        ng.interface_name(ev, c)
+                    },
+                    ctx,
+                ) else {
+                    continue;
+                };
+                let time = comp.get(eb.arg);
+                let offset = time.offset.concrete(comp);
+                let Some(&fsm) = fsms.get(&time.event) else {
+                    unreachable!("Invocation scheduled by event without FSM")
+                };
+                let dst = self.signal(format!("{prefix}{inst}.{dst}"), 1);
+                let guard = Guard {
+                    fsm,
+                    start: offset,
+                    end: offset + 1,
+                };
+                self.assign(
+                    dst,
+                    Some(guard),
+                    Src::Const(Value::from_bool(true)),
+                );
+            }
+        }
+
+        // Connections become guarded assignments
+        for cmd in &comp.cmds {
+            if let ir::Command::Connect(ir::Connect { dst, src, .. }) = cmd {
+                assert!(
+                    src.is_port(comp) && dst.is_port(comp),
+                    "Bundles should have been compiled away."
+                );
+                let g = guard(&comp.get(dst.port).live.range);
+                let dst = self.names
+                    [&Self::port_name(ctx, ng, comp, prefix, dst.port)];
+                let src = self.names
+                    [&Self::port_name(ctx, ng, comp, prefix, src.port)];
+                self.assign(dst, g, Src::Sig(src));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Is the guard active in the current cycle?
+    fn active(&self, g: &Guard, memo: &mut [Memo]) -> FilamentResult<bool> {
+        let fsm = &self.fsms[g.fsm];
+        for k in g.start..g.end {
+            let on = if k == 0 {
+                self.eval(fsm.go, memo)?.is_true()
+            } else {
+                fsm.hist[(k - 1) as usize]
+            };
+            if on {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Value of an input port of a cell
+    fn cell_input(
+        &self,
+        cell: &Cell,
+        port: &str,
+        memo: &mut [Memo],
+    ) -> FilamentResult<Value> {
+        match self.names.get(&format!("{}{port}", cell.prefix)) {
+            Some(&sig) => self.eval(sig, memo),
+            None => Ok(Value::zero(0)),
+        }
+    }
+
+    /// Compute the value of a signal in the current cycle
+    fn eval(&self, sig: SigIdx, memo: &mut [Memo]) -> FilamentResult<Value> {
+        match &memo[sig] {
+            Memo::Done(v) => return Ok(v.clone()),
+            Memo::InProgress => {
+                return Err(utils::Error::misc(format!(
+                    "Combinational loop involving `{}'",
+                    self.signals[sig].name
+                )));
+            }
+            Memo::Pending => memo[sig] = Memo::InProgress,
+        }
+        let signal = &self.signals[sig];
+        let width = signal.width;
+        let v = match &signal.driver {
+            Driver::Undriven => Value::zero(width),
+            Driver::Input => self
+                .inputs
+                .get(&sig)
+                .map(|v| v.resize(width))
+                .unwrap_or_else(|| Value::unknown(width)),
+            Driver::Assigns(assigns) => {
+                let mut out = Value::zero(width);
+                for (guard, src) in assigns {
+                    let on = match guard {
+                        Some(g) => self.active(g, memo)?,
+                        None => true,
+                    };
+                    if on {
+                        out = match src {
+                            Src::Sig(s) => self.eval(*s, memo)?,
+                            Src::Const(v) => v.clone(),
+                        }
+                        .resize(width);
+                        break;
+                    }
+                }
+                out
+            }
+            Driver::Cell(c) => {
+                let cell = &self.cells[*c];
+                cell.prim.output(width, &cell.params, &cell.state, |p| {
+                    self.cell_input(cell, p, memo)
+                })?
+            }
+        };
+        memo[sig] = Memo::Done(v.clone());
+        Ok(v)
+    }
+
+    /// Reset all the state in the design
+    pub fn reset(&mut self) {
+        for fsm in &mut self.fsms {
+            fsm.hist.iter_mut().for_each(|h| *h = false);
+        }
+        for cell in &mut self.cells {
+            cell.state = cell.init.clone();
+        }
+        self.invalidate();
+    }
+
+    fn invalidate(&mut self) {
+        self.memo.iter_mut().for_each(|m| *m = Memo::Pending);
+    }
+
+    /// Set the value of a top-level input for the current cycle
+    pub fn set_input(&mut self, name: &str, v: Value) -> FilamentResult<()> {
+        let Some(&sig) = self.names.get(name) else {
+            return Err(utils::Error::misc(format!("Unknown input `{name}'")));
+        };
+        self.inputs.insert(sig, v);
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Clear all the top-level inputs. Inputs that are not set are unknown.
+    pub fn clear_inputs(&mut self) {
+        self.inputs.clear();
+        self.invalidate();
+    }
+
+    /// Value of a signal in the current cycle
+    pub fn get(&mut self, name: &str) -> FilamentResult<Value> {
+        let Some(&sig) = self.names.get(name) else {
+            return Err(utils::Error::misc(format!("Unknown signal `{name}'")));
+        };
+        let mut memo = std::mem::take(&mut self.memo);
+        let v = self.eval(sig, &mut memo);
+        self.memo = memo;
+        v
+    }
+
+    /// Advance the simulation by one clock cycle
+    pub fn tick(&mut self) -> FilamentResult<()> {
+        let mut memo = std::mem::take(&mut self.memo);
+        let gos = self
+            .fsms
+            .iter()
+            .map(|fsm| Ok(self.eval(fsm.go, &mut memo)?.is_true()))
+            .collect::<FilamentResult<Vec<_>>>()?;
+        let states = self
+            .cells
+            .iter()
+            .map(|cell| {
+                if cell.prim.is_stateful() {
+                    cell.prim
+                        .next_state(&cell.params, &cell.state, |p| {
+                            self.cell_input(cell, p, &mut memo)
+                        })
+                        .map(Some)
+                } else {
+                    Ok(None)
+                }
+            })
+            .collect::<FilamentResult<Vec<_>>>()?;
+        self.memo = memo;
+
+        for (fsm, go) in self.fsms.iter_mut().zip(gos) {
+            if !fsm.hist.is_empty() {
+                fsm.hist.rotate_right(1);
+                fsm.hist[0] = go;
+            }
+        }
+        for (cell, st) in self.cells.iter_mut().zip(states) {
+            if let Some(st) = st {
+                cell.state = st;
+            }
+        }
+        self.invalidate();
+        Ok(())
+    }
+}
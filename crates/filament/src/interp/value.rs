@@ -0,0 +1,130 @@
+use fil_utils::{self as utils, FilamentResult};
+use num_bigint::BigUint;
+
+/// A bit vector value of a fixed width.
+/// Values are either fully known or fully unknown (`'x`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Value {
+    width: u64,
+    bits: BigUint,
+    unknown: bool,
+}
+
+impl Value {
+    /// The mask with the lower `width` bits set.
+    fn mask(width: u64) -> BigUint {
+        (BigUint::from(1u32) << width) - 1u32
+    }
+
+    /// Construct a value, truncating `bits` to `width`.
+    pub fn new(width: u64, bits: BigUint) -> Self {
+        Self {
+            width,
+            bits: bits & Self::mask(width),
+            unknown: false,
+        }
+    }
+
+    pub fn zero(width: u64) -> Self {
+        Self::new(width, BigUint::ZERO)
+    }
+
+    /// A value with all bits unknown
+    pub fn unknown(width: u64) -> Self {
+        Self {
+            unknown: true,
+            ..Self::zero(width)
+        }
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.unknown
+    }
+
+    pub fn from_u64(width: u64, v: u64) -> Self {
+        Self::new(width, BigUint::from(v))
+    }
+
+    pub fn from_bool(b: bool) -> Self {
+        Self::from_u64(1, b as u64)
+    }
+
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    pub fn bits(&self) -> &BigUint {
+        &self.bits
+    }
+
+    pub fn is_true(&self) -> bool {
+        self.bits != BigUint::ZERO
+    }
+
+    /// The value of the bit at position `idx`
+    pub fn bit(&self, idx: u64) -> bool {
+        self.bits.bit(idx)
+    }
+
+    /// The most significant bit of the value
+    pub fn msb(&self) -> bool {
+        self.width > 0 && self.bit(self.width - 1)
+    }
+
+    /// All ones in the given width
+    pub fn ones(width: u64) -> Self {
+        Self::new(width, Self::mask(width))
+    }
+
+    /// Zero-extend or truncate the value to `width` bits.
+    pub fn resize(&self, width: u64) -> Self {
+        Self {
+            unknown: self.unknown,
+            ..Self::new(width, self.bits.clone())
+        }
+    }
+
+    /// Interpret the value as a shift amount or index.
+    /// Saturates at [u64::MAX] for values that do not fit in 64 bits.
+    pub fn as_u64(&self) -> u64 {
+        u64::try_from(&self.bits).unwrap_or(u64::MAX)
+    }
+
+    /// Parse a value from the data file format. Values are either JSON
+    /// numbers or strings with an optional `0x`, `0o`, or `0b` prefix.
+    pub fn parse(v: &serde_json::Value, width: u64) -> FilamentResult<Self> {
+        let bits = match v {
+            serde_json::Value::Number(n) => n.as_u64().map(BigUint::from),
+            serde_json::Value::String(s) => {
+                let (radix, digits) = match s.get(..2) {
+                    Some("0x" | "0X") => (16, &s[2..]),
+                    Some("0o" | "0O") => (8, &s[2..]),
+                    Some("0b" | "0B") => (2, &s[2..]),
+                    _ => (10, s.as_str()),
+                };
+                BigUint::parse_bytes(digits.as_bytes(), radix)
+            }
+            _ => None,
+        }
+        .ok_or_else(|| {
+            utils::Error::misc(format!("Invalid value `{v}' in data file"))
+        })?;
+        if bits > Self::mask(width) {
+            return Err(utils::Error::misc(format!(
+                "Value {v} not representable in {width} bits"
+            )));
+        }
+        Ok(Self::new(width, bits))
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Unknown values are printed like cocotb's binary strings
+        if self.unknown {
+            write!(f, "\"{}\"", "x".repeat(self.width as usize))
+        } else {
+            write!(f, "{}", self.bits)
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use crate::{
+    cmdline,
+    ir_visitor::{Action, Construct, Visitor, VisitorData},
+};
+use codespan_reporting::diagnostic::Diagnostic;
+use fil_ast as ast;
+use fil_ir::{self as ir, AddCtx, Ctx, MutCtx};
+use fil_utils::{self as utils, Diagnostics, Error, GPosIdx};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// A primitive used to resize a port along with its input and output ports
+struct Prim {
+    idx: ir::CompIdx,
+    /// The events `'G` and `'L` bound to the start and end of the interval
+    events: (ir::EventIdx, ir::EventIdx),
+    input: (ir::PortIdx, ast::Id),
+    output: (ir::PortIdx, ast::Id),
+}
+
+impl Prim {
+    /// The primitive defined by `comp` if it has the signature of `name` in
+    /// `primitives/comb.fil`. Otherwise, returns the signature it is expected
+    /// to have.
+    fn new(
+        name: &str,
+        idx: ir::CompIdx,
+        comp: &ir::Component,
+    ) -> Result<Self, String> {
+        let params = if name == "Slice" { 4 } else { 2 };
+        let expected = format!(
+            "`{name}' must have {params} parameters, the events `'G' and `'L', and a single input and output"
+        );
+        let Some(src) = &comp.src_info else {
+            return Err(expected);
+        };
+        let event = |n: &str| {
+            src.events
+                .iter()
+                .find(|(_, e)| e.as_ref() == n)
+                .map(|(ev, _)| ev)
+        };
+        let (Some(start), Some(end)) = (event("G"), event("L")) else {
+            return Err(expected);
+        };
+        let (Ok((input, _)), Ok((output, _))) =
+            (comp.inputs().exactly_one(), comp.outputs().exactly_one())
+        else {
+            return Err(expected);
+        };
+        if comp.events().len() != 2 || comp.sig_params().count() != params {
+            return Err(expected);
+        }
+        Ok(Prim {
+            idx,
+            events: (start, end),
+            input: (input, *src.ports.get(input)),
+            output: (output, *src.ports.get(output)),
+        })
+    }
+}
+
+/// Resizes the sources of connections between ports of different widths.
+///
+/// Narrower sources are extended using `ZeroExtend`, or `SignExtend` if they
+/// are signed, and wider sources are truncated using `Slice`. Widths are only
+/// known after monomorphization so the pass runs on the monomorphized program
+/// and reports every primitive that it inserts. The primitives are defined in
+/// `primitives/comb.fil` which must be imported by the program.
+pub struct AutoResize {
+    /// Primitives with the expected signature or the reason they cannot be
+    /// used
+    prims: HashMap<&'static str, (Result<Prim, String>, GPosIdx)>,
+    diag: Diagnostics,
+}
+
+impl AutoResize {
+    /// Names of the primitives used to resize ports.
+    /// Monomorphization keeps them even if the program does not use them.
+    pub const PRIMITIVES: [&'static str; 3] =
+        ["ZeroExtend", "SignExtend", "Slice"];
+
+    /// Define a port on the invocation of a resizing primitive
+    fn port(
+        (base, name): &(ir::PortIdx, ast::Id),
+        owner: (ir::InvIdx, ir::Direction, ir::CompIdx),
+        width: u64,
+        range: ir::Range,
+        comp: &mut ir::Component,
+    ) -> ir::PortIdx {
+        let (inv, dir, prim) = owner;
+        let width = comp.num(width);
+        let one = comp.num(1);
+        let info = comp.add(ir::Info::port(
+            *name,
+            GPosIdx::UNKNOWN,
+            GPosIdx::UNKNOWN,
+            GPosIdx::UNKNOWN,
+        ));
+        let pidx = comp.add(ir::Port {
+            owner: ir::PortOwner::Inv {
+                inv,
+                dir,
+                base: ir::Foreign::new(*base, prim),
+            },
+            width,
+            live: ir::Liveness {
+                idxs: vec![],
+                lens: vec![one],
+                range,
+            },
+            info,
+            interp: None,
+        });
+        comp.port_attrs.push(pidx, utils::PortAttrs::default());
+
+        // Define the dummy bundle index of the port
+        let param = ir::Param {
+            owner: ir::ParamOwner::bundle(pidx),
+            info: comp.add(ir::Info::param("_".into(), GPosIdx::UNKNOWN)),
+        };
+        let param = comp.add(param);
+        comp.get_mut(pidx).live.idxs.push(param);
+
+        pidx
+    }
+}
+
+impl Construct for AutoResize {
+    fn from(_: &cmdline::Opts, ctx: &mut ir::Context) -> Self {
+        let prims = ctx
+            .iter()
+            .filter(|(_, comp)| comp.is_ext() && !comp.is_gen())
+            .filter_map(|(idx, comp)| {
+                let src = comp.src_info.as_ref()?;
+                let name = Self::PRIMITIVES
+                    .into_iter()
+                    .find(|n| src.name.as_ref() == *n)?;
+                Some((name, (Prim::new(name, idx, comp), comp.pos)))
+            })
+            .collect();
+
+        AutoResize {
+            prims,
+            diag: Diagnostics::default(),
+        }
+    }
+
+    fn clear_data(&mut self) {
+        /* Primitives and diagnostics are shared */
+    }
+}
+
+impl Visitor for AutoResize {
+    fn name() -> &'static str {
+        "auto-resize"
+    }
+
+    fn connect(
+        &mut self,
+        c: &mut ir::Connect,
+        data: &mut VisitorData,
+    ) -> Action {
+        let comp = &mut data.comp;
+        let ir::Connect { src, dst, info } = c.clone();
+        let (Some(src_w), Some(dst_w)) = (
+            comp.get(src.port).width.as_concrete(comp),
+            comp.get(dst.port).width.as_concrete(comp),
+        ) else {
+            return Action::Continue;
+        };
+        if src_w == dst_w {
+            return Action::Continue;
+        }
+        let &ir::info::Connect { dst_loc, src_loc } = comp.get(info).into();
+
+        let signed =
+            matches!(comp.get(src.port).interp, Some(ir::Interp::Signed));
+        let (name, args) = match (src_w < dst_w, signed) {
+            (true, true) => ("SignExtend", vec![src_w, dst_w]),
+            (true, false) => ("ZeroExtend", vec![src_w, dst_w]),
+            (false, _) => ("Slice", vec![src_w, dst_w - 1, 0, dst_w]),
+        };
+        let prim = match self.prims.get(name) {
+            Some((Ok(prim), _)) => prim,
+            Some((Err(expected), def)) => {
+                let err = Error::misc(format!(
+                    "cannot resize source of width {src_w} to width {dst_w} because `{name}' has an unexpected signature"
+                ))
+                .add_note(self.diag.add_info(
+                    format!("source has width {src_w}"),
+                    src_loc,
+                ))
+                .add_note(self.diag.add_info(format!("`{name}' defined here"), *def))
+                .add_note(self.diag.add_message(expected));
+                self.diag.add_error(err);
+                return Action::Continue;
+            }
+            None => {
+                let err = Error::misc(format!(
+                "cannot resize source of width {src_w} to width {dst_w} without `{name}'"
+            ))
+            .add_note(self.diag.add_info(
+                format!("source has width {src_w}"),
+                src_loc,
+            ))
+            .add_note(self.diag.add_info(
+                format!("destination has width {dst_w}"),
+                dst_loc,
+            ))
+            .add_note(self.diag.add_message(
+                "import `primitives/comb.fil' to use `--auto-resize`",
+            ));
+                self.diag.add_error(err);
+                return Action::Continue;
+            }
+        };
+
+        // Report the insertion
+        let prim_name = format!("{name}[{}]", args.iter().join(", "));
+        let mut warning = Diagnostic::warning().with_message(format!(
+            "inserted `{prim_name}' to resize source of width {src_w} to width {dst_w}"
+        ));
+        if src_loc != GPosIdx::UNKNOWN {
+            warning = warning.with_labels(vec![
+                src_loc
+                    .primary()
+                    .with_message(format!("source has width {src_w}")),
+            ]);
+        }
+        Diagnostics::emit(warning);
+
+        let args = args.into_iter().map(|a| comp.num(a)).collect();
+        let info = comp.add(ir::Info::instance(
+            "resize".into(),
+            GPosIdx::UNKNOWN,
+            src_loc,
+            vec![],
+        ));
+        let inst = comp.add(ir::Instance {
+            comp: prim.idx,
+            args,
+            lives: vec![],
+            params: vec![],
+            info,
+        });
+
+        // The primitives are combinational and are invoked for exactly as long
+        // as the destination requires the value: `'G` is bound to the start of
+        // the interval and `'L` to its end.
+        let range = comp.get(dst.port).live.range.clone();
+        let info = comp.add(ir::Info::invoke(
+            "resize".into(),
+            GPosIdx::UNKNOWN,
+            src_loc,
+            vec![],
+        ));
+        let inv = comp.add(ir::Invoke {
+            inst,
+            events: vec![],
+            ports: vec![],
+            info,
+        });
+        let delay = range.end.sub(range.start, comp);
+        let one = comp.num(1);
+        let info = comp.add(ir::Info::event_bind(GPosIdx::UNKNOWN, src_loc));
+        let events = vec![
+            ir::EventBind::new(
+                delay,
+                range.start,
+                info,
+                ir::Foreign::new(prim.events.0, prim.idx),
+            ),
+            ir::EventBind::new(
+                ir::TimeSub::Unit(one),
+                range.end,
+                info,
+                ir::Foreign::new(prim.events.1, prim.idx),
+            ),
+        ];
+
+        let input = Self::port(
+            &prim.input,
+            (inv, ir::Direction::In, prim.idx),
+            src_w,
+            range.clone(),
+            comp,
+        );
+        let output = Self::port(
+            &prim.output,
+            (inv, ir::Direction::Out, prim.idx),
+            dst_w,
+            range,
+            comp,
+        );
+        let invoke = comp.get_mut(inv);
+        invoke.events = events;
+        invoke.ports = vec![input, output];
+
+        let info = comp.add(ir::Info::connect(dst_loc, src_loc));
+        Action::Change(vec![
+            inst.into(),
+            inv.into(),
+            ir::Connect {
+                src,
+                dst: ir::Access::port(input, comp),
+                info,
+            }
+            .into(),
+            ir::Connect {
+                src: ir::Access::port(output, comp),
+                dst,
+                info,
+            }
+            .into(),
+        ])
+    }
+
+    fn after_traversal(&mut self) -> Option<u64> {
+        self.diag.report_all()
+    }
+}
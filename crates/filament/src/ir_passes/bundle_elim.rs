@@ -77,6 +77,7 @@ impl BundleElim {
             width,
             live,
             info,
+            interp,
         } = comp.get(pidx).clone();
 
         let attrs = comp.port_attrs.get(pidx).clone();
@@ -165,6 +166,7 @@ impl BundleElim {
                     owner,
                     info, // duplicate the info
                     width,
+                    interp: interp.clone(),
                 });
 
                 // copy over the attributes
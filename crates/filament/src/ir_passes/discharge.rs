@@ -1,17 +1,23 @@
+use super::discharge_cache::DischargeCache;
 use crate::cmdline;
-use crate::ir_visitor::{Action, Construct, Visitor, VisitorData};
+use crate::ir_visitor::{Construct, Visitor};
 use crate::log_time;
 use crate::utils::HoistFacts;
+use codespan_reporting::diagnostic as cr;
 use codespan_reporting::diagnostic::Diagnostic;
-use codespan_reporting::{diagnostic as cr, term};
+use codespan_reporting::files::Files;
 use easy_smt as smt;
 use fil_ast as ast;
 use fil_ir::{self as ir, Ctx, DisplayCtx};
-use fil_utils::GlobalPositionTable;
+use fil_utils::{Diagnostics, GPosIdx, GlobalPositionTable, MessageFormat};
 use itertools::Itertools;
-use std::collections::{HashMap, HashSet};
-use std::{fs, iter};
-use term::termcolor::{ColorChoice, StandardStream};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{fs, iter, thread};
 
 // We need this so we can implement Default for SExprs, allowing us to push to the prop_map out of order, when handling if-exprs
 #[derive(Clone, Copy)]
@@ -37,6 +43,9 @@ impl SExprWrapper {
     }
 }
 
+/// Values of parameters that falsify a constraint
+type Counterexample = BTreeMap<String, String>;
+
 #[derive(Default)]
 struct Assign(Vec<(ir::ParamIdx, String)>);
 
@@ -45,24 +54,42 @@ impl Assign {
         self.0.is_empty()
     }
 
+    /// Assignments to parameters that are not zero
+    fn nonzero(&self) -> impl Iterator<Item = &(ir::ParamIdx, String)> {
+        // Attempt to parse value as a number
+        self.0.iter().filter(|(_, v)| v.parse::<i64>() != Ok(0))
+    }
+
     fn display(&self, ctx: &ir::Component) -> String {
+        self.nonzero()
+            .map(|(k, v)| format!("{} = {v}", ctx.display(*k)))
+            .join(", ")
+    }
+
+    /// Values of all parameters in the assignment
+    fn to_map(&self, ctx: &ir::Component) -> Counterexample {
         self.0
             .iter()
-            .filter_map(|(k, v)| {
-                // Attempt to parse value as a number
-                match v.parse::<i64>() {
-                    Ok(0) => None,
-                    _ => Some(format!("{} = {v}", ctx.display(*k))),
-                }
-            })
-            .join(", ")
+            .map(|(k, v)| (ctx.display(*k), v.clone()))
+            .collect()
     }
 }
 
+/// Facts of a component that are discharged together by a solver
+struct Job {
+    comp: ir::CompIdx,
+    facts: Vec<ir::Fact>,
+}
+
 /// Pass to discharge top-level `assert` statements in the IR and turn them into
 /// `assume` if they are true. Any assertions within the body are left as-is.
 /// Run [super::HoistFacts] before this pass to ensure that all facts are
 /// top-level.
+///
+/// Components are independent of each other once their signatures are known
+/// so they are discharged concurrently by a pool of solver processes. With
+/// `--discharge-separate`, the facts of a component are also split between
+/// the solvers. Diagnostics are reported in the order of the components.
 pub struct Discharge {
     sol: smt::Context,
     /// Are we using a bitvector encoding
@@ -90,16 +117,87 @@ pub struct Discharge {
 
     /// Report the unsatisfied constraint and generate a model
     show_models: bool,
+    /// Generate a model to report as a structured counterexample
+    json: bool,
+    /// Report the assumptions relevant to failing constraints
+    explain: bool,
+    /// Locations of the assumptions in the component
+    assumes: HashMap<ir::PropIdx, GPosIdx>,
+    /// Labels for the assumptions relevant to propositions that failed
+    explained: HashMap<ir::PropIdx, Vec<cr::Label<usize>>>,
 
     to_prove: Vec<ir::Fact>,
 
-    // Diagnostics to be reported
-    diagnostics: Vec<cr::Diagnostic<usize>>,
-    /// Number of errors encountered
-    error_count: u64,
+    /// Directory to write the proof obligations to
+    obligations: Option<PathBuf>,
+    /// Commands sent to the solver for all components
+    prelude: Vec<smt::SExpr>,
+    /// Commands sent to the solver for the current component
+    script: Vec<smt::SExpr>,
+
+    // Diagnostics to be reported along with the counterexample for them
+    diagnostics: Vec<(cr::Diagnostic<usize>, Option<Counterexample>)>,
 }
 
 impl Discharge {
+    /// Start a solver with functions for the `some` parameters of all the
+    /// components in the context
+    fn new(opts: &cmdline::Opts, ctx: &ir::Context) -> Self {
+        let mut out = Self {
+            bv_size: opts.solver_bv,
+            sol: Self::conf_solver(opts),
+            sol_base: opts.solver,
+            act_lit_count: 0,
+            to_prove: vec![],
+            show_models: opts.show_models,
+            json: opts.message_format == MessageFormat::Json,
+            explain: opts.explain,
+            assumes: Default::default(),
+            explained: Default::default(),
+            func_map: Default::default(),
+            param_map: Default::default(),
+            prop_map: Default::default(),
+            time_map: Default::default(),
+            ev_map: Default::default(),
+            expr_map: Default::default(),
+            checked: Default::default(),
+            diagnostics: Default::default(),
+            comp_param_map: Default::default(),
+            obligations: opts.emit_obligations.clone(),
+            prelude: vec![],
+            script: vec![],
+        };
+
+        out.define_funcs();
+
+        // For each `some` parameter of a component, define function from the
+        // input parameters of the component to the `some` parameter.
+        for (comp_idx, comp) in ctx.comps.iter() {
+            let num_args = comp.param_args().len();
+            for some_param in comp.exist_params() {
+                let ir::ParamOwner::Exists { opaque } = &comp[some_param].owner
+                else {
+                    unreachable!()
+                };
+                if *opaque {
+                    // If this is an opaque parameter, then we don't define the function
+                    continue;
+                }
+                let func = out.declare_fun(
+                    format!("comp{}_param{}", comp_idx.get(), some_param.get()),
+                    (0..num_args).map(|_| out.sort()).collect_vec(),
+                    out.sort(),
+                );
+                let f = ir::Foreign::new(some_param, comp_idx);
+                out.comp_param_map.insert(f, func);
+            }
+        }
+
+        out.prelude = std::mem::take(&mut out.script);
+        out.sol.push_many(1).unwrap();
+        out
+    }
+
     /// Configure solver to use in this pass
     fn conf_solver(opts: &cmdline::Opts) -> smt::Context {
         let (name, s_opts) = match opts.solver {
@@ -225,65 +323,63 @@ impl Discharge {
         let ge_zero = self.gte(e, zero);
         let lt_max = self.gt(max, e);
         let and = self.sol.and(ge_zero, lt_max);
-        self.sol.assert(and).unwrap();
+        self.assert(and);
     }
-}
 
-impl Construct for Discharge {
-    fn from(opts: &cmdline::Opts, ctx: &mut ir::Context) -> Self {
-        let mut out = Self {
-            bv_size: opts.solver_bv,
-            sol: Self::conf_solver(opts),
-            sol_base: opts.solver,
-            error_count: 0,
-            act_lit_count: 0,
-            to_prove: vec![],
-            show_models: opts.show_models,
-            func_map: Default::default(),
-            param_map: Default::default(),
-            prop_map: Default::default(),
-            time_map: Default::default(),
-            ev_map: Default::default(),
-            expr_map: Default::default(),
-            checked: Default::default(),
-            diagnostics: Default::default(),
-            comp_param_map: Default::default(),
-        };
+    /// Declare a function in the solver
+    fn declare_fun(
+        &mut self,
+        name: String,
+        args: Vec<smt::SExpr>,
+        out: smt::SExpr,
+    ) -> smt::SExpr {
+        if self.obligations.is_some() {
+            let cmd = self.sol.list(vec![
+                self.sol.atom("declare-fun"),
+                self.sol.atom(&name),
+                self.sol.list(args.clone()),
+                out,
+            ]);
+            self.script.push(cmd);
+        }
+        self.sol.declare_fun(name, args, out).unwrap()
+    }
 
-        out.define_funcs();
+    /// Define a constant in the solver
+    fn define_const(
+        &mut self,
+        name: String,
+        sort: smt::SExpr,
+        value: smt::SExpr,
+    ) -> io::Result<smt::SExpr> {
+        let sexp = self.sol.define_const(name, sort, value)?;
+        if self.obligations.is_some() {
+            let cmd = self.sol.list(vec![
+                self.sol.atom("define-fun"),
+                sexp,
+                self.sol.list(vec![]),
+                sort,
+                value,
+            ]);
+            self.script.push(cmd);
+        }
+        Ok(sexp)
+    }
 
-        // For each `some` parameter of a component, define function from the
-        // input parameters of the component to the `some` parameter.
-        for (comp_idx, comp) in ctx.comps.iter() {
-            let num_args = comp.param_args().len();
-            for some_param in comp.exist_params() {
-                let ir::ParamOwner::Exists { opaque } = &comp[some_param].owner
-                else {
-                    unreachable!()
-                };
-                if *opaque {
-                    // If this is an opaque parameter, then we don't define the function
-                    continue;
-                }
-                let func = out
-                    .sol
-                    .declare_fun(
-                        format!(
-                            "comp{}_param{}",
-                            comp_idx.get(),
-                            some_param.get()
-                        ),
-                        (0..num_args).map(|_| out.sort()).collect_vec(),
-                        out.sort(),
-                    )
-                    .unwrap();
-                let f = ir::Foreign::new(some_param, comp_idx);
-                out.comp_param_map.insert(f, func);
-            }
+    /// Assert a fact about the encoding. Activation literals are asserted
+    /// directly because they are specific to a query.
+    fn assert(&mut self, e: smt::SExpr) {
+        if self.obligations.is_some() {
+            let cmd = self.sol.list(vec![self.sol.atom("assert"), e]);
+            self.script.push(cmd);
         }
+        self.sol.assert(e).unwrap();
+    }
+}
 
-        out.sol.push_many(1).unwrap();
-        out
+impl Construct for Discharge {
+    fn from(opts: &cmdline::Opts, ctx: &mut ir::Context) -> Self {
+        Self::new(opts, ctx)
     }
 
     fn clear_data(&mut self) {
@@ -293,9 +389,12 @@ impl Construct for Discharge {
         self.ev_map.clear();
         self.expr_map.clear();
         self.checked.clear();
+        self.assumes.clear();
+        self.explained.clear();
         self.diagnostics.clear();
         self.act_lit_count = 0;
         self.to_prove.clear();
+        self.script.clear();
 
         // Create a new solver context
         self.sol.pop_many(1).unwrap();
@@ -353,10 +452,8 @@ impl Discharge {
 
         macro_rules! sol_fn(
             ($name:tt($($args:ident),*) -> $out:ident) => {
-                self.func_map.insert(ast::Fn::$name, self
-                    .sol
-                    .declare_fun(stringify!($name).to_lowercase(), vec![$($args),*], $out)
-                    .unwrap());
+                let f = self.declare_fun(stringify!($name).to_lowercase(), vec![$($args),*], $out);
+                self.func_map.insert(ast::Fn::$name, f);
             }
         );
 
@@ -430,7 +527,7 @@ impl Discharge {
             );
             let out = match res {
                 smt::Response::Sat => {
-                    if self.show_models {
+                    if self.show_models || self.json {
                         Some(self.get_assignments(
                             ctx.prop_params(prop.consequent(ctx)),
                         ))
@@ -443,6 +540,10 @@ impl Discharge {
             };
             // Deassert the actlit after the `get-model` call.
             self.sol.assert(self.sol.not(actlit)).unwrap();
+            if self.explain && out.is_some() {
+                let labels = self.explanation(prop, ctx);
+                self.explained.insert(prop, labels);
+            }
             self.checked.insert(prop, out);
         }
         if let Some(assign) = &self.checked[&prop] {
@@ -458,12 +559,16 @@ impl Discharge {
                     "No information was given on who generated this error. Please report this as a bug in the compiler with the program that triggered it."
                         .to_string(),
                 ]);
-                self.diagnostics.push(diag);
+                self.diagnostics.push((diag, None));
                 return;
             };
+            let counterexample = self.json.then(|| assign.to_map(ctx));
             let mut diag = reason.diag(ctx);
+            if let Some(labels) = self.explained.get(&prop) {
+                diag = diag.with_labels(labels.clone());
+            }
             if self.show_models {
-                diag = reason.diag(ctx).with_notes(vec![format!(
+                diag = diag.with_notes(vec![format!(
                     "Cannot prove constraint: {}",
                     ctx.display(fact.prop.consequent(ctx))
                 )]);
@@ -474,7 +579,152 @@ impl Discharge {
                     )]);
                 }
             }
-            self.diagnostics.push(diag);
+            self.diagnostics.push((diag, counterexample));
+        }
+    }
+
+    /// Secondary labels for the assumptions relevant to a proposition that
+    /// cannot be proven. If the assumptions contradict the consequent, a
+    /// minimal unsat core of the contradiction is reported. Otherwise, we report
+    /// the assumptions that mention the parameters and events of the consequent.
+    fn explanation(
+        &mut self,
+        prop: ir::PropIdx,
+        ctx: &ir::Component,
+    ) -> Vec<cr::Label<usize>> {
+        // Split the antecedents of the (possibly nested) implication
+        let mut conjuncts = Vec::new();
+        let mut cons = prop;
+        while let ir::Prop::Implies(ante, c) = ctx.get(cons) {
+            Self::conjuncts(*ante, ctx, &mut conjuncts);
+            cons = *c;
+        }
+        let (assumes, hyps): (Vec<_>, Vec<_>) = conjuncts
+            .into_iter()
+            .unique()
+            .partition(|p| self.assumes.contains_key(p));
+        if assumes.is_empty() {
+            return vec![];
+        }
+
+        // Name each assumption with an activation literal
+        let lits = assumes
+            .iter()
+            .map(|p| {
+                let lit = self.new_act_lit();
+                let imp = self.sol.imp(lit, self.prop_map[*p].get());
+                self.sol.assert(imp).unwrap();
+                lit
+            })
+            .collect_vec();
+        // The goal holds along with the unnamed antecedents
+        let goal = self.new_act_lit();
+        let body = self.sol.and_many(
+            hyps.iter()
+                .chain(iter::once(&cons))
+                .map(|p| self.prop_map[*p].get()),
+        );
+        let imp = self.sol.imp(goal, body);
+        self.sol.assert(imp).unwrap();
+
+        let labels = if self.unsat_with(&lits, goal) {
+            // Compute a minimal core by removing assumptions that are not
+            // needed for the contradiction. We don't use `get-unsat-core`
+            // because not all supported solvers can produce cores.
+            let mut core = lits.clone();
+            for lit in &lits {
+                let rest =
+                    core.iter().copied().filter(|l| l != lit).collect_vec();
+                if self.unsat_with(&rest, goal) {
+                    core = rest;
+                }
+            }
+            assumes
+                .iter()
+                .zip(&lits)
+                .filter(|(_, lit)| core.contains(lit))
+                .map(|(p, _)| {
+                    self.assumes[p]
+                        .secondary()
+                        .with_message("constraint contradicts the requirement")
+                })
+                .collect_vec()
+        } else {
+            let (params, events) = cons.relevant_vars(ctx);
+            assumes
+                .iter()
+                .filter(|p| {
+                    let (ps, evs) = p.relevant_vars(ctx);
+                    ps.iter().any(|p| params.contains(p))
+                        || evs.iter().any(|e| events.contains(e))
+                })
+                .map(|p| {
+                    self.assumes[p]
+                        .secondary()
+                        .with_message("related constraint")
+                })
+                .collect_vec()
+        };
+
+        // Disable the literals
+        for lit in lits.into_iter().chain(iter::once(goal)) {
+            self.sol.assert(self.sol.not(lit)).unwrap();
+        }
+        labels.into_iter().unique_by(|l| l.range.clone()).collect()
+    }
+
+    /// Are the literals unsatisfiable together with the goal?
+    fn unsat_with(&mut self, lits: &[smt::SExpr], goal: smt::SExpr) -> bool {
+        let res = self
+            .sol
+            .check_assuming(lits.iter().copied().chain(iter::once(goal)))
+            .unwrap();
+        matches!(res, smt::Response::Unsat)
+    }
+
+    /// Split a proposition into its conjuncts
+    fn conjuncts(
+        prop: ir::PropIdx,
+        ctx: &ir::Component,
+        acc: &mut Vec<ir::PropIdx>,
+    ) {
+        match ctx.get(prop) {
+            ir::Prop::And(l, r) => {
+                Self::conjuncts(*l, ctx, acc);
+                Self::conjuncts(*r, ctx, acc);
+            }
+            _ => acc.push(prop),
+        }
+    }
+
+    /// Record the locations of the assumptions in the commands
+    fn collect_assumes(&mut self, cmds: &[ir::Command], comp: &ir::Component) {
+        for cmd in cmds {
+            match cmd {
+                ir::Command::Fact(f) if f.is_assume() => {
+                    let loc = comp.get(f.reason).as_assert().and_then(
+                        |ir::info::Assert(reason)| match reason {
+                            ir::info::Reason::Misc { def_loc, .. } => {
+                                def_loc.into_option()
+                            }
+                            ir::info::Reason::ExistsConstraint {
+                                constraint_loc,
+                                ..
+                            } => *constraint_loc,
+                            _ => None,
+                        },
+                    );
+                    if let Some(loc) = loc {
+                        self.assumes.insert(f.prop, loc);
+                    }
+                }
+                ir::Command::ForLoop(l) => self.collect_assumes(&l.body, comp),
+                ir::Command::If(i) => {
+                    self.collect_assumes(&i.then, comp);
+                    self.collect_assumes(&i.alt, comp);
+                }
+                _ => (),
+            }
         }
     }
 
@@ -575,36 +825,205 @@ impl Discharge {
     }
 }
 
-impl Visitor for Discharge {
-    fn name() -> &'static str {
-        "discharge"
+impl Discharge {
+    /// Discharge the facts in a job and return the diagnostics for the ones
+    /// that cannot be proven
+    fn check(
+        &mut self,
+        job: &Job,
+        comp: &ir::Component,
+        separate: bool,
+    ) -> Vec<(cr::Diagnostic<usize>, Option<Counterexample>)> {
+        self.clear_data();
+        self.to_prove = job.facts.clone();
+        self.encode(comp);
+        self.instances(&comp.cmds, comp);
+        if self.explain {
+            self.collect_assumes(&comp.cmds, comp);
+        }
+        let mut failed = vec![];
+        if let Some(dir) = &self.obligations {
+            for fact in &job.facts {
+                if let Err(e) = self.emit_obligation(fact, job.comp, comp, dir)
+                {
+                    failed.push((Diagnostic::error().with_message(e), None));
+                }
+            }
+        }
+        self.prove(comp, separate);
+        self.diagnostics.extend(failed);
+        std::mem::take(&mut self.diagnostics)
     }
 
-    fn start(&mut self, data: &mut VisitorData) -> Action {
-        self.to_prove = HoistFacts::hoist(&mut data.comp);
+    /// Write a self-contained SMT-LIB query for the fact to the directory.
+    /// The query is unsatisfiable if and only if the fact holds.
+    /// Returns a description of the error if the query cannot be written.
+    fn emit_obligation(
+        &self,
+        fact: &ir::Fact,
+        idx: ir::CompIdx,
+        comp: &ir::Component,
+        dir: &Path,
+    ) -> Result<(), String> {
+        let goal = self.prop_map[fact.prop].get();
+        let name = comp
+            .user_name()
+            .unwrap_or_else(|| format!("comp{}", idx.get()));
+        let reason = comp
+            .get(fact.reason)
+            .as_assert()
+            .map(|ir::info::Assert(r)| r);
+
+        // Source location of the assertion
+        let files = GlobalPositionTable::get().files();
+        let loc = reason.and_then(|r| r.loc().into_option()).and_then(|loc| {
+            let label = loc.primary();
+            let file = files.name(label.file_id).ok()?;
+            let pos = files.location(label.file_id, label.range.start).ok()?;
+            Some((file, pos.line_number, pos.column_number))
+        });
+
+        let mut out = String::new();
+        writeln!(out, "; {name}: {}", reason.map_or("Unknown", |r| r.name()))
+            .unwrap();
+        if let Some((file, line, col)) = &loc {
+            writeln!(out, "; {file}:{line}:{col}").unwrap();
+        }
+        writeln!(out, "; {}", comp.display(fact.prop.consequent(comp)))
+            .unwrap();
+        writeln!(out, "(set-logic ALL)").unwrap();
+        for cmd in self.relevant_cmds(goal) {
+            writeln!(out, "{}", self.sol.display(cmd)).unwrap();
+        }
+        writeln!(out, "(assert (not {}))", self.sol.display(goal)).unwrap();
+        writeln!(out, "(check-sat)").unwrap();
+
+        let stem = match &loc {
+            Some((file, line, col)) => {
+                let file = Path::new(file)
+                    .file_stem()
+                    .map_or(file.clone(), |f| f.to_string_lossy().to_string());
+                format!("{name}_{file}_{line}_{col}")
+            }
+            None => format!("{name}_{}", fact.prop.get()),
+        };
+        let stem = stem
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
 
-        for fact in &self.to_prove {
-            log::debug!("Checking {}", data.comp.display(fact.prop));
+        // Assertions at the same location get distinct files
+        for n in 1.. {
+            let path = if n == 1 {
+                dir.join(format!("{stem}.smt2"))
+            } else {
+                dir.join(format!("{stem}_{n}.smt2"))
+            };
+            match fs::File::create_new(&path) {
+                Ok(mut f) => {
+                    f.write_all(out.as_bytes()).map_err(|e| {
+                        format!("failed to write {}: {e}", path.display())
+                    })?;
+                    log::info!("Wrote obligation to {}", path.display());
+                    return Ok(());
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(format!(
+                        "failed to create {}: {e}",
+                        path.display()
+                    ));
+                }
+            }
         }
+        unreachable!("ran out of obligation file names")
+    }
 
-        let comp = &data.comp;
-        // Declare all parameters
+    /// The commands sent to the solver that the goal depends on. A definition
+    /// is needed if its name is used by the goal or another needed command and
+    /// an assertion is needed if it mentions a needed name.
+    fn relevant_cmds(&self, goal: smt::SExpr) -> Vec<smt::SExpr> {
+        let cmds = self
+            .prelude
+            .iter()
+            .chain(&self.script)
+            .copied()
+            .collect_vec();
+        let mut needed = HashSet::new();
+        Self::atoms(&self.sol, goal, &mut needed);
+        let mut keep = vec![false; cmds.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (cmd, keep) in cmds.iter().zip(keep.iter_mut()) {
+                if *keep {
+                    continue;
+                }
+                let smt::SExprData::List(parts) = self.sol.get(*cmd) else {
+                    unreachable!("commands are lists")
+                };
+                let used = if let smt::SExprData::Atom("assert") =
+                    self.sol.get(parts[0])
+                {
+                    let mut atoms = HashSet::new();
+                    Self::atoms(&self.sol, parts[1], &mut atoms);
+                    !atoms.is_disjoint(&needed)
+                } else {
+                    matches!(self.sol.get(parts[1]), smt::SExprData::Atom(n) if needed.contains(n))
+                };
+                if used {
+                    *keep = true;
+                    changed = true;
+                    for part in &parts[1..] {
+                        Self::atoms(&self.sol, *part, &mut needed);
+                    }
+                }
+            }
+        }
+        cmds.into_iter()
+            .zip(keep)
+            .filter_map(|(cmd, keep)| keep.then_some(cmd))
+            .collect()
+    }
+
+    /// Collect the atoms in an expression
+    fn atoms(sol: &smt::Context, e: smt::SExpr, acc: &mut HashSet<String>) {
+        match sol.get(e) {
+            smt::SExprData::Atom(a) => {
+                acc.insert(a.to_string());
+            }
+            smt::SExprData::String(_) => (),
+            smt::SExprData::List(es) => {
+                for e in es {
+                    Self::atoms(sol, *e, acc);
+                }
+            }
+        }
+    }
+
+    /// Define the parameters, events, expressions, and propositions of the
+    /// component
+    fn encode(&mut self, comp: &ir::Component) {
+        // Declare all parameters. Parameters are natural numbers.
         let int = self.sort();
-        for (idx, _) in data.comp.params().iter() {
-            let sexp = self
-                .sol
-                .declare_fun(self.fmt_param(idx, comp), vec![], int)
-                .unwrap();
+        for (idx, _) in comp.params().iter() {
+            let sexp = self.declare_fun(self.fmt_param(idx, comp), vec![], int);
             self.overflow_assert(sexp);
+            let zero = self.num(0);
+            let nat = self.gte(sexp, zero);
+            self.assert(nat);
             self.param_map.push(idx, sexp);
         }
 
         // Declare all events
-        for (idx, _) in data.comp.events().iter() {
-            let sexp = self
-                .sol
-                .declare_fun(self.fmt_event(idx, comp), vec![], int)
-                .unwrap();
+        for (idx, _) in comp.events().iter() {
+            let sexp = self.declare_fun(self.fmt_event(idx, comp), vec![], int);
             self.overflow_assert(sexp);
             self.ev_map.push(idx, sexp);
         }
@@ -614,62 +1033,55 @@ impl Visitor for Discharge {
         let mut interned_props = HashSet::new();
 
         // Declare all expressions
-        for (idx, expr) in data
-            .comp
-            .exprs()
-            .iter()
-            .filter(|(idx, _)| idx.valid(&data.comp))
+        for (idx, expr) in
+            comp.exprs().iter().filter(|(idx, _)| idx.valid(comp))
         {
             // do props inside of exprs ahead of time
             let relevant_props = idx
-                .relevant_props(&data.comp)
+                .relevant_props(comp)
                 .into_iter()
-                .map(|i| (i, data.comp.get(i)));
+                .map(|i| (i, comp.get(i)));
 
             for (pidx, prop) in relevant_props {
-                // Save the proposition in the interned_props set
-                interned_props.insert(pidx);
+                // Save the proposition in the interned_props set. Conditions
+                // may share sub-propositions so skip ones already defined.
+                if !interned_props.insert(pidx) {
+                    continue;
+                }
 
                 let assign = self.prop_to_sexp(prop);
                 let sexp = self
-                    .sol
                     .define_const(Discharge::fmt_prop(pidx), bs, assign)
                     .unwrap();
                 self.prop_map.insert(pidx, SExprWrapper::SExpr(sexp));
             }
 
             let assign = self.expr_to_sexp(expr);
-            let sexp = self
-                .sol
-                .define_const(Self::fmt_expr(idx), int, assign)
-                .unwrap();
+            let sexp =
+                self.define_const(Self::fmt_expr(idx), int, assign).unwrap();
             self.overflow_assert(sexp);
             self.expr_map.push(idx, sexp);
         }
 
         // Assert bindings for all let-bound parameters
-        for (idx, p) in data.comp.params().iter() {
+        for (idx, p) in comp.params().iter() {
             let ir::ParamOwner::Let { bind: Some(bind) } = &p.owner else {
                 continue;
             };
             let param_s = self.param_map[idx];
             let bind_s = self.expr_map[*bind];
             let assign = self.sol.eq(param_s, bind_s);
-            self.sol.assert(assign).unwrap();
+            self.assert(assign);
         }
 
         // Declare all time expressions
-        for (idx, ir::Time { event, offset }) in data
-            .comp
-            .times()
-            .iter()
-            .filter(|(idx, _)| idx.valid(&data.comp))
+        for (idx, ir::Time { event, offset }) in
+            comp.times().iter().filter(|(idx, _)| idx.valid(comp))
         {
             if self.ev_map.contains(*event) && self.expr_map.contains(*offset) {
                 let assign =
                     self.plus(self.ev_map[*event], self.expr_map[*offset]);
                 let sexp = self
-                    .sol
                     .define_const(Self::fmt_time(idx), int, assign)
                     .unwrap();
                 self.overflow_assert(sexp);
@@ -678,28 +1090,39 @@ impl Visitor for Discharge {
         }
 
         // Declare all propositions
-        for (idx, prop) in data
-            .comp
+        for (idx, prop) in comp
             .props()
             .iter()
-            .filter(|(idx, _)| idx.valid(&data.comp))
+            .filter(|(idx, _)| idx.valid(comp))
             // Filter out propositions that are already defined in expressions
             .filter(|(idx, _)| !interned_props.contains(idx))
         {
             // Define assertion equating the proposition to its assignment
             let assign = self.prop_to_sexp(prop);
             if let Ok(sexp) =
-                self.sol.define_const(Discharge::fmt_prop(idx), bs, assign)
+                self.define_const(Discharge::fmt_prop(idx), bs, assign)
             {
                 self.prop_map.insert(idx, SExprWrapper::SExpr(sexp));
             }
         }
-        // Pass does not need to traverse the control program.
-        Action::Continue
     }
 
-    fn instance(&mut self, idx: ir::InstIdx, data: &mut VisitorData) -> Action {
-        let comp = &data.comp;
+    /// Define the `some` parameters of the instances in the commands
+    fn instances(&mut self, cmds: &[ir::Command], comp: &ir::Component) {
+        for cmd in cmds {
+            match cmd {
+                ir::Command::Instance(idx) => self.instance(*idx, comp),
+                ir::Command::ForLoop(l) => self.instances(&l.body, comp),
+                ir::Command::If(i) => {
+                    self.instances(&i.then, comp);
+                    self.instances(&i.alt, comp);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn instance(&mut self, idx: ir::InstIdx, comp: &ir::Component) {
         let inst = &comp[idx];
         let sexp_args =
             inst.args.iter().map(|e| self.expr_map[*e]).collect_vec();
@@ -713,65 +1136,158 @@ impl Visitor for Discharge {
                 let param_s = self.param_map[*param];
                 let app = self.app(*f, sexp_args.clone());
                 let assign = self.sol.eq(param_s, app);
-                self.sol.assert(assign).unwrap();
+                self.assert(assign);
             }
         }
-        Action::Continue
     }
 
-    fn end(&mut self, data: &mut VisitorData) {
-        if self.to_prove.is_empty() {
-            return;
-        }
-
-        if !data.opts.discharge_separate {
+    /// Attempt to prove the facts and add diagnostics for the failing ones
+    fn prove(&mut self, comp: &ir::Component, separate: bool) {
+        if !separate {
             // Attempt to prove all facts
             let total_prop = self.sol.and_many(
                 self.to_prove.iter().map(|f| self.prop_map[f.prop].get()),
             );
-            let total_prop = self.sol.not(total_prop);
-            self.sol.assert(total_prop).unwrap();
+            let actlit = self.new_act_lit();
+            let imp = self.sol.imp(actlit, self.sol.not(total_prop));
+            self.sol.assert(imp).unwrap();
+            let res = self.sol.check_assuming([actlit]).unwrap();
+            // Disable the activation literal so that later queries are not
+            // restricted to models that falsify some fact
+            self.sol.assert(self.sol.not(actlit)).unwrap();
 
             // If there is at least one failing prop, roll back to individually checking the props for error reporting
-            if matches!(self.sol.check().unwrap(), smt::Response::Sat) {
+            if matches!(res, smt::Response::Sat) {
                 log::info!(
                     "Failed to prove all facts. Checking each fact individually"
                 );
-                self.failing_props(&data.comp);
+                self.failing_props(comp);
             }
         } else {
             // Check each proposition individually
             let to_prove = std::mem::take(&mut self.to_prove);
             for fact in to_prove {
-                self.check_valid(fact, &data.comp);
+                self.check_valid(fact, comp);
             }
         }
+    }
+}
+
+impl Visitor for Discharge {
+    fn name() -> &'static str {
+        "discharge"
+    }
 
-        // Report all the errors
-        let is_tty = atty::is(atty::Stream::Stderr);
-        let writer = StandardStream::stderr(if is_tty {
-            ColorChoice::Always
+    fn do_pass(opts: &cmdline::Opts, ctx: &mut ir::Context) -> Result<(), u64> {
+        // Components verified by a previous run are checked again when
+        // emitting obligations so that every assertion is written out.
+        let cache = opts
+            .cache_dir
+            .clone()
+            .filter(|_| opts.emit_obligations.is_none())
+            .map(|dir| DischargeCache::new(dir, opts, ctx));
+
+        if let Some(dir) = &opts.emit_obligations {
+            if let Err(e) = fs::create_dir_all(dir) {
+                Diagnostics::emit(Diagnostic::error().with_message(format!(
+                    "failed to create obligations directory {}: {e}",
+                    dir.display()
+                )));
+                return Err(1);
+            }
+        }
+
+        // A single solver when replaying so that the log is not clobbered
+        let workers = if opts.solver_replay_file.is_some() {
+            1
         } else {
-            ColorChoice::Never
+            opts.discharge_jobs
+                .unwrap_or_else(|| {
+                    thread::available_parallelism().map_or(1, |n| n.get())
+                })
+                .max(1)
+        };
+
+        // Facts are hoisted up front so that the components are only read
+        // while they are discharged.
+        let mut jobs = Vec::new();
+        for idx in ctx.comps.idx_iter() {
+            let comp = ctx.comps.get_mut(idx);
+            let facts = HoistFacts::hoist(comp);
+            if facts.is_empty() {
+                continue;
+            }
+            if cache.as_ref().is_some_and(|c| c.verified(idx)) {
+                if let Some(name) = comp.source_name() {
+                    log::info!("{name}: verified by a previous run");
+                }
+                continue;
+            }
+            for fact in &facts {
+                log::debug!("Checking {}", comp.display(fact.prop));
+            }
+            if opts.discharge_separate {
+                let size = facts.len().div_ceil(workers);
+                for chunk in facts.chunks(size) {
+                    jobs.push(Job {
+                        comp: idx,
+                        facts: chunk.to_vec(),
+                    });
+                }
+            } else {
+                jobs.push(Job { comp: idx, facts });
+            }
+        }
+
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(jobs.len()));
+        let shared: &ir::Context = ctx;
+        thread::scope(|s| {
+            for _ in 0..workers.min(jobs.len()) {
+                s.spawn(|| {
+                    let mut sol = Discharge::new(opts, shared);
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(job) = jobs.get(i) else {
+                            break;
+                        };
+                        let comp = shared.get(job.comp);
+                        let diags =
+                            sol.check(job, comp, opts.discharge_separate);
+                        results.lock().unwrap().push((i, diags));
+                    }
+                });
+            }
         });
-        let table = GlobalPositionTable::get();
-        for diag in &self.diagnostics {
-            term::emit(
-                &mut writer.lock(),
-                &term::Config::default(),
-                table.files(),
-                diag,
-            )
-            .unwrap();
-            self.error_count += 1;
+
+        // Report all the errors in the order of the jobs
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(i, _)| *i);
+        let mut error_count = 0;
+        let mut failed = HashSet::new();
+        for (i, diags) in results {
+            if !diags.is_empty() {
+                failed.insert(jobs[i].comp);
+            }
+            for (diag, counterexample) in diags {
+                Diagnostics::emit_with_counterexample(diag, counterexample);
+                error_count += 1;
+            }
+        }
+
+        if let Some(cache) = &cache {
+            for comp in jobs.iter().map(|j| j.comp).dedup() {
+                if !failed.contains(&comp) {
+                    cache.insert(comp);
+                }
+            }
         }
-    }
 
-    fn after_traversal(&mut self) -> Option<u64> {
-        if self.error_count > 0 {
-            Some(self.error_count)
+        ir::Validate::context(ctx);
+        if error_count > 0 {
+            Err(error_count)
         } else {
-            None
+            Ok(())
         }
     }
 }
@@ -0,0 +1,95 @@
+use crate::cmdline;
+use fil_ir::{self as ir, Ctx};
+use fil_utils::GlobalPositionTable;
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+/// On-disk cache of the components that have been verified by
+/// [super::Discharge].
+///
+/// The proof obligations of a component only depend on its own definition and
+/// the signatures of the components it instantiates. A component is therefore
+/// identified by a hash of the contents of the files that define these and the
+/// user-defined functions inlined into them along with the solver
+/// configuration and the compiler itself.
+/// Each verified component is stored as an empty file named by its hash so
+/// that concurrent runs can share the same cache.
+pub(super) struct DischargeCache {
+    dir: PathBuf,
+    /// Hash of each component defined in a source file
+    keys: HashMap<ir::CompIdx, String>,
+}
+
+impl DischargeCache {
+    pub fn new(dir: PathBuf, opts: &cmdline::Opts, ctx: &ir::Context) -> Self {
+        let table = GlobalPositionTable::get();
+        let pos = |comp: &ir::Component| {
+            comp.pos.into_option().map(|p| table.get_pos(p.0))
+        };
+
+        // Changes to the compiler can change the proof obligations
+        let exe = env::current_exe()
+            .and_then(fs::metadata)
+            .map(|m| format!("{}:{:?}", m.len(), m.modified().ok()))
+            .unwrap_or_default();
+        let config = format!("{exe}:{:?}:{:?}", opts.solver, opts.solver_bv);
+
+        let mut keys = HashMap::new();
+        for (idx, comp) in ctx.comps.iter() {
+            // Files defining the component and the signatures it uses
+            let comps = std::iter::once(comp)
+                .chain(comp.instances().iter().map(|(_, i)| ctx.get(i.comp)))
+                .collect_vec();
+            let Some(defs) =
+                comps.iter().map(|c| pos(c)).collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+
+            let mut hasher = Sha256::new();
+            hasher.update(&config);
+            // Name of the component
+            let (_, src) = table.get_file_data(defs[0].file);
+            hasher.update(&src[defs[0].start..defs[0].end]);
+            for file in defs.iter().map(|d| d.file.get()).sorted().dedup() {
+                hasher.update(table.files().get(file).unwrap().source());
+            }
+            // Functions can be defined in files that are not hashed above
+            for def in comps.iter().flat_map(|c| &c.fn_defs).unique() {
+                hasher.update(def.source());
+            }
+            let key = hasher
+                .finalize()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .join("");
+            keys.insert(idx, key);
+        }
+
+        if let Err(e) = fs::create_dir_all(&dir) {
+            log::warn!(
+                "Failed to create cache directory {}: {e}",
+                dir.display()
+            );
+        }
+        Self { dir, keys }
+    }
+
+    /// Has the component already been verified?
+    pub fn verified(&self, idx: ir::CompIdx) -> bool {
+        self.keys
+            .get(&idx)
+            .is_some_and(|key| self.dir.join(key).exists())
+    }
+
+    /// Record that the component has been verified
+    pub fn insert(&self, idx: ir::CompIdx) {
+        let Some(key) = self.keys.get(&idx) else {
+            return;
+        };
+        if let Err(e) = fs::write(self.dir.join(key), "") {
+            log::warn!("Failed to write to cache {}: {e}", self.dir.display());
+        }
+    }
+}
@@ -1,97 +1,226 @@
-use crate::ir_passes::lower::max_states;
+use crate::ir_passes::{NameGenerator, port_ends};
 use fil_ir::{self as ir, Ctx, DisplayCtx};
 use itertools::Itertools;
+use serde::Serialize;
+use std::{collections::BTreeMap, io};
+
+#[derive(Serialize, PartialEq)]
+#[serde(untagged)]
+/// A number in the interface. Numbers are concrete for monomorphized
+/// components and symbolic for parametric ones.
+pub(crate) enum Value {
+    Concrete(u64),
+    Symbolic(String),
+}
+
+impl Value {
+    /// The value of a concrete number
+    pub(crate) fn concrete(&self) -> Option<u64> {
+        match self {
+            Value::Concrete(n) => Some(*n),
+            Value::Symbolic(_) => None,
+        }
+    }
+
+    fn expr(idx: ir::ExprIdx, comp: &ir::Component) -> Self {
+        idx.as_concrete(comp)
+            .map_or_else(|| Value::Symbolic(comp.display(idx)), Value::Concrete)
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Concrete(n) => write!(f, "{n}"),
+            Value::Symbolic(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+/// An event and the interface port that triggers it.
+/// For an interface port like this:
+///      @interface['G, 'G+5] go_G
+/// Generates:
+/// {
+///   "name": "go_G",
+///   "event": "G",
+///   "delay": 5,
+///   "states": 2,
+///   "phantom": false
+/// }
+pub(crate) struct Event {
+    /// Name of the interface port. `null` for phantom events.
+    pub(crate) name: Option<String>,
+    pub(crate) event: String,
+    pub(crate) delay: Value,
+    /// Number of states in the FSM for the event, i.e., the latest end of
+    /// the ports that use it
+    pub(crate) states: Value,
+    pub(crate) phantom: bool,
+}
+
+#[derive(Serialize)]
+/// A dimension of a bundle
+pub(crate) struct Dim {
+    /// Name of the index parameter used in the liveness of the bundle
+    pub(crate) index: String,
+    pub(crate) len: Value,
+}
+
+#[derive(Serialize)]
+/// An input or output port.
+/// For a port like this:
+///     left: ['G+n, 'G+m] 32
+/// Generates:
+/// {
+///   "event": "G",
+///   "name": "left",
+///   "width": 32,
+///   "start": n,
+///   "end": m
+/// }
+pub(crate) struct Port {
+    pub(crate) event: String,
+    pub(crate) name: String,
+    pub(crate) width: Value,
+    pub(crate) start: Value,
+    pub(crate) end: Value,
+    /// Event of the end of the interval if it differs from `event`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) end_event: Option<String>,
+    /// Dimensions of the port if it is a bundle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) bundle: Option<Vec<Dim>>,
+}
+
+#[derive(Serialize)]
+/// Externally visible interface of a component
+pub struct Interface {
+    pub(crate) name: String,
+    /// Values of the parameters the component was monomorphized with
+    pub(crate) params: BTreeMap<String, u64>,
+    /// Values of the existentially quantified parameters
+    pub(crate) exists: BTreeMap<String, u64>,
+    pub(crate) attributes: BTreeMap<String, serde_json::Value>,
+    pub(crate) interfaces: Vec<Event>,
+    pub(crate) inputs: Vec<Port>,
+    pub(crate) outputs: Vec<Port>,
+}
 
 pub struct DumpInterface;
 
 impl DumpInterface {
     /// Print out the interface of the main component in JSON format
-    pub fn print(ctx: &ir::Context) {
+    pub fn print(
+        ctx: &ir::Context,
+        out: &mut impl io::Write,
+    ) -> io::Result<()> {
         let entrypoint = ctx
             .entrypoint
             .as_ref()
             .map(|ep| ep.comp)
             .unwrap_or_else(|| panic!("No entrypoint found."));
-        let main = ctx.get(entrypoint);
-        let src_info = main
-            .src_info
-            .as_ref()
-            .unwrap_or_else(|| panic!("No source info found for main."));
-
-        // For an interface port like this:
-        //      @interface['G, 'G+5] go_G
-        // Generate the JSON information:
-        // {
-        //   "name": "go_G",
-        //   "event": "G",
-        //   "delay": 5,
-        //   "states": 2,
-        //   "phantom": false
-        // }
-        let states = max_states(main);
-        let interfaces = main
+        let interface = Self::interface(ctx, entrypoint);
+        writeln!(out, "{}", serde_json::to_string_pretty(&interface).unwrap())
+    }
+
+    /// Compute the interface of a component.
+    /// Names are the ones used in the generated Verilog.
+    pub fn interface(ctx: &ir::Context, idx: ir::CompIdx) -> Interface {
+        let comp = ctx.get(idx);
+        let ng = NameGenerator::new(true);
+        let ends = port_ends(comp);
+        let states = |ev: ir::EventIdx| {
+            let (concrete, symbolic): (Vec<_>, Vec<_>) = ends
+                .iter()
+                .filter(|(e, _)| *e == ev)
+                .map(|(_, end)| *end)
+                .partition_result();
+            let max = concrete.into_iter().max().unwrap_or(0);
+            let symbolic = symbolic
+                .into_iter()
+                .map(|e| comp.display(e))
+                .unique()
+                .collect_vec();
+            match symbolic.as_slice() {
+                [] => Value::Concrete(max),
+                [end] if max == 0 => Value::Symbolic(end.clone()),
+                _ => Value::Symbolic(format!(
+                    "max({})",
+                    (max > 0)
+                        .then(|| max.to_string())
+                        .into_iter()
+                        .chain(symbolic)
+                        .join(", ")
+                )),
+            }
+        };
+
+        let interfaces = comp
             .events()
             .iter()
-            .map(|(idx, ev)| {
-                let id = src_info.interface_ports.find(idx).map_or("null", |v| v.as_ref());
-                let phantom = !ev.has_interface;
-                let ir::TimeSub::Unit(delay) = ev.delay else {
-                    panic!("Event `{}` has a non-simple delay.", main.display(idx));
-                };
-                let delay = delay.as_concrete(main).unwrap();
-
-                format!(
-                    "{{\"name\": \"{}\", \"event\": \"{}\", \"delay\": {}, \"states\": {}, \"phantom\": {} }}",
-                    id,
-                    src_info.events.get(idx),
-                    delay,
-                    states[&idx],
-                    phantom
-                )
+            .map(|(ev, event)| Event {
+                name: ng.interface_name(ev, comp),
+                event: ng.event_name(ev, comp),
+                delay: match &event.delay {
+                    ir::TimeSub::Unit(delay) => Value::expr(*delay, comp),
+                    ts @ ir::TimeSub::Sym { .. } => {
+                        Value::Symbolic(comp.display(ts))
+                    }
+                },
+                states: states(ev),
+                phantom: !event.has_interface,
             })
-            .collect_vec().join(",\n");
-
-        // For each input and output that looks like:
-        // @[G+n, G+m] left: 32
-        // Generate the JSON information:
-        // {
-        //   "event": "G",
-        //   "name": "left",
-        //   "width": 32,
-        //   "start": n,
-        //   "end": m
-        // },
-        let pd_to_info = |(idx, p): (ir::PortIdx, &ir::Port)| {
-            let w = p.width.as_concrete(main).unwrap();
-            assert!(
-                idx.is_not_bundle(main),
-                "Main component cannot have bundle ports in signature."
-            );
-            let ir::Liveness { range, .. } = &p.live;
-            let start = main.get(range.start);
-            let end = main.get(range.end);
-
-            assert!(
-                start.event == end.event,
-                "Range `{}` cannot be represented as a simple offset",
-                main.display(range)
-            );
-
-            format!(
-                "{{ \"event\": \"{event}\", \"name\": \"{name}\", \"width\": {w} , \"start\": {st}, \"end\": {end} }}",
-                event = src_info.events.get(start.event),
-                name = src_info.ports.get(idx),
-                st = start.offset.as_concrete(main).unwrap(),
-                end = end.offset.as_concrete(main).unwrap(),
-            )
+            .collect();
+
+        let port = |(pidx, p): (ir::PortIdx, &ir::Port)| {
+            let ir::Liveness { idxs, lens, range } = &p.live;
+            let start = comp.get(range.start);
+            let end = comp.get(range.end);
+            // Ports are represented as bundles of length one
+            let is_bundle = !lens.iter().all(|l| l.is_const(comp, 1));
+            let bundle = is_bundle.then(|| {
+                idxs.iter()
+                    .zip_eq(lens)
+                    .map(|(i, l)| Dim {
+                        index: comp.display(*i),
+                        len: Value::expr(*l, comp),
+                    })
+                    .collect()
+            });
+            Port {
+                event: ng.event_name(start.event, comp),
+                name: ng.port_name(pidx, ctx, comp),
+                width: Value::expr(p.width, comp),
+                start: Value::expr(start.offset, comp),
+                end: Value::expr(end.offset, comp),
+                end_event: (start.event != end.event)
+                    .then(|| ng.event_name(end.event, comp)),
+                bundle,
+            }
+        };
+
+        let binding = |b: &[(fil_ast::Id, u64)]| {
+            b.iter().map(|(n, v)| (n.to_string(), *v)).collect()
         };
 
-        let inputs = main.inputs().map(pd_to_info).collect_vec().join(",\n");
-        let outputs = main.outputs().map(pd_to_info).collect_vec().join(",\n");
+        let attrs = &comp.attrs;
+        let attributes = attrs
+            .bools()
+            .map(|(a, v)| (a.to_string(), v.into()))
+            .chain(attrs.nums().map(|(a, v)| (a.to_string(), v.into())))
+            .chain(attrs.floats().map(|(a, v)| (a.to_string(), v.into())))
+            .collect();
 
-        // Look ma, a JSON serializer!
-        println!(
-            "{{\n\"interfaces\": [\n{interfaces}\n],\n\"inputs\": [\n{inputs}\n],\n\"outputs\": [\n{outputs}\n]\n}}",
-        );
+        Interface {
+            name: ng.comp_name(idx, ctx),
+            params: binding(&comp.mono_binding.params),
+            exists: binding(&comp.mono_binding.exists),
+            attributes,
+            interfaces,
+            inputs: comp.inputs().map(port).collect(),
+            outputs: comp.outputs().map(port).collect(),
+        }
     }
 }
@@ -3,7 +3,6 @@ use super::fsm::{FsmBind, FsmType};
 use super::utils::{NameGenerator, cell_to_port_def};
 use calyx_ir::{self as calyx, RRC};
 use fil_ir::{self as ir, Ctx, DenseIndexInfo, DisplayCtx};
-use fil_utils::{self as utils, AttrCtx};
 use itertools::Itertools;
 use std::{collections::HashMap, rc::Rc};
 
@@ -225,21 +224,8 @@ impl<'a> BuildCtx<'a> {
     /// Attempts to declare an fsm component (if not already declared) in the [Binding] stored by this [BuildCtx]
     /// and creates an [Fsm] from this [calyx::Component] FSM and stores it in the [BuildCtx]
     pub fn insert_fsm(&mut self, event: ir::EventIdx, states: u64) {
-        let evt = self.comp.get(event);
         // Construct an fsm iff the event is connected to an interface port
-        if evt.has_interface {
-            let ir::TimeSub::Unit(delay) = evt.delay else {
-                self.comp.internal_error(
-                    "Non-unit delays should have been compiled away.",
-                );
-            };
-            let delay = delay.concrete(self.comp);
-            let typ =
-                match self.comp.attrs.get(utils::CompBool::CounterFSM).unwrap()
-                {
-                    false => FsmType::Simple(states),
-                    true => FsmType::CounterChain(states, delay),
-                };
+        if let Some(typ) = FsmType::of_event(self.comp, event, states) {
             self.implement_fsm(&typ);
 
             // Construct the FSM
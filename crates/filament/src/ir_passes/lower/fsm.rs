@@ -5,7 +5,8 @@ use super::{
 };
 use calyx_ir::{self as calyx, RRC};
 use calyx_ir::{Guard, Nothing, build_assignments, guard, structure};
-use fil_ir as ir;
+use fil_ir::{self as ir, Ctx};
+use fil_utils::{self as utils, AttrCtx};
 use itertools::Itertools;
 use std::{collections::HashMap, iter, ops::Not};
 
@@ -20,6 +21,47 @@ pub enum FsmType {
     CounterChain(u64, u64),
 }
 
+impl FsmType {
+    /// The type of the FSM used to implement an event with the given number
+    /// of states. Events without an interface port do not have an FSM.
+    pub fn of_event(
+        comp: &ir::Component,
+        event: ir::EventIdx,
+        states: u64,
+    ) -> Option<Self> {
+        let evt = comp.get(event);
+        if !evt.has_interface {
+            return None;
+        }
+        let ir::TimeSub::Unit(delay) = evt.delay else {
+            comp.internal_error(
+                "Non-unit delays should have been compiled away.",
+            );
+        };
+        if *comp.attrs.get(utils::CompBool::CounterFSM).unwrap() {
+            Some(FsmType::CounterChain(states, delay.concrete(comp)))
+        } else {
+            Some(FsmType::Simple(states))
+        }
+    }
+
+    /// Number of flip-flops used by the FSM
+    pub fn flip_flops(&self) -> u64 {
+        // Width of a counter with `n` states and its done register
+        let counter = |n: u64| {
+            ((64 - n.saturating_sub(1).leading_zeros()) as u64).max(1) + 1
+        };
+        match self {
+            FsmType::Simple(states) => *states,
+            FsmType::Counter(states) => counter(*states),
+            FsmType::CounterChain(states, delay) => {
+                let fsm_num = states / delay + (states % delay != 0) as u64;
+                fsm_num * counter(*delay)
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 /// Represents an fsm component.
 pub(super) struct FsmBind {
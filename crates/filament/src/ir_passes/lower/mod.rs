@@ -1,10 +1,14 @@
 mod build_ctx;
 mod compile;
 mod fsm;
+mod sv;
 mod utils;
 
 use build_ctx::BuildCtx;
-use fsm::{Fsm, FsmType};
+use fsm::Fsm;
 
 pub use compile::Compile;
-pub use utils::max_states;
+pub(crate) use fsm::FsmType;
+pub use sv::EmitSv;
+pub(crate) use utils::NameGenerator;
+pub use utils::{max_states, port_ends};
@@ -0,0 +1,606 @@
+use super::{FsmType, max_states, utils::NameGenerator};
+use fil_ir::{self as ir, Ctx, DisplayCtx, Traversal};
+use linked_hash_map::LinkedHashMap;
+use std::{collections::HashMap, fmt::Write as _, fs, io};
+
+/// Formats the type of a signal with the given width.
+fn logic(width: u64) -> String {
+    if width == 1 {
+        "logic".to_string()
+    } else {
+        format!("logic [{}:0]", width - 1)
+    }
+}
+
+/// Number of bits needed to represent the state of a counter with `states` states.
+fn counter_width(states: u64) -> u64 {
+    ((64 - states.saturating_sub(1).leading_zeros()) as u64).max(1)
+}
+
+/// A port on a generated FSM module.
+struct FsmPort {
+    name: String,
+    width: u64,
+    output: bool,
+}
+
+impl FsmType {
+    /// The type of the module that implements this FSM.
+    /// Counter chains are keyed on the number of counters instead of the number of states.
+    fn sv_module(&self) -> FsmType {
+        match self {
+            FsmType::CounterChain(states, delay) => FsmType::CounterChain(
+                states / delay + (states % delay != 0) as u64,
+                *delay,
+            ),
+            FsmType::Simple(n) => FsmType::Simple(*n),
+            FsmType::Counter(n) => FsmType::Counter(*n),
+        }
+    }
+
+    /// Name of the module implementing the FSM. No component in the design
+    /// has a name that starts with `prefix`.
+    /// Only valid for types returned by [FsmType::sv_module].
+    fn sv_name(&self, prefix: &str) -> String {
+        match self {
+            FsmType::Simple(n) => format!("{prefix}fsm_{n}"),
+            FsmType::Counter(n) => format!("{prefix}counter_{n}"),
+            FsmType::CounterChain(n, d) => {
+                format!("{prefix}counter_chain_{n}_{d}")
+            }
+        }
+    }
+
+    /// The state ports exposed by the FSM module.
+    fn sv_ports(&self) -> Vec<FsmPort> {
+        let port = |name: String, width| FsmPort {
+            name,
+            width,
+            output: true,
+        };
+        let mut ports = match self {
+            FsmType::Simple(n) => vec![port("state".to_string(), *n)],
+            FsmType::Counter(n) => vec![
+                port("state".to_string(), counter_width(*n)),
+                port("_0".to_string(), 1),
+            ],
+            FsmType::CounterChain(n, d) => (0..*n)
+                .flat_map(|i| {
+                    [
+                        port(format!("_{i}state"), counter_width(*d)),
+                        port(format!("_{i}_0"), 1),
+                    ]
+                })
+                .collect(),
+        };
+        ports.extend(["clk", "reset", "go"].into_iter().map(|n| FsmPort {
+            name: n.to_string(),
+            width: 1,
+            output: false,
+        }));
+        ports.push(port("done".to_string(), 1));
+        ports
+    }
+
+    /// Writes out the module implementing this FSM.
+    /// The behavior matches the calyx components generated in [super::fsm].
+    fn sv_define(&self, prefix: &str, out: &mut String) -> std::fmt::Result {
+        let name = self.sv_name(prefix);
+        let ports = self
+            .sv_ports()
+            .into_iter()
+            .map(|p| {
+                format!(
+                    "  {} {} {}",
+                    if p.output { "output" } else { "input" },
+                    logic(p.width),
+                    p.name
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+        writeln!(out, "module {name}(\n{ports}\n);")?;
+        match self {
+            FsmType::Simple(n) => {
+                // Shift register where `state[i]` is high `i` cycles after `go`.
+                writeln!(out, "{} r;", logic(*n))?;
+                let shifted = if *n == 1 {
+                    "go".to_string()
+                } else {
+                    format!("{{r[{}:0], go}}", n - 2)
+                };
+                writeln!(out, "always_ff @(posedge clk) begin")?;
+                writeln!(out, "  if (reset) r <= '0;")?;
+                writeln!(out, "  else r <= {shifted};")?;
+                writeln!(out, "end")?;
+                writeln!(out, "assign state = {shifted};")?;
+                if *n == 1 {
+                    writeln!(out, "assign done = r;")?;
+                } else {
+                    writeln!(out, "assign done = r[{}];", n - 1)?;
+                }
+            }
+            FsmType::Counter(n) => {
+                let w = counter_width(*n);
+                writeln!(out, "{} st;", logic(w))?;
+                writeln!(out, "logic done_r;")?;
+                writeln!(out, "wire rst_check = st == {w}'d{};", n - 1)?;
+                writeln!(out, "always_ff @(posedge clk) begin")?;
+                writeln!(out, "  if (reset) begin")?;
+                writeln!(out, "    st <= '0;")?;
+                writeln!(out, "    done_r <= 1'b0;")?;
+                writeln!(out, "  end else begin")?;
+                writeln!(out, "    if (rst_check) st <= '0;")?;
+                writeln!(
+                    out,
+                    "    else if (go || st != '0) st <= st + {w}'d1;"
+                )?;
+                writeln!(out, "    done_r <= rst_check;")?;
+                writeln!(out, "  end")?;
+                writeln!(out, "end")?;
+                writeln!(out, "assign state = st;")?;
+                writeln!(out, "assign _0 = go && st == '0;")?;
+                writeln!(out, "assign done = done_r;")?;
+            }
+            FsmType::CounterChain(n, d) => {
+                let counter = FsmType::Counter(*d);
+                for i in 0..*n {
+                    writeln!(out, "logic c{i}_done;")?;
+                    let go = if i == 0 {
+                        "go".to_string()
+                    } else {
+                        format!("c{}_done", i - 1)
+                    };
+                    writeln!(
+                        out,
+                        "{} c{i} (\n  .clk(clk),\n  .reset(reset),\n  .go({go}),\n  .state(_{i}state),\n  ._0(_{i}_0),\n  .done(c{i}_done)\n);",
+                        counter.sv_name(prefix),
+                    )?;
+                }
+                writeln!(out, "assign done = c{}_done;", n - 1)?;
+            }
+        }
+        writeln!(out, "endmodule")
+    }
+
+    /// A guard expression that is active for the states in `start..end`
+    /// of the FSM instantiated as `inst`.
+    fn sv_range_guard(&self, inst: &str, start: u64, end: u64) -> String {
+        self.sv_range_guard_prefix(inst, "", start, end)
+    }
+
+    fn sv_range_guard_prefix(
+        &self,
+        inst: &str,
+        prefix: &str,
+        start: u64,
+        end: u64,
+    ) -> String {
+        match self {
+            FsmType::Simple(1) => format!("{inst}_state"),
+            FsmType::Simple(_) => {
+                if end - start == 1 {
+                    format!("{inst}_state[{start}]")
+                } else {
+                    format!("(|{inst}_state[{}:{start}])", end - 1)
+                }
+            }
+            FsmType::Counter(states) => {
+                let w = counter_width(*states);
+                let state = format!("{inst}_{prefix}state");
+                // The zeroth state is only active when `go` is high
+                let (start, zero) = if start == 0 {
+                    (1, Some(format!("{inst}_{prefix}_0")))
+                } else {
+                    (start, None)
+                };
+                let range = (start < end).then(|| {
+                    format!(
+                        "({state} >= {w}'d{start} && {state} <= {w}'d{})",
+                        end - 1
+                    )
+                });
+                match (zero, range) {
+                    (Some(z), Some(r)) => format!("({z} | {r})"),
+                    (Some(z), None) => z,
+                    (None, Some(r)) => r,
+                    (None, None) => unreachable!("empty range guard"),
+                }
+            }
+            FsmType::CounterChain(_, delay) => {
+                let counter = FsmType::Counter(*delay);
+                let fsm_start = start / delay;
+                let fsm_end = (end - 1) / delay;
+                (fsm_start..=fsm_end)
+                    .map(|f| {
+                        let s =
+                            if f == fsm_start { start - f * delay } else { 0 };
+                        let e = if f == fsm_end {
+                            end - f * delay
+                        } else {
+                            *delay
+                        };
+                        counter.sv_range_guard_prefix(
+                            inst,
+                            &format!("_{f}"),
+                            s,
+                            e,
+                        )
+                    })
+                    .reduce(|a, b| format!("{a} | {b}"))
+                    .unwrap()
+            }
+        }
+    }
+}
+
+/// An instantiated FSM reifying an event.
+struct SvFsm {
+    /// Name of the instance
+    inst: String,
+    typ: FsmType,
+}
+
+/// Assignments to a signal, guarded by the FSM states in which they are active.
+struct Assigns {
+    width: u64,
+    /// Guard (`None` if always active) and the source signal
+    srcs: Vec<(Option<String>, String)>,
+}
+
+/// Emits SystemVerilog directly from a monomorphized, bundle-free [ir::Context].
+/// Unlike [super::Compile], this does not go through calyx and keeps the
+/// names of ports and instances from the Filament program.
+pub struct EmitSv<'a> {
+    ctx: &'a ir::Context,
+    ng: &'a NameGenerator,
+    /// FSM modules that need to be defined
+    fsms: &'a mut LinkedHashMap<FsmType, ()>,
+    /// Prefix of the names of the FSM modules
+    fsm_prefix: &'a str,
+}
+
+impl<'a> EmitSv<'a> {
+    /// Name of the wire connected to a port of an instance.
+    fn inst_port(inst: &str, port: &str) -> String {
+        format!("{inst}_{port}")
+    }
+
+    /// Name of the signal representing an [ir::Port] in the component
+    fn port(&self, comp: &ir::Component, idx: ir::PortIdx) -> String {
+        let name = self.ng.port_name(idx, self.ctx, comp);
+        match &comp.get(idx).owner {
+            ir::PortOwner::Sig { .. } => name,
+            ir::PortOwner::Inv { inv, .. } => {
+                let inst = comp.get(*inv).inst;
+                Self::inst_port(&self.ng.instance_name(inst, comp), &name)
+            }
+            ir::PortOwner::Local => {
+                unreachable!("Local ports should have been eliminated.")
+            }
+        }
+    }
+
+    /// Guard for the range, `None` if the event has no interface port.
+    fn range(
+        &self,
+        comp: &ir::Component,
+        fsms: &HashMap<ir::EventIdx, SvFsm>,
+        range: &ir::Range,
+    ) -> Option<String> {
+        let start = comp.get(range.start);
+        let end = comp.get(range.end);
+        assert!(
+            start.event == end.event,
+            "Range `{}` cannot be represented as a simple offset",
+            comp.display(range)
+        );
+        let fsm = fsms.get(&start.event)?;
+        Some(fsm.typ.sv_range_guard(
+            &fsm.inst,
+            start.offset.concrete(comp),
+            end.offset.concrete(comp),
+        ))
+    }
+
+    /// The ports in the signature of a component as `(direction, width, name)`.
+    fn signature(
+        &self,
+        comp: &ir::Component,
+    ) -> Vec<(&'static str, u64, String)> {
+        let mut ports: Vec<_> = comp
+            .ports()
+            .idx_iter()
+            .filter(|idx| comp.get(*idx).is_sig())
+            .map(|idx| {
+                let p = comp.get(idx);
+                let ir::PortOwner::Sig { dir } = &p.owner else {
+                    unreachable!()
+                };
+                let dir = match dir.reverse() {
+                    ir::Direction::In => "input",
+                    ir::Direction::Out => "output",
+                };
+                (
+                    dir,
+                    p.width.concrete(comp),
+                    self.ng.port_name(idx, self.ctx, comp),
+                )
+            })
+            .chain(
+                comp.unannotated_ports
+                    .iter()
+                    .map(|(name, w)| ("input", *w, name.to_string())),
+            )
+            .chain(
+                comp.events()
+                    .idx_iter()
+                    .filter_map(|idx| self.ng.interface_name(idx, comp))
+                    .map(|name| ("input", 1, name)),
+            )
+            .collect();
+        for name in ["clk", "reset"] {
+            if !ports.iter().any(|(_, _, n)| n == name) {
+                ports.push(("input", 1, name.to_string()));
+            }
+        }
+        ports
+    }
+
+    /// Writes out a single component.
+    fn component(
+        &mut self,
+        idx: ir::CompIdx,
+        out: &mut String,
+    ) -> std::fmt::Result {
+        let comp = self.ctx.get(idx);
+        let name = self.ng.comp_name(idx, self.ctx);
+        log::debug!("Emitting component {name}");
+
+        let sig = self
+            .signature(comp)
+            .into_iter()
+            .map(|(dir, w, n)| format!("  {dir} {} {n}", logic(w)))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        writeln!(out, "module {name}(\n{sig}\n);")?;
+
+        // Declare and instantiate the FSMs
+        let mut fsms = HashMap::new();
+        for (event, states) in max_states(comp) {
+            let (Some(trigger), Some(typ)) = (
+                self.ng.interface_name(event, comp),
+                FsmType::of_event(comp, event, states),
+            ) else {
+                continue;
+            };
+            let module = typ.sv_module();
+            let inst = format!("{trigger}_fsm");
+            let mut conns = vec![];
+            for p in module.sv_ports() {
+                let sig = match p.name.as_str() {
+                    "go" => trigger.clone(),
+                    "clk" | "reset" => p.name.clone(),
+                    _ => {
+                        let w = Self::inst_port(&inst, &p.name);
+                        writeln!(out, "{} {w};", logic(p.width))?;
+                        w
+                    }
+                };
+                conns.push(format!("  .{}({sig})", p.name));
+            }
+            writeln!(
+                out,
+                "{} {inst} (\n{}\n);",
+                module.sv_name(self.fsm_prefix),
+                conns.join(",\n")
+            )?;
+            if let FsmType::CounterChain(_, d) = &module {
+                self.fsms.insert(FsmType::Counter(*d), ());
+            }
+            self.fsms.insert(module, ());
+            fsms.insert(event, SvFsm { inst, typ });
+        }
+
+        // All the assignments in the component
+        let mut assigns: LinkedHashMap<String, Assigns> = LinkedHashMap::new();
+
+        // Wires for the ports used on each instance
+        let mut inst_ports: LinkedHashMap<
+            ir::InstIdx,
+            LinkedHashMap<String, u64>,
+        > = comp
+            .instances()
+            .idx_iter()
+            .map(|i| (i, LinkedHashMap::new()))
+            .collect();
+        for (pidx, p) in comp.ports().iter() {
+            if let ir::PortOwner::Inv { inv, .. } = &p.owner {
+                let inst = comp.get(*inv).inst;
+                inst_ports.get_mut(&inst).unwrap().insert(
+                    self.ng.port_name(pidx, self.ctx, comp),
+                    p.width.concrete(comp),
+                );
+            }
+        }
+
+        // Trigger the interface ports of the invoked instances
+        for inv in comp.invocations().iter().map(|(_, inv)| inv) {
+            let inst = self.ng.instance_name(inv.inst, comp);
+            for eb in &inv.events {
+                let Some(dst) = eb.base.apply(
+                    |ev: ir::EventIdx, c: &ir::Component| {
+                        self.ng.interface_name(ev, c)
+                    },
+                    self.ctx,
+                ) else {
+                    continue;
+                };
+                let time = comp.get(eb.arg);
+                let offset = time.offset.concrete(comp);
+                let Some(fsm) = fsms.get(&time.event) else {
+                    unreachable!("Invocation scheduled by event without FSM")
+                };
+                inst_ports
+                    .get_mut(&inv.inst)
+                    .unwrap()
+                    .insert(dst.clone(), 1);
+                assigns
+                    .entry(Self::inst_port(&inst, &dst))
+                    .or_insert(Assigns {
+                        width: 1,
+                        srcs: vec![],
+                    })
+                    .srcs
+                    .push((
+                        Some(fsm.typ.sv_range_guard(
+                            &fsm.inst,
+                            offset,
+                            offset + 1,
+                        )),
+                        "1'd1".to_string(),
+                    ));
+            }
+        }
+
+        for cmd in &comp.cmds {
+            match cmd {
+                ir::Command::Connect(con) => {
+                    let ir::Connect { dst, src, .. } = con;
+                    assert!(
+                        src.is_port(comp) && dst.is_port(comp),
+                        "Bundles should have been compiled away."
+                    );
+                    let guard =
+                        self.range(comp, &fsms, &comp.get(dst.port).live.range);
+                    assigns
+                        .entry(self.port(comp, dst.port))
+                        .or_insert(Assigns {
+                            width: comp.get(dst.port).width.concrete(comp),
+                            srcs: vec![],
+                        })
+                        .srcs
+                        .push((guard, self.port(comp, src.port)));
+                }
+                ir::Command::ForLoop(_)
+                | ir::Command::If(_)
+                | ir::Command::BundleDef(_)
+                | ir::Command::Exists(_)
+                | ir::Command::Let(_) => unreachable!(
+                    "Control flow and bundles should have been compiled away."
+                ),
+                ir::Command::Instance(_)
+                | ir::Command::Invoke(_)
+                | ir::Command::Fact(_) => (),
+            }
+        }
+
+        // Declare the wires and define the instances
+        for (iidx, ports) in inst_ports.iter() {
+            let inst = comp.get(*iidx);
+            let inst_name = self.ng.instance_name(*iidx, comp);
+            let callee = self.ctx.get(inst.comp);
+            let mut conns = vec![];
+            for (port, width) in ports.iter() {
+                let wire = Self::inst_port(&inst_name, port);
+                writeln!(out, "{} {wire};", logic(*width))?;
+                conns.push(format!("  .{port}({wire})"));
+            }
+            // Connect the clock and reset signals if the component expects them
+            for name in ["clk", "reset"] {
+                if !callee.is_ext()
+                    || callee.unannotated_ports.iter().any(|(n, _)| n == name)
+                {
+                    conns.push(format!("  .{name}({name})"));
+                }
+            }
+
+            let params = callee
+                .params()
+                .iter()
+                .filter(|(_, p)| p.owner == ir::ParamOwner::Sig)
+                .zip(&inst.args)
+                .map(|((p, _), arg)| {
+                    format!(
+                        "  .{}({})",
+                        self.ng.param_name(p, callee),
+                        arg.concrete(comp)
+                    )
+                })
+                .collect::<Vec<_>>();
+            let params = if params.is_empty() {
+                String::new()
+            } else {
+                format!(" #(\n{}\n)", params.join(",\n"))
+            };
+            writeln!(
+                out,
+                "{}{params} {inst_name} (\n{}\n);",
+                self.ng.comp_name(inst.comp, self.ctx),
+                conns.join(",\n")
+            )?;
+        }
+
+        for (dst, Assigns { width, srcs }) in assigns {
+            match srcs.as_slice() {
+                [(None, src)] => writeln!(out, "assign {dst} = {src};")?,
+                _ => {
+                    write!(out, "assign {dst} =")?;
+                    for (guard, src) in srcs {
+                        let guard = guard.unwrap_or_else(|| "1'b1".to_string());
+                        write!(out, "\n  {guard} ? {src} :")?;
+                    }
+                    writeln!(out, "\n  {width}'d0;")?;
+                }
+            }
+        }
+
+        writeln!(out, "endmodule")
+    }
+
+    /// Emit SystemVerilog for the program.
+    /// The source files of external components are included verbatim.
+    pub fn emit(
+        ctx: ir::Context,
+        debug: bool,
+        out: &mut impl io::Write,
+    ) -> io::Result<()> {
+        let mut files: Vec<_> = ctx.externals.keys().cloned().collect();
+        files.sort();
+        for file in files {
+            let content = fs::read_to_string(&file).map_err(|e| {
+                io::Error::new(e.kind(), format!("Failed to read {file}: {e}"))
+            })?;
+            writeln!(out, "// external: {file}\n{content}")?;
+        }
+
+        let ng = NameGenerator::new(debug);
+        // FSM modules must not have the same name as any component
+        let names = ctx
+            .comps
+            .iter()
+            .map(|(idx, _)| ng.comp_name(idx, &ctx))
+            .collect::<Vec<_>>();
+        let mut fsm_prefix = "fil_".to_string();
+        while names.iter().any(|n| n.starts_with(&fsm_prefix)) {
+            fsm_prefix.push('_');
+        }
+
+        let mut fsms = LinkedHashMap::new();
+        let mut buf = String::new();
+        Traversal::from(ctx).apply_pre_order(|ctx, idx| {
+            EmitSv {
+                ctx,
+                ng: &ng,
+                fsms: &mut fsms,
+                fsm_prefix: &fsm_prefix,
+            }
+            .component(idx, &mut buf)
+            .unwrap();
+        });
+        for (fsm, _) in fsms {
+            fsm.sv_define(&fsm_prefix, &mut buf).unwrap();
+        }
+        out.write_all(buf.as_bytes())
+    }
+}
@@ -1,8 +1,9 @@
 use calyx_ir::{self as calyx, RRC};
 use fil_ir::{
-    self as ir, CompIdx, Component, Context, Ctx, EventIdx, ExprIdx, Info,
-    InfoIdx, InstIdx, Instance, ParamIdx, PortIdx,
+    self as ir, AddCtx, CompIdx, Component, Context, Ctx, EventIdx, ExprIdx,
+    Info, InfoIdx, InstIdx, Instance, ParamIdx, PortIdx,
 };
+use itertools::Itertools;
 use linked_hash_map::LinkedHashMap;
 
 type AttrPair = (calyx::Attribute, u64);
@@ -20,7 +21,7 @@ pub(super) const INTERFACE_PORTS: [(AttrPair, (&str, u64, calyx::Direction));
 ];
 
 /// Helper struct that generates names for [crate::utils::Idx]s given their [Ctx].
-pub(super) struct NameGenerator {
+pub(crate) struct NameGenerator {
     use_info: bool,
 }
 
@@ -53,6 +54,15 @@ impl NameGenerator {
         })
     }
 
+    /// Returns the name of an [ir::Event] in the source program
+    pub fn event_name(&self, idx: EventIdx, comp: &Component) -> String {
+        comp.src_info
+            .as_ref()
+            .map(|src| src.events.get(idx).to_string())
+            .or_else(|| comp.get(idx).info.get_name(comp))
+            .unwrap_or_else(|| format!("ev{}", idx.get()))
+    }
+
     /// Converts an [ir::ExprIdx] into a [calyx::Width].
     /// Expects the [ir::ExprIdx] to either be a singular constant or an abstract variable.
     pub fn expr_width(&self, idx: ExprIdx, comp: &Component) -> calyx::Width {
@@ -102,6 +112,16 @@ impl NameGenerator {
         }
     }
 
+    /// Returns the name of a port in the signature of a component in the
+    /// source program
+    pub fn sig_port_name(&self, idx: PortIdx, comp: &Component) -> String {
+        comp.src_info
+            .as_ref()
+            .map(|src| src.ports.get(idx).to_string())
+            .or_else(|| comp.get(idx).info.get_name(comp))
+            .unwrap_or_else(|| format!("p{}", idx.get()))
+    }
+
     /// Returns the name of an [Instance]
     pub fn instance_name<C: Ctx<Instance> + Ctx<Info>>(
         &self,
@@ -120,32 +140,80 @@ impl NameGenerator {
             .map(|src| src.name.to_string())
             .unwrap_or_else(|| format!("comp{}", idx.get()))
     }
+
+    /// Returns the name of a monomorphized [Component] in the source program
+    /// along with the values of its parameters
+    pub fn mono_comp_name(
+        &self,
+        idx: CompIdx,
+        ctx: &impl Ctx<Component>,
+    ) -> String {
+        let binding = &ctx.get(idx).mono_binding;
+        let name = binding
+            .name
+            .map_or_else(|| self.comp_name(idx, ctx), |n| n.to_string());
+        let params = &binding.params;
+        if params.is_empty() {
+            name
+        } else {
+            let binding =
+                params.iter().map(|(n, v)| format!("{n}={v}")).join(", ");
+            format!("{name}[{binding}]")
+        }
+    }
+}
+
+/// The end of the liveness of every port. Bundles have an end for each of
+/// their elements. Ends that are not concrete, such as the ones of parametric
+/// components, are returned as the expression in the liveness of the port.
+pub fn port_ends(comp: &Component) -> Vec<(EventIdx, Result<u64, ExprIdx>)> {
+    let mut ends = Vec::new();
+    // Copy of the component used to substitute the indices of bundles
+    let mut elems: Option<Component> = None;
+
+    for (idx, port) in comp.ports().iter() {
+        // need only the end here as ends follow starts and all ranges should be represented by a simple offset.
+        let ir::Liveness { idxs, lens, range } = &port.live;
+        let time = comp.get(range.end);
+        if idx.is_not_bundle(comp) {
+            let end = time.offset.as_concrete(comp).ok_or(time.offset);
+            ends.push((time.event, end));
+            continue;
+        }
+
+        let Some(lens) = lens
+            .iter()
+            .map(|l| l.as_concrete(comp).map(|l| l as usize))
+            .collect::<Option<Vec<_>>>()
+        else {
+            ends.push((time.event, Err(time.offset)));
+            continue;
+        };
+        let elems = elems.get_or_insert_with(|| comp.clone());
+        for i in 0..lens.iter().product::<usize>() {
+            let vals = fil_utils::nd_idx(i, &lens)
+                .into_iter()
+                .map(|v| elems.add(ir::Expr::Concrete(v as u64)))
+                .collect_vec();
+            let binding = ir::Bind::new(idxs.iter().copied().zip_eq(vals));
+            let end = ir::Subst::new(time.offset, &binding).apply(elems);
+            ends.push((time.event, end.as_concrete(elems).ok_or(time.offset)));
+        }
+    }
+
+    ends
 }
 
 /// Calculates the max states used for every fsm for the given component.
+/// Ends that are not concrete are ignored.
 pub fn max_states(comp: &Component) -> LinkedHashMap<EventIdx, u64> {
     let mut max_states = LinkedHashMap::new();
-
-    comp.ports()
-        .iter()
-        .map(|(idx, port)| {
-            let live = &port.live;
-            assert!(
-                idx.is_not_bundle(comp),
-                "Bundles should have been compiled away."
-            );
-
-            // need only the end here as ends follow starts and all ranges should be represented by a simple offset.
-            live.range.end
-        })
-        .for_each(|idx| {
-            let time = comp.get(idx);
-            let nv = time.offset.concrete(comp);
-            if nv > *max_states.get(&time.event).unwrap_or(&0) {
-                max_states.insert(time.event, nv);
-            }
-        });
-
+    for (event, end) in port_ends(comp) {
+        let Ok(nv) = end else { continue };
+        if nv > *max_states.get(&event).unwrap_or(&0) {
+            max_states.insert(event, nv);
+        }
+    }
     max_states
 }
 
@@ -1,8 +1,10 @@
 mod assignment_check;
 mod assumptions;
+mod auto_resize;
 mod build_domination;
 mod bundle_elim;
 mod discharge;
+mod discharge_cache;
 mod dump_interface;
 mod fsm_attributes;
 mod infer_assumes;
@@ -11,10 +13,14 @@ mod lower;
 mod mono;
 mod phantom_check;
 mod prop_simplify;
+mod report;
+mod termination_check;
+mod testbench;
 mod type_check;
 
 pub use assignment_check::AssignCheck;
 pub use assumptions::Assumptions;
+pub use auto_resize::AutoResize;
 pub use build_domination::BuildDomination;
 pub use bundle_elim::BundleElim;
 pub use discharge::Discharge;
@@ -22,8 +28,12 @@ pub use dump_interface::DumpInterface;
 pub use fsm_attributes::FSMAttributes;
 pub use infer_assumes::InferAssumes;
 pub use interval_check::IntervalCheck;
-pub use lower::Compile;
+pub use lower::{Compile, EmitSv};
+pub(crate) use lower::{FsmType, NameGenerator, max_states, port_ends};
 pub use mono::Monomorphize;
 pub use phantom_check::PhantomCheck;
 pub use prop_simplify::Simplify;
+pub use report::Report;
+pub use termination_check::TerminationCheck;
+pub use testbench::Testbench;
 pub use type_check::TypeCheck;
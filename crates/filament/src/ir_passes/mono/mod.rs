@@ -2,11 +2,15 @@ mod global;
 mod monodeferred;
 mod monomorphize;
 mod monosig;
+mod prefetch;
+mod signature;
 mod utils;
 
 pub(super) use global::{CompKey, InstanceInfo};
 pub(super) use monodeferred::MonoDeferred;
 pub(super) use monosig::MonoSig;
+pub(super) use prefetch::Prefetch;
+pub(super) use signature::ToolSignature;
 pub(super) use utils::{
     Base, BaseComp, IntoBase, IntoUdl, Underlying, UnderlyingComp,
 };
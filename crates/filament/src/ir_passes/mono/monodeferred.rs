@@ -141,6 +141,20 @@ impl MonoDeferred<'_, '_> {
                 .event_delay(&self.underlying, self.pass, old, new);
         }
 
+        // Record the parameters used to generate this component
+        let value = |p: ir::ParamIdx| {
+            self.monosig
+                .binding
+                .get(&p.ul())
+                .map(|v| (self.underlying.display(p.ul()).as_str().into(), *v))
+        };
+        let binding = ir::MonoBinding {
+            name: self.underlying.user_name().map(|n| n.as_str().into()),
+            params: self.underlying.sig_params().filter_map(value).collect(),
+            exists: self.underlying.exist_params().filter_map(value).collect(),
+        };
+        self.monosig.base.set_mono_binding(binding);
+
         // Mark the signature monormophization as complete
         self.sig_mono_complete = true;
     }
@@ -150,8 +164,9 @@ impl MonoDeferred<'_, '_> {
         self.monosig.binding.push(p, v);
     }
 
-    /// Monomorphize a component definition
-    pub fn comp(mut self) -> ir::Component {
+    /// Monomorphize a component definition. Fails if monomorphizing an
+    /// instance exceeds the instantiation depth limit.
+    pub fn comp(mut self) -> Result<ir::Component, u64> {
         assert!(!self.underlying.is_ext(), "cannot monomorphize external");
 
         // Monomorphize the part of the signature that doesn't use existential parameters
@@ -159,7 +174,7 @@ impl MonoDeferred<'_, '_> {
 
         // Monomorphize the component's body
         for cmd in self.underlying.cmds().clone() {
-            let cmd = self.command(&cmd);
+            let cmd = self.command(&cmd)?;
             self.monosig.base.extend_cmds(cmd);
         }
 
@@ -180,7 +195,7 @@ impl MonoDeferred<'_, '_> {
         self.sig_complete_mono();
 
         // Return the component
-        self.monosig.base.take()
+        Ok(self.monosig.base.take())
     }
 
     fn access(&mut self, acc: &ir::Access) -> ir::Access {
@@ -219,7 +234,7 @@ impl MonoDeferred<'_, '_> {
         }
     }
 
-    fn forloop(&mut self, lp: &ir::Loop) {
+    fn forloop(&mut self, lp: &ir::Loop) -> Result<(), u64> {
         let ir::Loop {
             index,
             start,
@@ -244,7 +259,7 @@ impl MonoDeferred<'_, '_> {
             let orig_l = self.monosig.binding.len();
             self.monosig.binding.push(index, i);
             for cmd in body.iter() {
-                let cmd = self.command(cmd);
+                let cmd = self.command(cmd)?;
                 self.monosig.base.extend_cmds(cmd);
             }
             // Remove all the bindings added in this scope including the index
@@ -253,9 +268,10 @@ impl MonoDeferred<'_, '_> {
                 .pop_n(self.monosig.binding.len() - orig_l);
             i += 1;
         }
+        Ok(())
     }
 
-    fn if_stmt(&mut self, if_stmt: &ir::If) {
+    fn if_stmt(&mut self, if_stmt: &ir::If) -> Result<(), u64> {
         let ir::If { cond, then, alt } = if_stmt;
 
         let cond = cond.ul();
@@ -275,16 +291,13 @@ impl MonoDeferred<'_, '_> {
                 .internal_error(format!("Non-bool condition: {cond}")),
         };
 
-        let body = branch
-            .iter()
-            .map(|cmd| self.command(cmd))
-            .fold(&mut vec![], |acc, cvec| {
-                acc.extend(cvec);
-                acc
-            })
-            .to_vec();
+        let mut body = vec![];
+        for cmd in branch.iter() {
+            body.extend(self.command(cmd)?);
+        }
 
         self.monosig.base.extend_cmds(body);
+        Ok(())
     }
 
     fn fact(&mut self, fact: &ir::Fact) -> Option<ir::Fact> {
@@ -318,11 +331,14 @@ impl MonoDeferred<'_, '_> {
     }
 
     /// Compile the given command and return the generated command if any.
-    fn command(&mut self, cmd: &ir::Command) -> Option<ir::Command> {
-        match cmd {
+    fn command(
+        &mut self,
+        cmd: &ir::Command,
+    ) -> Result<Option<ir::Command>, u64> {
+        let cmd = match cmd {
             ir::Command::Instance(idx) => Some(
                 self.monosig
-                    .inst_def(&self.underlying, self.pass, idx.ul())
+                    .inst_def(&self.underlying, self.pass, idx.ul())?
                     .get()
                     .into(),
             ),
@@ -362,11 +378,11 @@ impl MonoDeferred<'_, '_> {
             }
             ir::Command::Connect(con) => Some(self.connect(con).into()),
             ir::Command::ForLoop(lp) => {
-                self.forloop(lp);
+                self.forloop(lp)?;
                 None
             }
             ir::Command::If(if_stmt) => {
-                self.if_stmt(if_stmt);
+                self.if_stmt(if_stmt)?;
                 None
             }
             ir::Command::Exists(ir::Exists { param, expr }) => {
@@ -392,6 +408,7 @@ impl MonoDeferred<'_, '_> {
             // If we want to do this long term, this should be done in a
             // separate pass and monomorphization should fail on facts.
             ir::Command::Fact(fact) => self.fact(fact).map(|f| f.into()),
-        }
+        };
+        Ok(cmd)
     }
 }
@@ -1,11 +1,13 @@
 use super::{
     Base, CompKey, InstanceInfo, IntoBase, IntoUdl, MonoDeferred, MonoSig,
-    Underlying, UnderlyingComp,
+    Prefetch, ToolSignature, Underlying, UnderlyingComp,
 };
+use crate::ir_passes::DumpInterface;
 use fil_gen as fgen;
-use fil_ir::{self as ir, Ctx, IndexStore};
+use fil_ir::{self as ir, Ctx, DisplayCtx, IndexStore};
+use fil_utils as utils;
 use ir::{AddCtx, EntryPoint};
-use itertools::Itertools;
+use itertools::{Either, Itertools};
 use std::collections::HashMap;
 
 /// The Monomorphize pass.
@@ -49,6 +51,15 @@ use std::collections::HashMap;
 /// }
 /// ```
 ///
+/// ## Recursion
+///
+/// Recursive components are monomorphized by following the recursive
+/// instances. With `--check-termination`, [super::super::TerminationCheck]
+/// ensures that this process terminates, but the number of nested
+/// instantiations can still be very large. The pass fails once the depth of
+/// nested instantiations exceeds the recursion limit and reports the chain of
+/// instantiations that caused it.
+///
 /// ## Struct Information
 ///
 /// Information generated while monomorphizing a program. This tracks the global
@@ -69,12 +80,17 @@ pub struct Monomorphize<'a> {
     pub ext_map: HashMap<String, Vec<ir::CompIdx>>,
     /// Generator executor
     gen_exec: &'a mut Option<fgen::GenExec>,
+    /// Components currently being monomorphized, outermost first
+    stack: Vec<CompKey>,
+    /// Maximum depth of nested instantiations
+    recursion_limit: usize,
 }
 
 impl<'a> Monomorphize<'a> {
     fn new(
         old: &'a ir::Context,
         gen_exec: &'a mut Option<fgen::GenExec>,
+        recursion_limit: usize,
     ) -> Self {
         Monomorphize {
             ctx: ir::Context::default(),
@@ -84,6 +100,8 @@ impl<'a> Monomorphize<'a> {
             inst_info: HashMap::new(),
             ext_map: HashMap::new(),
             gen_exec,
+            stack: vec![],
+            recursion_limit,
         }
     }
 }
@@ -103,13 +121,114 @@ impl Monomorphize<'_> {
         self.inst_info.entry(comp_key).or_default()
     }
 
-    /// Generate an component using the `gen` framework
+    /// Name of the component and the values of its parameters as written in
+    /// the source program, e.g., `IsEven[VALUE=10]`.
+    fn key_name(&self, ck: &CompKey) -> String {
+        let comp = self.old.get(ck.comp.idx());
+        let Some(name) = comp.user_name() else {
+            return ck.to_string();
+        };
+        if ck.params.is_empty() {
+            return name;
+        }
+        let params = comp
+            .sig_params()
+            .zip(&ck.params)
+            .map(|(p, v)| format!("{}={v}", comp.display(p)))
+            .join(", ");
+        format!("{name}[{params}]")
+    }
+
+    /// Report that monomorphizing `ck` exceeds the recursion limit along with
+    /// the chain of instantiations that lead to it.
+    fn recursion_limit_error(&self, ck: &CompKey) -> u64 {
+        // Only show the ends of long chains
+        const SHOWN: usize = 4;
+        let chain = self
+            .stack
+            .iter()
+            .chain(std::iter::once(ck))
+            .map(|ck| self.key_name(ck))
+            .collect_vec();
+        let chain = if chain.len() > 2 * SHOWN {
+            let elided = chain.len() - 2 * SHOWN;
+            chain[..SHOWN]
+                .iter()
+                .cloned()
+                .chain(std::iter::once(format!("... {elided} more ...")))
+                .chain(chain[chain.len() - SHOWN..].iter().cloned())
+                .join(" -> ")
+        } else {
+            chain.join(" -> ")
+        };
+
+        let mut diag = utils::Diagnostics::default();
+        let err = utils::Error::misc(format!(
+            "instantiating `{}' exceeds the recursion limit of {}",
+            self.key_name(ck),
+            self.recursion_limit
+        ))
+        .add_note(diag.add_message(format!("instantiation chain: {chain}")))
+        .add_note(
+            diag.add_message("use `--recursion-limit` to increase the limit"),
+        );
+        diag.add_error(err);
+        diag.report_all().unwrap_or(1)
+    }
+
+    /// Report an error in generating an instance of the component at `pos`
+    fn gen_diag(
+        is: &ir::InterfaceSrc,
+        msg: String,
+        label: String,
+        pos: utils::GPosIdx,
+        notes: Vec<String>,
+    ) -> u64 {
+        let mut diag = utils::Diagnostics::default();
+        let mut err =
+            utils::Error::misc(msg)
+                .add_note(diag.add_info(label, pos))
+                .add_note(diag.add_info(
+                    "tool used by this generate definition",
+                    is.gen_pos,
+                ));
+        for note in notes {
+            err = err.add_note(diag.add_message(note));
+        }
+        diag.add_error(err);
+        diag.report_all().unwrap_or(1)
+    }
+
+    /// Report that the tool failed to generate the instance at `pos`
+    fn gen_error(
+        is: &ir::InterfaceSrc,
+        inst: &fgen::Instance,
+        e: fgen::GenError,
+        pos: utils::GPosIdx,
+    ) -> u64 {
+        let tool = e.tool.as_ref().or(is.gen_tool.as_ref()).unwrap();
+        Self::gen_diag(
+            is,
+            format!("tool `{tool}' failed to generate `{inst}'"),
+            e.msg,
+            pos,
+            e.stderr
+                .map(|stderr| format!("tool stderr:\n{stderr}"))
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    /// Generate an component using the `gen` framework.
+    /// Errors from the tool are reported at `pos`, the location of the
+    /// instance that requires the component.
     pub fn generated(
         &mut self,
         comp: Underlying<ir::Component>,
         params: Vec<u64>,
         key: CompKey,
-    ) -> Base<ir::Component> {
+        pos: utils::GPosIdx,
+    ) -> Result<Base<ir::Component>, u64> {
         let underlying = self.old.get(comp.idx());
         let Some(is) = &underlying.src_info else {
             unreachable!("external component has no src_info")
@@ -127,12 +246,89 @@ impl Monomorphize<'_> {
         let fgen::ToolOutput {
             name,
             file,
+            files,
             exist_params,
+            signature,
         } = self
             .gen_exec
             .as_mut()
             .unwrap_or_else(|| unreachable!("no generate executor defined"))
-            .gen_instance(tool, &inst);
+            .gen_instance(tool, &inst)
+            .map_err(|e| Self::gen_error(is, &inst, e, pos))?;
+
+        // The signature reported by the tool
+        let tool_sig = signature
+            .map(|sig| {
+                ToolSignature::parse(format!("<{name} signature>"), sig, |e| {
+                    Self::gen_diag(
+                        is,
+                        format!("tool `{tool}' reported a malformed signature for `{inst}'"),
+                        "module generated for this instance".to_string(),
+                        pos,
+                        vec![e],
+                    )
+                })
+            })
+            .transpose()?;
+
+        // Values of the existential parameters are reported as outputs or
+        // inferred from the signature reported by the tool
+        let mut env: HashMap<_, _> = underlying
+            .sig_params()
+            .zip(params.iter().copied())
+            .collect();
+        for (name, val) in exist_params.into_iter().sorted() {
+            let param = is
+                .param_from_src_name(name.clone())
+                .filter(|p| underlying.exist_params().contains(p));
+            let Some(param) = param else {
+                return Err(Self::gen_diag(
+                    is,
+                    format!(
+                        "tool `{tool}' reported a value for `{name}' which is not an existential parameter of `{inst}'"
+                    ),
+                    "module generated for this instance".to_string(),
+                    pos,
+                    vec![format!(
+                        "outputs of the tool must be mapped to existential parameters of `{}'",
+                        is.name
+                    )],
+                ));
+            };
+            let Ok(v) = val.parse() else {
+                return Err(Self::gen_diag(
+                    is,
+                    format!(
+                        "tool `{tool}' reported the value `{val}' for `{name}' of `{inst}' which is not a number"
+                    ),
+                    "module generated for this instance".to_string(),
+                    pos,
+                    vec![],
+                ));
+            };
+            env.insert(param, v);
+        }
+        if let Some(sig) = &tool_sig {
+            sig.infer(underlying, &mut env);
+        }
+        let (exists, missing): (Vec<_>, Vec<_>) = underlying
+            .exist_params()
+            .partition_map(|p| match env.get(&p) {
+                Some(v) => Either::Left((p.ul(), *v)),
+                None => Either::Right(is.params.get(p).to_string()),
+            });
+        if !missing.is_empty() {
+            return Err(Self::gen_diag(
+                is,
+                format!("tool `{tool}' did not report the existential parameters of `{inst}'"),
+                format!(
+                    "values of {} are unknown",
+                    missing.iter().map(|p| format!("`{p}'")).join(", ")
+                ),
+                pos,
+                vec!["existential parameters must be outputs of the tool or be inferable from the signature it reports".to_string()],
+            ));
+        }
 
         // Partially convert the signature
         let monosig =
@@ -144,19 +340,10 @@ impl Monomorphize<'_> {
         );
         mono_comp.sig_partial_mono();
 
-        // Add generated existential parameters
-        let exists = exist_params
-            .into_iter()
-            .map(|(name, val)| {
-                let v: u64 = val.parse().unwrap();
-                let Some(param) = is.param_from_src_name(name.clone()) else {
-                    unreachable!("component does not have parameter `{name}'")
-                };
-                // Add to the binding
-                mono_comp.push_binding(param.ul(), v);
-                (param.ul(), v)
-            })
-            .collect_vec();
+        // Add generated existential parameters to the binding
+        for (param, v) in &exists {
+            mono_comp.push_binding(*param, *v);
+        }
 
         // Monomorphize the siganture of the component using the parameters
         mono_comp.sig_complete_mono();
@@ -175,13 +362,38 @@ impl Monomorphize<'_> {
         let idx = self.ctx.add(comp).base();
         self.processed.insert(key, idx);
 
+        // The reported signature must agree with the declared one
+        if let Some(sig) = tool_sig {
+            let declared = DumpInterface::interface(&self.ctx, idx.get());
+            let mismatches = sig.mismatches(&declared);
+            if !mismatches.is_empty() {
+                return Err(Self::gen_diag(
+                    is,
+                    format!(
+                        "signature reported by tool `{tool}' for `{inst}' does not match its declaration"
+                    ),
+                    "module generated for this instance".to_string(),
+                    pos,
+                    mismatches,
+                ));
+            }
+        }
+
         // Add the component to the filemap
         self.ext_map
             .entry(file.to_string_lossy().to_string())
             .or_default()
             .push(idx.get());
+        // Other Verilog files generated by the tool are included as well
+        for dep in files.iter().filter(|f| {
+            f.extension().is_some_and(|ext| ext == "v" || ext == "sv")
+        }) {
+            self.ext_map
+                .entry(dep.to_string_lossy().to_string())
+                .or_default();
+        }
 
-        idx
+        Ok(idx)
     }
 
     /// Monomorphize an external component.
@@ -224,7 +436,14 @@ impl Monomorphize<'_> {
     }
 
     /// Monomorphize a component and return its index in the new context.
-    pub fn monomorphize(&mut self, ck: CompKey) -> Base<ir::Component> {
+    /// `pos` is the location of the instance that requires the component.
+    /// Fails if the depth of nested instantiations exceeds the recursion limit
+    /// or a generated component cannot be generated.
+    pub fn monomorphize(
+        &mut self,
+        ck: CompKey,
+        pos: utils::GPosIdx,
+    ) -> Result<Base<ir::Component>, u64> {
         log::debug!("Monomorphizing `{}'", ck.comp.idx());
         let CompKey { comp, params } = ck;
         let underlying = self.old.get(comp.idx());
@@ -237,16 +456,20 @@ impl Monomorphize<'_> {
 
         // If we've already processed this, return the component
         if let Some(&name) = self.processed.get(&n_ck) {
-            return name;
+            return Ok(name);
         }
 
         if underlying.is_gen() {
-            return self.generated(comp, params, n_ck);
+            return self.generated(comp, params, n_ck, pos);
         }
 
         // Copy the component signature if it is an external and return it.
         if underlying.is_ext() {
-            return self.ext(comp, n_ck);
+            return Ok(self.ext(comp, n_ck));
+        }
+
+        if self.stack.len() >= self.recursion_limit {
+            return Err(self.recursion_limit_error(&n_ck));
         }
 
         // Otherwise monomorphize the definition of the component
@@ -255,52 +478,123 @@ impl Monomorphize<'_> {
 
         // the component whose signature we want to monomorphize
         // Monomorphize the sig
+        self.stack.push(n_ck.clone());
         let mono_comp = MonoDeferred::new(
             UnderlyingComp::new(self.old.get(comp.idx())),
             self,
             monosig,
         )
         .comp();
+        self.stack.pop();
 
-        let new_comp = self.ctx.add(mono_comp).base();
+        let new_comp = self.ctx.add(mono_comp?).base();
         self.processed.insert(n_ck, new_comp);
 
         // return the `base` index so we can update the instance
-        new_comp
+        Ok(new_comp)
     }
 }
 
 impl Monomorphize<'_> {
+    /// Check that the bindings provided for the top-level component satisfy
+    /// the constraints on its parameters. Monomorphization assumes that they
+    /// hold.
+    pub fn check_bindings(ctx: &ir::Context) -> Result<(), u64> {
+        let Some(ep) = &ctx.entrypoint else {
+            return Ok(());
+        };
+        let mut comp = ctx.get(ep.comp).clone();
+        let params = comp.sig_params().collect_vec();
+        let vals = ep
+            .bindings
+            .iter()
+            .map(|v| comp.add(ir::Expr::Concrete(*v)))
+            .collect_vec();
+        let binding = ir::Bind::new(params.iter().copied().zip(vals));
+        let assignment = params
+            .iter()
+            .zip(&ep.bindings)
+            .map(|(p, v)| {
+                let name = comp
+                    .src_info
+                    .as_ref()
+                    .map(|src| src.params.get(*p).to_string())
+                    .unwrap_or_else(|| comp.display(*p));
+                format!("{name} = {v}")
+            })
+            .join(", ");
+
+        let mut diag = utils::Diagnostics::default();
+        for (prop, pos) in comp.get_param_asserts().to_vec() {
+            let prop = ir::Subst::new(prop, &binding).apply(&mut comp);
+            if prop.is_false(&comp) {
+                let err = utils::Error::malformed(
+                    "parameter bindings violate the constraints of the top-level component",
+                )
+                .add_note(diag.add_info(
+                    format!("constraint does not hold when {assignment}"),
+                    pos,
+                ));
+                diag.add_error(err);
+            }
+        }
+        diag.report_all().map_or(Ok(()), Err)
+    }
+
     /// Monomorphize the context by tracing starting from the top-level component.
+    /// External components named in `keep` are added to the result even if the
+    /// program does not instantiate them.
     /// Returns an empty context if there is no top-level component.
     pub fn transform(
         ctx: &ir::Context,
         generated: &mut Option<fgen::GenExec>,
-    ) -> ir::Context {
+        recursion_limit: usize,
+        keep: &[&str],
+    ) -> Result<ir::Context, u64> {
         let Some(entrypoint) = &ctx.entrypoint else {
             log::warn!("Program has no entrypoint. Result will be empty.");
-            return ir::Context {
+            return Ok(ir::Context {
                 comps: IndexStore::default(),
                 entrypoint: None,
                 externals: HashMap::new(),
-            };
+            });
         };
         let EntryPoint {
             comp: entrypoint,
             bindings,
         } = entrypoint;
 
+        // Run the tools for the generated components that are known upfront
+        // concurrently instead of one at a time while monomorphizing
+        if let Some(gen_exec) = generated {
+            gen_exec.prefetch(Prefetch::collect(ctx, recursion_limit));
+        }
+
         let entrypoint = entrypoint.ul();
         // Monomorphize the entrypoint
-        let mut mono = Monomorphize::new(ctx, generated);
+        let mut mono = Monomorphize::new(ctx, generated, recursion_limit);
         let ck = CompKey::new(entrypoint, bindings.clone());
-        mono.monomorphize(ck.clone());
+        mono.monomorphize(ck.clone(), utils::GPosIdx::UNKNOWN)?;
+        for (idx, comp) in ctx.iter() {
+            let kept = comp.is_ext()
+                && !comp.is_gen()
+                && comp
+                    .src_info
+                    .as_ref()
+                    .is_some_and(|src| keep.contains(&src.name.as_ref()));
+            if kept {
+                mono.monomorphize(
+                    CompKey::new(idx.ul(), vec![]),
+                    utils::GPosIdx::UNKNOWN,
+                )?;
+            }
+        }
 
         let new_entrypoint = mono.processed.get(&ck).unwrap();
         // New component no longer has any bindings
         mono.ctx.entrypoint = Some(EntryPoint::new(new_entrypoint.get()));
         mono.ctx.externals = mono.ext_map;
         ir::Validate::context(&mono.ctx);
-        mono.ctx
+        Ok(mono.ctx)
     }
 }
@@ -6,6 +6,7 @@ use fil_ir::{
     self as ir, AddCtx, Ctx, DenseIndexInfo, DisplayCtx, Foreign, MutCtx,
     SparseInfoMap,
 };
+use fil_utils::GPosIdx;
 use itertools::Itertools;
 use std::collections::HashMap;
 
@@ -198,12 +199,14 @@ impl MonoSig {
             | ir::info::Reason::EventConstraint { .. }
             | ir::info::Reason::BundleLenMatch { .. }
             | ir::info::Reason::BundleWidthMatch { .. }
+            | ir::info::Reason::InterpMatch { .. }
             | ir::info::Reason::InBoundsAccess { .. }
             | ir::info::Reason::BundleDelay { .. }
             | ir::info::Reason::WellFormedInterval { .. }
             | ir::info::Reason::EventTrig { .. }
             | ir::info::Reason::EventLive { .. }
             | ir::info::Reason::EventLiveDelay { .. }
+            | ir::info::Reason::Termination { .. }
             | ir::info::Reason::Misc { .. }
             | ir::info::Reason::Generated { .. } => {
                 ir::info::Reason::generated(
@@ -486,6 +489,7 @@ impl MonoSig {
                         .collect(),
                     // Things do not need to be generated after monomorphize
                     gen_tool: None,
+                    gen_pos: GPosIdx::UNKNOWN,
                 }
             },
         ));
@@ -697,7 +701,7 @@ impl MonoSig {
         underlying: &UnderlyingComp,
         pass: &mut Monomorphize,
         inst: Underlying<ir::Instance>,
-    ) -> Base<ir::Instance> {
+    ) -> Result<Base<ir::Instance>, u64> {
         let ir::Instance {
             comp,
             args,
@@ -708,7 +712,11 @@ impl MonoSig {
 
         // Monomorphize the component
         let ck = self.comp_key(underlying, inst, pass);
-        let mono_comp = pass.monomorphize(ck.clone());
+        let pos = match underlying.get(info.ul()).into() {
+            Some(&ir::info::Instance { comp_loc, .. }) => comp_loc,
+            None => GPosIdx::UNKNOWN,
+        };
+        let mono_comp = pass.monomorphize(ck.clone(), pos)?;
 
         // Binding for parameters defined by this instance
         self.binding.extend(params.iter().map(|p| {
@@ -749,7 +757,7 @@ impl MonoSig {
         let new_idx = self.base.add(new_inst);
         self.instance_map.insert(inst, new_idx);
 
-        new_idx
+        Ok(new_idx)
     }
 
     /// Return base representation of a port that has already been monomorphized
@@ -797,6 +805,7 @@ impl MonoSig {
             width,
             live,
             info,
+            ..
         } = underlying.get(port);
         let attrs = underlying.port_attrs().get(port.idx());
 
@@ -818,6 +827,7 @@ impl MonoSig {
             width: *width,      // placeholder
             live: live.clone(), // placeholder
             info: info.get(),
+            interp: None, // placeholder
         });
 
         self.base.push_port_attrs(new_port, attrs.clone());
@@ -829,7 +839,7 @@ impl MonoSig {
         new_port
     }
 
-    /// Monomorphize [ir::Liveness] and width of a port.
+    /// Monomorphize [ir::Liveness], width, and interpretation of a port.
     /// This is seperate from [port_def] because for signature ports, we need to
     /// do this after the body has been monomorphized. This is because port
     /// liveness and widths might mention existentially quantified parameters
@@ -842,7 +852,11 @@ impl MonoSig {
         new_port: Base<ir::Port>,
     ) {
         let ir::Port {
-            owner, width, live, ..
+            owner,
+            width,
+            live,
+            interp,
+            ..
         } = underlying.get(port);
 
         // Find the new port owner
@@ -876,9 +890,18 @@ impl MonoSig {
         mono_liveness.range =
             self.range(underlying, pass, &mono_liveness.range);
 
+        let mono_interp = interp.clone().map(|interp| match interp {
+            ir::Interp::Fixed { int, frac } => ir::Interp::Fixed {
+                int: self.expr(underlying, int.ul(), pass).get(),
+                frac: self.expr(underlying, frac.ul(), pass).get(),
+            },
+            interp => interp,
+        });
+
         let port = self.base.get_mut(new_port);
         port.live = mono_liveness; // update
         port.width = mono_width.get(); // update
         port.owner = mono_owner; // update
+        port.interp = mono_interp; // update
     }
 }
@@ -0,0 +1,196 @@
+use fil_ast as ast;
+use fil_gen as fgen;
+use fil_ir::{self as ir, Ctx};
+use std::collections::{HashMap, HashSet};
+
+/// Collects the instances of generated components that a program requires
+/// before it is monomorphized so that their tools can be run concurrently.
+///
+/// Starting from the entrypoint, the pass follows the instances whose
+/// arguments can be computed from the parameters of the component. Instances
+/// whose arguments depend on existential parameters, such as the latency of
+/// a generated component, are skipped and generated by [super::Monomorphize]
+/// once the parameters are known.
+pub struct Prefetch<'a> {
+    ctx: &'a ir::Context,
+    /// Components that have already been visited with their parameters
+    visited: HashSet<(ir::CompIdx, Vec<u64>)>,
+    /// Instances of generated components along with the tool generating them
+    instances: Vec<(String, fgen::Instance)>,
+    /// Maximum depth of nested instantiations
+    recursion_limit: usize,
+}
+
+impl<'a> Prefetch<'a> {
+    /// Instances of generated components required by the entrypoint of the
+    /// context
+    pub fn collect(
+        ctx: &'a ir::Context,
+        recursion_limit: usize,
+    ) -> Vec<(String, fgen::Instance)> {
+        let mut pass = Prefetch {
+            ctx,
+            visited: HashSet::new(),
+            instances: vec![],
+            recursion_limit,
+        };
+        if let Some(ep) = &ctx.entrypoint {
+            pass.comp(ep.comp, ep.bindings.clone(), 0);
+        }
+        pass.instances
+    }
+
+    fn comp(&mut self, idx: ir::CompIdx, params: Vec<u64>, depth: usize) {
+        if depth > self.recursion_limit
+            || !self.visited.insert((idx, params.clone()))
+        {
+            return;
+        }
+        let comp = self.ctx.get(idx);
+        if comp.is_gen() {
+            let Some(src) = &comp.src_info else {
+                unreachable!("external component has no src_info")
+            };
+            let Some(tool) = &src.gen_tool else {
+                unreachable!("gen component does not have a tool")
+            };
+            let inst = fgen::Instance {
+                name: src.name.as_ref().to_string(),
+                parameters: params.iter().map(|p| p.to_string()).collect(),
+            };
+            self.instances.push((tool.clone(), inst));
+            return;
+        }
+        if comp.is_ext() {
+            return;
+        }
+
+        let mut env: HashMap<_, _> = comp.sig_params().zip(params).collect();
+        self.cmds(&comp.cmds, comp, &mut env, depth);
+    }
+
+    fn cmds(
+        &mut self,
+        cmds: &[ir::Command],
+        comp: &ir::Component,
+        env: &mut HashMap<ir::ParamIdx, u64>,
+        depth: usize,
+    ) {
+        for cmd in cmds {
+            match cmd {
+                ir::Command::Instance(idx) => {
+                    let inst = comp.get(*idx);
+                    let args = inst
+                        .args
+                        .iter()
+                        .map(|e| Self::expr(*e, comp, env))
+                        .collect::<Option<Vec<_>>>();
+                    if let Some(args) = args {
+                        self.comp(inst.comp, args, depth + 1);
+                    }
+                }
+                ir::Command::Let(ir::Let {
+                    param,
+                    expr: Some(expr),
+                }) => {
+                    if let Some(v) = Self::expr(*expr, comp, env) {
+                        env.insert(*param, v);
+                    }
+                }
+                ir::Command::ForLoop(l) => {
+                    let (Some(start), Some(end)) = (
+                        Self::expr(l.start, comp, env),
+                        Self::expr(l.end, comp, env),
+                    ) else {
+                        continue;
+                    };
+                    for i in start..end {
+                        env.insert(l.index, i);
+                        self.cmds(&l.body, comp, env, depth);
+                    }
+                    env.remove(&l.index);
+                }
+                ir::Command::If(i) => match Self::prop(i.cond, comp, env) {
+                    Some(true) => self.cmds(&i.then, comp, env, depth),
+                    Some(false) => self.cmds(&i.alt, comp, env, depth),
+                    None => (),
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// Value of the expression if it only depends on the known parameters
+    pub(super) fn expr(
+        e: ir::ExprIdx,
+        comp: &ir::Component,
+        env: &HashMap<ir::ParamIdx, u64>,
+    ) -> Option<u64> {
+        match comp.get(e) {
+            ir::Expr::Param(p) => {
+                env.get(p).copied().or_else(|| match comp.get(*p).owner {
+                    ir::ParamOwner::Let { bind: Some(bind) } => {
+                        Self::expr(bind, comp, env)
+                    }
+                    _ => None,
+                })
+            }
+            ir::Expr::Concrete(n) => Some(*n),
+            ir::Expr::Bin { op, lhs, rhs } => {
+                let l = Self::expr(*lhs, comp, env)?;
+                let r = Self::expr(*rhs, comp, env)?;
+                match op {
+                    ast::Op::Add => l.checked_add(r),
+                    ast::Op::Sub => l.checked_sub(r),
+                    ast::Op::Mul => l.checked_mul(r),
+                    ast::Op::Div => l.checked_div(r),
+                    ast::Op::Mod => l.checked_rem(r),
+                }
+            }
+            ir::Expr::Fn { op, args } => args
+                .iter()
+                .map(|a| Self::expr(*a, comp, env))
+                .collect::<Option<Vec<_>>>()
+                .map(|args| op.eval(args)),
+            ir::Expr::If { cond, then, alt } => {
+                if Self::prop(*cond, comp, env)? {
+                    Self::expr(*then, comp, env)
+                } else {
+                    Self::expr(*alt, comp, env)
+                }
+            }
+        }
+    }
+
+    /// Value of the proposition if it only depends on the known parameters
+    fn prop(
+        p: ir::PropIdx,
+        comp: &ir::Component,
+        env: &HashMap<ir::ParamIdx, u64>,
+    ) -> Option<bool> {
+        match comp.get(p) {
+            ir::Prop::True => Some(true),
+            ir::Prop::False => Some(false),
+            ir::Prop::Cmp(ir::CmpOp { op, lhs, rhs }) => {
+                let l = Self::expr(*lhs, comp, env)?;
+                let r = Self::expr(*rhs, comp, env)?;
+                Some(match op {
+                    ir::Cmp::Gt => l > r,
+                    ir::Cmp::Gte => l >= r,
+                    ir::Cmp::Eq => l == r,
+                })
+            }
+            ir::Prop::Not(p) => Self::prop(*p, comp, env).map(|b| !b),
+            ir::Prop::And(l, r) => {
+                Some(Self::prop(*l, comp, env)? && Self::prop(*r, comp, env)?)
+            }
+            ir::Prop::Or(l, r) => {
+                Some(Self::prop(*l, comp, env)? || Self::prop(*r, comp, env)?)
+            }
+            ir::Prop::Implies(l, r) => {
+                Some(!Self::prop(*l, comp, env)? || Self::prop(*r, comp, env)?)
+            }
+            ir::Prop::TimeCmp(_) | ir::Prop::TimeSubCmp(_) => None,
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use super::Prefetch;
+use crate::ir_passes::{
+    DumpInterface,
+    dump_interface::{Interface, Port},
+};
+use fil_ast as ast;
+use fil_ir::{self as ir, Ctx};
+use fil_utils::GPosIdx;
+use std::collections::HashMap;
+
+/// The signature of a generated module reported by the tool that generated
+/// it.
+///
+/// The declared signature of a generated component is used to check the
+/// program before any tool runs, so the reported signature must agree with
+/// the declared one once its parameters are known. Existential parameters
+/// that the tool does not report as outputs are inferred from the reported
+/// signature instead.
+pub struct ToolSignature {
+    interface: Interface,
+}
+
+impl ToolSignature {
+    /// Parse the signature reported for an instance. `name` identifies the
+    /// signature in error messages and `fail` reports malformed signatures.
+    pub fn parse(
+        name: String,
+        sig: String,
+        fail: impl FnOnce(String) -> u64,
+    ) -> Result<Self, u64> {
+        let src = sig.clone();
+        let sig = match ast::FilamentParser::parse_signature(name.clone(), sig)
+        {
+            Ok(sig) => sig,
+            Err(e) => return Err(fail(e.kind)),
+        };
+        if sig.params().next().is_some() {
+            return Err(fail(format!(
+                "signature of `{}' must not have parameters",
+                sig.name
+            )));
+        }
+        let ns = ast::Namespace {
+            externs: vec![ast::Extern::new(
+                name,
+                vec![sig],
+                None,
+                GPosIdx::UNKNOWN,
+            )],
+            ..Default::default()
+        };
+        // Errors in the signature are reported before the malformed signature
+        let ctx = fil_ir::transform(ns).map_err(|n| {
+            n + fail(format!("`{src}' is not a valid signature"))
+        })?;
+        Ok(Self {
+            interface: DumpInterface::interface(&ctx, ir::CompIdx::new(0)),
+        })
+    }
+
+    /// Infer the values of the existential parameters of the declared
+    /// component from the reported signature. `env` contains the known
+    /// values of the parameters and is extended with the inferred ones.
+    pub fn infer(
+        &self,
+        comp: &ir::Component,
+        env: &mut HashMap<ir::ParamIdx, u64>,
+    ) {
+        let Some(src) = &comp.src_info else {
+            unreachable!("generated component has no src_info")
+        };
+
+        // Pairs of expressions in the declaration and their reported values
+        let mut eqs = vec![];
+        for (ev, event) in comp.events().iter() {
+            let name = src.events.get(ev).to_string();
+            let reported =
+                self.interface.interfaces.iter().find(|e| e.event == name);
+            if let (Some(e), ir::TimeSub::Unit(delay)) =
+                (reported, &event.delay)
+            {
+                eqs.extend(e.delay.concrete().map(|v| (*delay, v)));
+            }
+        }
+        for (pidx, port) in comp.inputs().chain(comp.outputs()) {
+            let name = src.ports.get(pidx).to_string();
+            let Some(p) = self.port(&name) else {
+                continue;
+            };
+            let range = &port.live.range;
+            for (e, v) in [
+                (port.width, &p.width),
+                (comp.get(range.start).offset, &p.start),
+                (comp.get(range.end).offset, &p.end),
+            ] {
+                eqs.extend(v.concrete().map(|v| (e, v)));
+            }
+        }
+
+        // Solving an equation can make the value of another one known
+        while eqs.iter().fold(false, |progress, (e, v)| {
+            Self::solve(*e, *v, comp, env) || progress
+        }) {}
+    }
+
+    /// Bind the unknown existential parameter in `e` so that it evaluates to
+    /// `v`. Returns true if a parameter was bound.
+    fn solve(
+        e: ir::ExprIdx,
+        v: u64,
+        comp: &ir::Component,
+        env: &mut HashMap<ir::ParamIdx, u64>,
+    ) -> bool {
+        let eval =
+            |e: ir::ExprIdx, env: &HashMap<_, _>| Prefetch::expr(e, comp, env);
+        match comp.get(e) {
+            ir::Expr::Param(p) => {
+                let exists =
+                    matches!(comp.get(*p).owner, ir::ParamOwner::Exists { .. });
+                if !exists || env.contains_key(p) {
+                    return false;
+                }
+                env.insert(*p, v);
+                true
+            }
+            ir::Expr::Bin {
+                op: ast::Op::Add,
+                lhs,
+                rhs,
+            } => match (eval(*lhs, env), eval(*rhs, env)) {
+                (Some(l), None) => v
+                    .checked_sub(l)
+                    .is_some_and(|v| Self::solve(*rhs, v, comp, env)),
+                (None, Some(r)) => v
+                    .checked_sub(r)
+                    .is_some_and(|v| Self::solve(*lhs, v, comp, env)),
+                _ => false,
+            },
+            ir::Expr::Bin {
+                op: ast::Op::Sub,
+                lhs,
+                rhs,
+            } => match (eval(*lhs, env), eval(*rhs, env)) {
+                (None, Some(r)) => v
+                    .checked_add(r)
+                    .is_some_and(|v| Self::solve(*lhs, v, comp, env)),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// The reported port with the given name
+    fn port(&self, name: &str) -> Option<&Port> {
+        self.interface
+            .inputs
+            .iter()
+            .chain(&self.interface.outputs)
+            .find(|p| p.name == name)
+    }
+
+    /// Differences between the reported signature and the interface of the
+    /// monomorphized declaration
+    pub fn mismatches(&self, declared: &Interface) -> Vec<String> {
+        let reported = &self.interface;
+        let mut out = vec![];
+
+        for ev in &declared.interfaces {
+            let Some(r) =
+                reported.interfaces.iter().find(|r| r.event == ev.event)
+            else {
+                out.push(format!("event `{}' is missing", ev.event));
+                continue;
+            };
+            if r.delay != ev.delay {
+                out.push(format!(
+                    "event `{}' has delay {} but is declared with delay {}",
+                    ev.event, r.delay, ev.delay
+                ));
+            }
+            if r.phantom != ev.phantom {
+                let kind =
+                    |phantom| if phantom { "phantom" } else { "not phantom" };
+                out.push(format!(
+                    "event `{}' is {} but is declared as {}",
+                    ev.event,
+                    kind(r.phantom),
+                    kind(ev.phantom)
+                ));
+            }
+        }
+        for r in &reported.interfaces {
+            if !declared.interfaces.iter().any(|ev| ev.event == r.event) {
+                out.push(format!("event `{}' is not declared", r.event));
+            }
+        }
+
+        for (kind, decl, rep) in [
+            ("input", &declared.inputs, &reported.inputs),
+            ("output", &declared.outputs, &reported.outputs),
+        ] {
+            for p in decl {
+                let Some(r) = rep.iter().find(|r| r.name == p.name) else {
+                    out.push(format!("{kind} `{}' is missing", p.name));
+                    continue;
+                };
+                if r.width != p.width {
+                    out.push(format!(
+                        "{kind} `{}' has width {} but is declared with width {}",
+                        p.name, r.width, p.width
+                    ));
+                }
+                if Self::interval(r) != Self::interval(p) {
+                    out.push(format!(
+                        "{kind} `{}' is live in {} but is declared live in {}",
+                        p.name,
+                        Self::interval(r),
+                        Self::interval(p)
+                    ));
+                }
+                let lens = |p: &Port| {
+                    p.bundle.as_ref().map(|dims| {
+                        dims.iter()
+                            .map(|d| d.len.to_string())
+                            .collect::<Vec<_>>()
+                    })
+                };
+                if lens(r) != lens(p) {
+                    out.push(format!(
+                        "{kind} `{}' does not have the declared bundle dimensions",
+                        p.name
+                    ));
+                }
+            }
+            for r in rep {
+                if !decl.iter().any(|p| p.name == r.name) {
+                    out.push(format!("{kind} `{}' is not declared", r.name));
+                }
+            }
+        }
+        out
+    }
+
+    /// Liveness interval of a port
+    fn interval(p: &Port) -> String {
+        format!(
+            "['{}+{}, '{}+{}]",
+            p.event,
+            p.start,
+            p.end_event.as_ref().unwrap_or(&p.event),
+            p.end
+        )
+    }
+}
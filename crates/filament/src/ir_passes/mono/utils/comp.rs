@@ -30,12 +30,18 @@ impl<'a> UnderlyingComp<'a> {
     pub fn port_attrs(&self) -> &DenseIndexInfo<ir::Port, utils::PortAttrs> {
         &self.0.port_attrs
     }
+    pub fn user_name(&self) -> Option<String> {
+        self.0.user_name()
+    }
     pub fn src_info(&self) -> &Option<InterfaceSrc> {
         &self.0.src_info
     }
     pub fn unannotated_ports(&self) -> &Vec<(ast::Id, u64)> {
         &self.0.unannotated_ports
     }
+    pub fn sig_params(&self) -> impl Iterator<Item = ir::ParamIdx> + '_ {
+        self.0.sig_params()
+    }
     pub fn exist_params(&self) -> impl Iterator<Item = ir::ParamIdx> + '_ {
         self.0.exist_params()
     }
@@ -96,6 +102,9 @@ impl BaseComp {
     pub fn set_unannotated_ports(&mut self, other: Vec<(ast::Id, u64)>) {
         self.0.unannotated_ports = Box::new(other);
     }
+    pub fn set_mono_binding(&mut self, binding: ir::MonoBinding) {
+        self.0.mono_binding = binding;
+    }
     pub fn set_src_info(&mut self, other: Option<InterfaceSrc>) {
         self.0.src_info = other;
     }
@@ -0,0 +1,507 @@
+use crate::ir_passes::{FsmType, NameGenerator, max_states};
+use fil_ir::{self as ir, Ctx};
+use fil_utils::{self as utils, AttrCtx};
+use itertools::Itertools;
+use linked_hash_map::LinkedHashMap;
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt::Write as _};
+
+#[derive(Default)]
+/// Estimate of the hardware used by a component
+struct Resources {
+    /// Number of registers of each width
+    registers: BTreeMap<u64, u64>,
+    /// Number of adders and subtractors of each width
+    adders: BTreeMap<u64, u64>,
+    /// Number of multipliers of each width
+    multipliers: BTreeMap<u64, u64>,
+    /// Number of multiplexers with the given number of inputs and width.
+    /// Includes the multiplexers introduced by sharing an instance between
+    /// invocations.
+    muxes: BTreeMap<(u64, u64), u64>,
+    /// Flip-flops used by the FSMs of the events
+    fsm_flip_flops: u64,
+    /// Number of uses of primitives without a resource attribute
+    unclassified: BTreeMap<String, u64>,
+}
+
+impl Resources {
+    /// Add `times` copies of the resources in `other`
+    fn add(&mut self, other: &Resources, times: u64) {
+        fn merge<K: Ord + Clone>(
+            into: &mut BTreeMap<K, u64>,
+            from: &BTreeMap<K, u64>,
+            times: u64,
+        ) {
+            for (k, n) in from {
+                *into.entry(k.clone()).or_default() += n * times;
+            }
+        }
+        merge(&mut self.registers, &other.registers, times);
+        merge(&mut self.adders, &other.adders, times);
+        merge(&mut self.multipliers, &other.multipliers, times);
+        merge(&mut self.muxes, &other.muxes, times);
+        merge(&mut self.unclassified, &other.unclassified, times);
+        self.fsm_flip_flops += other.fsm_flip_flops * times;
+    }
+
+    fn write(&self, f: &mut String) -> std::fmt::Result {
+        let by_width = |f: &mut String,
+                        name: &str,
+                        map: &BTreeMap<u64, u64>|
+         -> std::fmt::Result {
+            if map.is_empty() {
+                return Ok(());
+            }
+            writeln!(f, "  {name}: {}", map.values().sum::<u64>())?;
+            for (w, n) in map {
+                writeln!(f, "    {w} bits x {n}")?;
+            }
+            Ok(())
+        };
+        if !self.registers.is_empty() {
+            let bits: u64 = self.registers.iter().map(|(w, n)| w * n).sum();
+            writeln!(
+                f,
+                "  registers: {} ({bits} bits)",
+                self.registers.values().sum::<u64>()
+            )?;
+            for (w, n) in &self.registers {
+                writeln!(f, "    {w} bits x {n}")?;
+            }
+        }
+        by_width(f, "adders", &self.adders)?;
+        by_width(f, "multipliers", &self.multipliers)?;
+        if !self.muxes.is_empty() {
+            writeln!(f, "  muxes: {}", self.muxes.values().sum::<u64>())?;
+            for ((inputs, w), n) in &self.muxes {
+                writeln!(f, "    {inputs}:1 {w} bits x {n}")?;
+            }
+        }
+        writeln!(f, "  fsm flip-flops: {}", self.fsm_flip_flops)?;
+        if !self.unclassified.is_empty() {
+            writeln!(
+                f,
+                "  unclassified: {}",
+                self.unclassified.values().sum::<u64>()
+            )?;
+            for (prim, n) in &self.unclassified {
+                writeln!(f, "    {prim} x {n}")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn json(&self) -> ResourcesJson {
+        let by_width = |map: &BTreeMap<u64, u64>| {
+            map.iter()
+                .map(|(w, n)| WidthCount {
+                    width: *w,
+                    count: *n,
+                })
+                .collect()
+        };
+        ResourcesJson {
+            registers: by_width(&self.registers),
+            adders: by_width(&self.adders),
+            multipliers: by_width(&self.multipliers),
+            muxes: self
+                .muxes
+                .iter()
+                .map(|((inputs, w), n)| MuxCount {
+                    inputs: *inputs,
+                    width: *w,
+                    count: *n,
+                })
+                .collect(),
+            fsm_flip_flops: self.fsm_flip_flops,
+            unclassified: self
+                .unclassified
+                .iter()
+                .map(|(prim, n)| NameCount {
+                    name: prim.clone(),
+                    count: *n,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+/// Number of units with the given width
+struct WidthCount {
+    width: u64,
+    count: u64,
+}
+
+#[derive(Serialize)]
+/// Number of multiplexers with the given number of inputs and width
+struct MuxCount {
+    inputs: u64,
+    width: u64,
+    count: u64,
+}
+
+#[derive(Serialize)]
+/// Number of uses of a primitive or component
+struct NameCount {
+    name: String,
+    count: u64,
+}
+
+#[derive(Serialize)]
+/// JSON representation of [Resources]
+struct ResourcesJson {
+    registers: Vec<WidthCount>,
+    adders: Vec<WidthCount>,
+    multipliers: Vec<WidthCount>,
+    muxes: Vec<MuxCount>,
+    fsm_flip_flops: u64,
+    unclassified: Vec<NameCount>,
+}
+
+#[derive(Serialize)]
+/// Resources used by a component in the JSON report
+struct CompResources {
+    name: String,
+    #[serde(flatten)]
+    resources: ResourcesJson,
+    /// Number of instances of each non-primitive component. Empty for the
+    /// total of the entrypoint.
+    subcomponents: Vec<NameCount>,
+}
+
+#[derive(Serialize)]
+/// JSON report of the resources used by a program
+struct ResourcesReport {
+    components: Vec<CompResources>,
+    /// Total for the entrypoint including its subcomponents
+    total: Option<CompResources>,
+}
+
+/// Resources used directly by each monomorphized component along with the
+/// number of instances of each non-primitive component it uses
+type Estimate =
+    LinkedHashMap<ir::CompIdx, (Resources, LinkedHashMap<ir::CompIdx, u64>)>;
+
+/// Reports about the monomorphized components of a program that are computed
+/// without synthesizing the design.
+pub struct Report;
+
+impl Report {
+    /// Report the timing behavior of every monomorphized component:
+    /// - The initiation interval (delay) and number of FSM states of each event.
+    /// - The latency from each input to each output that starts after it.
+    /// - The number of cycles each instance is busy for in every interval of
+    ///   the events that invoke it.
+    pub fn timing(ctx: &ir::Context) -> String {
+        let mut out = String::new();
+        for (idx, comp) in ctx.comps.iter() {
+            if comp.is_ext() {
+                continue;
+            }
+            Self::comp_timing(&mut out, idx, comp, ctx).unwrap();
+        }
+        out
+    }
+
+    /// Formats a number of cycles
+    fn cycles(n: u64) -> String {
+        if n == 1 {
+            "1 cycle".to_string()
+        } else {
+            format!("{n} cycles")
+        }
+    }
+
+    /// The concrete delay of an event or binding
+    fn delay(delay: &ir::TimeSub, comp: &ir::Component) -> Option<u64> {
+        match delay {
+            ir::TimeSub::Unit(d) => d.as_concrete(comp),
+            ir::TimeSub::Sym { .. } => None,
+        }
+    }
+
+    fn comp_timing(
+        f: &mut String,
+        idx: ir::CompIdx,
+        comp: &ir::Component,
+        ctx: &ir::Context,
+    ) -> std::fmt::Result {
+        let ng = NameGenerator::new(true);
+        let states = max_states(comp);
+        let ii = |ev: ir::EventIdx| Self::delay(&comp.get(ev).delay, comp);
+        let show =
+            |n: Option<u64>| n.map_or("?".to_string(), |n| n.to_string());
+
+        writeln!(f, "component {}", ng.mono_comp_name(idx, ctx))?;
+        writeln!(f, "  events:")?;
+        for (ev, _) in comp.events().iter() {
+            writeln!(
+                f,
+                "    {}: initiation interval {}, {} states",
+                ng.event_name(ev, comp),
+                show(ii(ev)),
+                states.get(&ev).copied().unwrap_or(0)
+            )?;
+        }
+
+        // Latency from the start of an input to the start of an output that
+        // is scheduled using the same event.
+        let start = |p: &ir::Port| {
+            let t = comp.get(p.live.range.start);
+            (t.event, t.offset.as_concrete(comp))
+        };
+        let latencies = comp
+            .inputs()
+            .cartesian_product(comp.outputs().collect_vec())
+            .filter_map(|((i, ip), (o, op))| {
+                let ((iev, Some(is)), (oev, Some(os))) = (start(ip), start(op))
+                else {
+                    return None;
+                };
+                (iev == oev && os >= is).then(|| {
+                    (
+                        ng.sig_port_name(i, comp),
+                        ng.sig_port_name(o, comp),
+                        os - is,
+                    )
+                })
+            })
+            .collect_vec();
+        if !latencies.is_empty() {
+            writeln!(f, "  latency:")?;
+            for (i, o, lat) in latencies {
+                writeln!(f, "    {i} -> {o}: {}", Self::cycles(lat))?;
+            }
+        }
+
+        // Cycles each instance is busy for in an interval of the parent's
+        // events.
+        if comp.instances().iter().next().is_some() {
+            writeln!(f, "  instances:")?;
+        }
+        for (inst, instance) in comp.instances().iter() {
+            let invokes = comp
+                .invocations()
+                .iter()
+                .filter(|(_, inv)| inv.inst == inst)
+                .collect_vec();
+            let mut busy: LinkedHashMap<ir::EventIdx, Option<u64>> =
+                LinkedHashMap::new();
+            for (_, inv) in &invokes {
+                // An invocation keeps the instance busy until all the events
+                // it binds can be triggered again.
+                let mut longest: LinkedHashMap<ir::EventIdx, Option<u64>> =
+                    LinkedHashMap::new();
+                for eb in &inv.events {
+                    let parent = comp.get(eb.arg).event;
+                    let d = Self::delay(&eb.delay, comp);
+                    let l = longest.entry(parent).or_insert(Some(0));
+                    *l = l.zip(d).map(|(l, d)| l.max(d));
+                }
+                for (parent, d) in longest {
+                    let total = busy.entry(parent).or_insert(Some(0));
+                    *total = total.zip(d).map(|(t, d)| t + d);
+                }
+            }
+            let usage = busy
+                .iter()
+                .map(|(ev, b)| {
+                    let name = ng.event_name(*ev, comp);
+                    match (b, ii(*ev)) {
+                        (Some(b), Some(ii)) if ii > 0 => format!(
+                            "busy {b}/{ii} cycles of {name} ({}%)",
+                            b * 100 / ii
+                        ),
+                        (b, ii) => format!(
+                            "busy {}/{} cycles of {name}",
+                            show(*b),
+                            show(ii)
+                        ),
+                    }
+                })
+                .join(", ");
+            let name = instance
+                .info
+                .get_name(comp)
+                .unwrap_or_else(|| format!("inst{}", inst.get()));
+            write!(
+                f,
+                "    {name} ({}): {} invocation{}",
+                ng.mono_comp_name(instance.comp, ctx),
+                invokes.len(),
+                if invokes.len() == 1 { "" } else { "s" }
+            )?;
+            if usage.is_empty() {
+                writeln!(f)?;
+            } else {
+                writeln!(f, ", {usage}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Estimate the hardware used by every monomorphized component and the
+    /// total for the entrypoint, including the hardware of the components it
+    /// instantiates.
+    pub fn resources(ctx: &ir::Context) -> String {
+        let ng = NameGenerator::new(true);
+        let local = Self::estimate(ctx);
+        let mut out = String::new();
+        for (idx, (res, subs)) in &local {
+            writeln!(out, "component {}", ng.mono_comp_name(*idx, ctx))
+                .unwrap();
+            res.write(&mut out).unwrap();
+            if !subs.is_empty() {
+                writeln!(out, "  subcomponents:").unwrap();
+                for (sub, n) in subs {
+                    writeln!(out, "    {} x {n}", ng.mono_comp_name(*sub, ctx))
+                        .unwrap();
+                }
+            }
+        }
+
+        if let Some((ep, total)) = Self::total(ctx, &local) {
+            writeln!(out, "total for {}", ng.mono_comp_name(ep, ctx)).unwrap();
+            total.write(&mut out).unwrap();
+        }
+        out
+    }
+
+    /// The resources report as JSON
+    pub fn resources_json(ctx: &ir::Context) -> String {
+        let ng = NameGenerator::new(true);
+        let local = Self::estimate(ctx);
+        let components = local
+            .iter()
+            .map(|(idx, (res, subs))| CompResources {
+                name: ng.mono_comp_name(*idx, ctx),
+                resources: res.json(),
+                subcomponents: subs
+                    .iter()
+                    .map(|(sub, n)| NameCount {
+                        name: ng.mono_comp_name(*sub, ctx),
+                        count: *n,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let total = Self::total(ctx, &local).map(|(ep, total)| CompResources {
+            name: ng.mono_comp_name(ep, ctx),
+            resources: total.json(),
+            subcomponents: vec![],
+        });
+        let report = ResourcesReport { components, total };
+        format!("{}\n", serde_json::to_string_pretty(&report).unwrap())
+    }
+
+    /// The resources used directly by every monomorphized component
+    fn estimate(ctx: &ir::Context) -> Estimate {
+        let ng = NameGenerator::new(true);
+        ctx.comps
+            .iter()
+            .filter(|(_, comp)| !comp.is_ext())
+            .map(|(idx, comp)| (idx, Self::comp_resources(comp, ctx, &ng)))
+            .collect()
+    }
+
+    /// The resources used by the entrypoint including the resources of every
+    /// instance of its subcomponents
+    fn total(
+        ctx: &ir::Context,
+        local: &Estimate,
+    ) -> Option<(ir::CompIdx, Resources)> {
+        let ep = ctx.entrypoint.as_ref()?;
+        let mut total = Resources::default();
+        let mut todo = vec![(ep.comp, 1)];
+        while let Some((idx, times)) = todo.pop() {
+            let (res, subs) = &local[&idx];
+            total.add(res, times);
+            todo.extend(subs.iter().map(|(sub, n)| (*sub, n * times)));
+        }
+        Some((ep.comp, total))
+    }
+
+    /// The resources used directly by a component and the number of
+    /// instances of each non-primitive component it uses. Primitives are
+    /// classified using their resource attributes.
+    fn comp_resources(
+        comp: &ir::Component,
+        ctx: &ir::Context,
+        ng: &NameGenerator,
+    ) -> (Resources, LinkedHashMap<ir::CompIdx, u64>) {
+        let mut res = Resources::default();
+        let mut subs = LinkedHashMap::new();
+
+        for (event, states) in max_states(comp) {
+            if let Some(fsm) = FsmType::of_event(comp, event, states) {
+                res.fsm_flip_flops += fsm.flip_flops();
+            }
+        }
+
+        for (idx, inst) in comp.instances().iter() {
+            let sub = ctx.get(inst.comp);
+            if sub.is_ext() || sub.is_gen() {
+                let name = ng.comp_name(inst.comp, ctx);
+                let args = inst
+                    .args
+                    .iter()
+                    .map(|a| a.as_concrete(comp))
+                    .collect::<Option<Vec<_>>>()
+                    .unwrap_or_default();
+                let width = args.first().copied();
+                let entry = |map: &mut BTreeMap<u64, u64>, w| {
+                    *map.entry(w).or_default() += 1
+                };
+                let is = |attr| sub.attrs.get(attr) == Some(&true);
+                match width {
+                    Some(w) if is(utils::CompBool::Register) => {
+                        entry(&mut res.registers, w)
+                    }
+                    Some(w) if is(utils::CompBool::Adder) => {
+                        entry(&mut res.adders, w)
+                    }
+                    Some(w) if is(utils::CompBool::Multiplier) => {
+                        entry(&mut res.multipliers, w)
+                    }
+                    Some(w) if is(utils::CompBool::Mux) => {
+                        *res.muxes.entry((2, w)).or_default() += 1
+                    }
+                    _ => {
+                        let prim = if args.is_empty() {
+                            name
+                        } else {
+                            format!("{name}[{}]", args.iter().join(", "))
+                        };
+                        *res.unclassified.entry(prim).or_default() += 1;
+                    }
+                }
+            } else {
+                *subs.entry(inst.comp).or_insert(0) += 1;
+            }
+
+            // Inputs of an instance shared by multiple invocations are
+            // driven through a multiplexer.
+            let invokes = comp
+                .invocations()
+                .iter()
+                .filter(|(_, inv)| inv.inst == idx)
+                .collect_vec();
+            let shared = invokes.len() as u64;
+            if let Some((_, inv)) = invokes.first()
+                && shared > 1
+            {
+                for p in &inv.ports {
+                    let port = comp.get(*p);
+                    if let (true, Some(w)) =
+                        (port.is_inv_in(), port.width.as_concrete(comp))
+                    {
+                        *res.muxes.entry((shared, w)).or_default() += 1;
+                    }
+                }
+            }
+        }
+        (res, subs)
+    }
+}
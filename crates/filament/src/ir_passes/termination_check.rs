@@ -0,0 +1,190 @@
+use crate::{
+    cmdline,
+    ir_visitor::{Action, Construct, Visitor, VisitorData},
+};
+use fil_ir::{self as ir, AddCtx, Ctx};
+use fil_utils::GPosIdx;
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+
+/// Ensures that recursive instantiations terminate.
+///
+/// Two components are mutually recursive if each one (transitively)
+/// instantiates the other. For every instance of a component that is mutually
+/// recursive with the component containing it, the pass asserts that the
+/// instance's parameters are lexicographically smaller than the parameters of
+/// the containing component. Since parameters are natural numbers, this
+/// measure is well-founded and monomorphization is guaranteed to terminate.
+/// Components with fewer parameters than others in the same recursive group
+/// are padded with zeros.
+///
+/// Like [super::TypeCheck], this pass only generates the assertions. They are
+/// discharged along with the other assertions by [super::Discharge].
+///
+/// The check is only performed with `--check-termination` because it rejects
+/// recursions that terminate by increasing a parameter up to a bound. Without
+/// it, runaway recursion is caught by the recursion limit of
+/// [super::Monomorphize].
+pub struct TerminationCheck {
+    /// Whether the check is enabled
+    enabled: bool,
+    /// Components transitively instantiated by each component
+    reaches: HashMap<ir::CompIdx, HashSet<ir::CompIdx>>,
+    /// Number of parameters of each component
+    params: HashMap<ir::CompIdx, usize>,
+}
+
+impl TerminationCheck {
+    /// Add the components instantiated by the commands to `out`
+    fn callees(
+        cmds: &[ir::Command],
+        comp: &ir::Component,
+        out: &mut Vec<ir::CompIdx>,
+    ) {
+        for cmd in cmds {
+            match cmd {
+                ir::Command::Instance(idx) => out.push(comp[*idx].comp),
+                ir::Command::ForLoop(l) => Self::callees(&l.body, comp, out),
+                ir::Command::If(i) => {
+                    Self::callees(&i.then, comp, out);
+                    Self::callees(&i.alt, comp, out);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Are the two components mutually recursive?
+    fn recursive(&self, src: ir::CompIdx, dst: ir::CompIdx) -> bool {
+        self.reaches[&src].contains(&dst) && self.reaches[&dst].contains(&src)
+    }
+
+    /// Number of parameters that the measure of `comp` is padded to
+    fn measure_len(&self, comp: ir::CompIdx) -> usize {
+        self.reaches[&comp]
+            .iter()
+            .filter(|&&c| self.recursive(comp, c))
+            .map(|c| self.params[c])
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Proposition that `lhs` is lexicographically smaller than `rhs`
+    fn lex_lt(
+        lhs: &[ir::ExprIdx],
+        rhs: &[ir::ExprIdx],
+        comp: &mut ir::Component,
+    ) -> ir::PropIdx {
+        lhs.iter().zip_eq(rhs).collect_vec().into_iter().rev().fold(
+            comp.add(ir::Prop::False),
+            |rest, (&l, &r)| {
+                let lt = l.lt(r, comp);
+                let eq = l.equal(r, comp);
+                lt.or(eq.and(rest, comp), comp)
+            },
+        )
+    }
+}
+
+impl Construct for TerminationCheck {
+    fn from(opts: &cmdline::Opts, ctx: &mut ir::Context) -> Self {
+        let calls: HashMap<_, _> = ctx
+            .comps
+            .iter()
+            .map(|(idx, comp)| {
+                let mut out = Vec::new();
+                Self::callees(&comp.cmds, comp, &mut out);
+                (idx, out)
+            })
+            .collect();
+
+        let reaches = calls
+            .keys()
+            .map(|&idx| {
+                let mut seen = HashSet::new();
+                let mut todo = calls[&idx].clone();
+                while let Some(c) = todo.pop() {
+                    if seen.insert(c) {
+                        todo.extend(calls[&c].iter().copied());
+                    }
+                }
+                (idx, seen)
+            })
+            .collect();
+
+        let params = ctx
+            .comps
+            .iter()
+            .map(|(idx, comp)| (idx, comp.sig_params().count()))
+            .collect();
+
+        TerminationCheck {
+            enabled: opts.check_termination,
+            reaches,
+            params,
+        }
+    }
+
+    fn clear_data(&mut self) {}
+}
+
+impl Visitor for TerminationCheck {
+    fn name() -> &'static str {
+        "termination-check"
+    }
+
+    fn start(&mut self, data: &mut VisitorData) -> Action {
+        if self.enabled
+            && self.reaches[&data.idx]
+                .iter()
+                .any(|&c| self.recursive(data.idx, c))
+        {
+            Action::Continue
+        } else {
+            Action::Stop
+        }
+    }
+
+    fn instance(
+        &mut self,
+        inst: ir::InstIdx,
+        data: &mut VisitorData,
+    ) -> Action {
+        let callee = data.comp[inst].comp;
+        if !self.recursive(data.idx, callee) {
+            return Action::Continue;
+        }
+
+        let len = self.measure_len(data.idx);
+        let comp = &mut data.comp;
+        let zero = comp.num(0);
+        let params = comp.sig_params().collect_vec();
+        let args = comp[inst].args.to_vec();
+        let params = params
+            .into_iter()
+            .map(|p| p.expr(comp))
+            .pad_using(len, |_| zero)
+            .collect_vec();
+        let args = args.into_iter().pad_using(len, |_| zero).collect_vec();
+
+        // Parameters are natural numbers so the arguments must remain
+        // non-negative as well.
+        let mut prop = Self::lex_lt(&args, &params, comp);
+        for &arg in &args {
+            prop = arg.gte(zero, comp).and(prop, comp);
+        }
+        let mut nat = comp.add(ir::Prop::True);
+        for &param in &params {
+            nat = param.gte(zero, comp).and(nat, comp);
+        }
+        let prop = nat.implies(prop, comp);
+
+        let inst_loc = comp
+            .get(comp[inst].info)
+            .as_instance()
+            .map(|i| i.bind_loc)
+            .unwrap_or(GPosIdx::UNKNOWN);
+        let reason = comp.add(ir::info::Reason::termination(inst_loc).into());
+        Action::AddBefore(comp.assert(prop, reason).into_iter().collect())
+    }
+}
@@ -0,0 +1,353 @@
+use super::DumpInterface;
+use super::dump_interface::{Port, Value};
+use crate::interp::{self, Value as Bits};
+use fil_ir as ir;
+use fil_utils::{self as utils, FilamentResult};
+use itertools::Itertools;
+use std::{fmt::Write as _, path::Path};
+
+/// Number of cycles the design is held in reset. Same as the cocotb harness.
+const RESET_CYCLES: u64 = 3;
+
+/// A port of the entrypoint with a concrete width and liveness
+struct TbPort {
+    name: String,
+    width: u64,
+    start: u64,
+    end: u64,
+}
+
+impl TbPort {
+    fn new(port: &Port) -> FilamentResult<Self> {
+        let concrete = |v: &Value, what: &str| {
+            v.concrete().ok_or_else(|| {
+                utils::Error::misc(format!(
+                    "Testbench requires a concrete {what} for port `{}'",
+                    port.name
+                ))
+            })
+        };
+        Ok(Self {
+            name: port.name.clone(),
+            width: concrete(&port.width, "width")?,
+            start: concrete(&port.start, "start time")?,
+            end: concrete(&port.end, "end time")?,
+        })
+    }
+
+    /// Number of cycles the port is live for in a transaction
+    fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Condition that is true when the port is live for the transaction `txn`
+    fn live(&self) -> String {
+        format!(
+            "cycle >= txn * DELAY + {} && cycle < txn * DELAY + {}",
+            self.start, self.end
+        )
+    }
+}
+
+/// Formats the type of a signal with the given width.
+fn logic(width: u64) -> String {
+    if width == 1 {
+        "logic".to_string()
+    } else {
+        format!("logic [{}:0]", width - 1)
+    }
+}
+
+/// Formats a value as a SystemVerilog literal
+fn literal(v: &Bits) -> String {
+    format!("{}'h{:x}", v.width(), v.bits())
+}
+
+/// Generates a self-checking SystemVerilog testbench for the entrypoint of a
+/// program. Like the cocotb harness (`fud/harness/utils.py`), the `i`th
+/// transaction provides the `i`th value of every input in the cycles the input
+/// is live and transactions start every `delay` cycles. Outputs with values in
+/// the data file are compared against them in every cycle they are live.
+///
+/// The expected value of an output is either a single value for every cycle
+/// or a list with a value for each cycle the output is live in.
+pub struct Testbench;
+
+impl Testbench {
+    /// Generate the testbench for the entrypoint of the context using the
+    /// transactions in the data file.
+    pub fn emit(ctx: &ir::Context, data: &Path) -> FilamentResult<String> {
+        let ep = ctx.entrypoint.as_ref().unwrap();
+        let interface = DumpInterface::interface(ctx, ep.comp);
+        let Some(event) = interface.interfaces.iter().exactly_one().ok() else {
+            return Err(utils::Error::misc(format!(
+                "Testbench requires exactly one event in the entrypoint, found {}",
+                interface.interfaces.len()
+            )));
+        };
+        let delay = event.delay.concrete().ok_or_else(|| {
+            utils::Error::misc(format!(
+                "Testbench requires a concrete delay for event `{}'",
+                event.event
+            ))
+        })?;
+        let inputs = interface
+            .inputs
+            .iter()
+            .map(TbPort::new)
+            .collect::<FilamentResult<Vec<_>>>()?;
+        let outputs = interface
+            .outputs
+            .iter()
+            .map(TbPort::new)
+            .collect::<FilamentResult<Vec<_>>>()?;
+
+        let (data, txns) = interp::read_data(data)?;
+        if txns == 0 {
+            return Err(utils::Error::misc(
+                "Data file does not contain any transactions".to_string(),
+            ));
+        }
+        // Inputs that are live for longer than the delay are written by
+        // multiple transactions in the same cycle.
+        if let Some(port) = inputs.iter().find(|p| p.len() > delay) {
+            return Err(utils::Error::misc(format!(
+                "Invalid: multiple writes to input `{}' because it is live for {} cycles but transactions start every {delay} cycles",
+                port.name,
+                port.len()
+            )));
+        }
+
+        // Values of each input in every transaction
+        let mut input_vals = Vec::with_capacity(inputs.len());
+        for port in &inputs {
+            let Some(vals) = data.get(&port.name) else {
+                return Err(utils::Error::misc(format!(
+                    "Data file does not contain values for input `{}'",
+                    port.name
+                )));
+            };
+            input_vals.push(
+                vals.iter()
+                    .map(|v| Bits::parse(v, port.width))
+                    .collect::<FilamentResult<Vec<_>>>()?,
+            );
+        }
+
+        // Expected values of each output in every cycle it is live
+        let mut expected = Vec::with_capacity(outputs.len());
+        for port in &outputs {
+            let Some(vals) = data.get(&port.name) else {
+                expected.push(None);
+                continue;
+            };
+            let mut exp = Vec::with_capacity(txns * port.len() as usize);
+            for (txn, v) in vals.iter().enumerate() {
+                match v {
+                    serde_json::Value::Array(vs) => {
+                        if vs.len() as u64 != port.len() {
+                            return Err(utils::Error::misc(format!(
+                                "Transaction {txn} expects {} values for output `{}' but it is live for {} cycles",
+                                vs.len(),
+                                port.name,
+                                port.len()
+                            )));
+                        }
+                        for v in vs {
+                            exp.push(Bits::parse(v, port.width)?);
+                        }
+                    }
+                    v => {
+                        let v = Bits::parse(v, port.width)?;
+                        exp.extend((0..port.len()).map(|_| v.clone()));
+                    }
+                }
+            }
+            expected.push(Some(exp));
+        }
+
+        // Like the harness, wait for `delay` cycles after starting the last
+        // transaction even if it finishes earlier.
+        let states = event.states.concrete().ok_or_else(|| {
+            utils::Error::misc(format!(
+                "Testbench requires a concrete number of states for event `{}'",
+                event.event
+            ))
+        })?;
+        let states = states.max(1);
+        let last = (txns as u64 - 1) * delay;
+        let cycles = (last + states).max(last + delay);
+
+        let mut out = String::new();
+        Self::module(
+            &mut out,
+            &interface.name,
+            event.name.as_deref(),
+            (txns, delay, cycles),
+            (&inputs, &input_vals),
+            (&outputs, &expected),
+        )
+        .unwrap();
+        Ok(out)
+    }
+
+    fn module(
+        f: &mut String,
+        main: &str,
+        go: Option<&str>,
+        (txns, delay, cycles): (usize, u64, u64),
+        (inputs, input_vals): (&[TbPort], &[Vec<Bits>]),
+        (outputs, expected): (&[TbPort], &[Option<Vec<Bits>>]),
+    ) -> std::fmt::Result {
+        writeln!(
+            f,
+            "// Testbench for `{main}' generated by the Filament compiler."
+        )?;
+        writeln!(f, "`timescale 1ns/1ps")?;
+        writeln!(f, "module {main}_tb;")?;
+        writeln!(f, "  localparam int TXNS = {txns};")?;
+        writeln!(f, "  localparam int DELAY = {delay};")?;
+        writeln!(f, "  localparam int CYCLES = {cycles};")?;
+        writeln!(f)?;
+
+        // Signals connected to the design
+        writeln!(f, "  logic clk = 1'b0;")?;
+        writeln!(f, "  logic reset = 1'b1;")?;
+        if let Some(go) = go {
+            writeln!(f, "  logic {go};")?;
+        }
+        for port in inputs.iter().chain(outputs) {
+            writeln!(f, "  {} {};", logic(port.width), port.name)?;
+        }
+        writeln!(f)?;
+
+        let conns = inputs
+            .iter()
+            .chain(outputs)
+            .map(|p| p.name.as_str())
+            .chain(go)
+            .chain(["clk", "reset"])
+            .map(|n| format!("    .{n}({n})"))
+            .join(",\n");
+        writeln!(f, "  {main} dut (\n{conns}\n  );")?;
+        writeln!(f)?;
+
+        // Data for the transactions
+        for port in inputs {
+            writeln!(
+                f,
+                "  {} {}_data [0:TXNS-1];",
+                logic(port.width),
+                port.name
+            )?;
+        }
+        for (port, exp) in outputs.iter().zip(expected) {
+            if let Some(exp) = exp {
+                writeln!(
+                    f,
+                    "  {} {}_expected [0:{}];",
+                    logic(port.width),
+                    port.name,
+                    exp.len() - 1
+                )?;
+            }
+        }
+        writeln!(f, "  int errors = 0;")?;
+        writeln!(f)?;
+        writeln!(f, "  always #5 clk = ~clk;")?;
+        writeln!(f)?;
+
+        writeln!(f, "  initial begin")?;
+        for (port, vals) in inputs.iter().zip(input_vals) {
+            for (i, v) in vals.iter().enumerate() {
+                writeln!(f, "    {}_data[{i}] = {};", port.name, literal(v))?;
+            }
+        }
+        for (port, exp) in outputs.iter().zip(expected) {
+            for (i, v) in exp.iter().flatten().enumerate() {
+                writeln!(
+                    f,
+                    "    {}_expected[{i}] = {};",
+                    port.name,
+                    literal(v)
+                )?;
+            }
+        }
+
+        // Hold the design in reset and idle for a cycle afterwards
+        let clear = |f: &mut String, indent: &str| -> std::fmt::Result {
+            if let Some(go) = go {
+                writeln!(f, "{indent}{go} = 1'b0;")?;
+            }
+            for port in inputs {
+                writeln!(f, "{indent}{} = 'x;", port.name)?;
+            }
+            Ok(())
+        };
+        clear(f, "    ")?;
+        writeln!(f, "    repeat ({RESET_CYCLES}) @(posedge clk);")?;
+        // Inputs change after the clock edge to avoid races with the design
+        writeln!(f, "    #1 reset = 1'b0;")?;
+        writeln!(f, "    @(posedge clk);")?;
+
+        writeln!(f, "    for (int cycle = 0; cycle < CYCLES; cycle++) begin")?;
+        writeln!(f, "      #1;")?;
+        clear(f, "      ")?;
+        writeln!(f, "      for (int txn = 0; txn < TXNS; txn++) begin")?;
+        if let Some(go) = go {
+            writeln!(f, "        if (cycle == txn * DELAY) {go} = 1'b1;")?;
+        }
+        for port in inputs {
+            writeln!(
+                f,
+                "        if ({}) {name} = {name}_data[txn];",
+                port.live(),
+                name = port.name
+            )?;
+        }
+        writeln!(f, "      end")?;
+
+        // Sample the outputs after the inputs have propagated
+        writeln!(f, "      @(negedge clk);")?;
+        writeln!(f, "      for (int txn = 0; txn < TXNS; txn++) begin")?;
+        for (port, exp) in outputs.iter().zip(expected) {
+            let name = &port.name;
+            if exp.is_some() {
+                let idx = format!(
+                    "txn * {} + cycle - txn * DELAY - {}",
+                    port.len(),
+                    port.start
+                );
+                writeln!(
+                    f,
+                    "        if ({} && {name} !== {name}_expected[{idx}]) begin",
+                    port.live()
+                )?;
+                writeln!(
+                    f,
+                    "          $display(\"FAIL: transaction %0d: `{name}' is %0d in cycle %0d but expected %0d\", txn, {name}, cycle, {name}_expected[{idx}]);"
+                )?;
+                writeln!(f, "          errors++;")?;
+                writeln!(f, "        end")?;
+            } else {
+                writeln!(
+                    f,
+                    "        if ({}) $display(\"transaction %0d: `{name}' is %0d in cycle %0d\", txn, {name}, cycle);",
+                    port.live()
+                )?;
+            }
+        }
+        writeln!(f, "      end")?;
+        writeln!(f, "      @(posedge clk);")?;
+        writeln!(f, "    end")?;
+
+        writeln!(
+            f,
+            "    if (errors == 0) $display(\"PASS: %0d transactions in %0d cycles\", TXNS, CYCLES);"
+        )?;
+        writeln!(f, "    else $fatal(1, \"FAIL: %0d mismatches\", errors);")?;
+        writeln!(f, "    $finish;")?;
+        writeln!(f, "  end")?;
+        writeln!(f, "endmodule")
+    }
+}
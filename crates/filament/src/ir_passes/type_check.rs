@@ -1,17 +1,24 @@
-use crate::ir_visitor::{Action, Visitor, VisitorData};
+use crate::{
+    cmdline,
+    ir_visitor::{Action, Construct, Visitor, VisitorData},
+};
 use fil_ir::{self as ir, AddCtx, Ctx};
 use fil_utils::GPosIdx;
 use ir::DisplayCtx;
 use itertools::Itertools;
 
-#[derive(Default)]
 /// Implements the type checking algorithm for Filament.
 /// It does things like:
 /// * Port accesses are in-bounds
 /// * Connections are between ports of same size
 /// * Connected ports have the same bitwidths
+/// * Connected ports have the same numeric interpretation
 /// * Add constraints on existentially quantified parameters
-pub struct TypeCheck;
+pub struct TypeCheck {
+    /// Mismatched widths are fixed by [super::AutoResize] instead of being
+    /// reported.
+    auto_resize: bool,
+}
 
 impl TypeCheck {
     /// Generate constraints to ensure range accesses are within range and well-formed
@@ -68,6 +75,16 @@ impl TypeCheck {
     }
 }
 
+impl Construct for TypeCheck {
+    fn from(opts: &cmdline::Opts, _: &mut ir::Context) -> Self {
+        Self {
+            auto_resize: opts.auto_resize,
+        }
+    }
+
+    fn clear_data(&mut self) {}
+}
+
 impl Visitor for TypeCheck {
     fn name() -> &'static str {
         "type-check"
@@ -111,16 +128,21 @@ impl Visitor for TypeCheck {
         cons.extend(self.port_access(dst, dst_loc, comp));
 
         // Ensure that the bitwidths of the ports are the same
+        // unless they will be resized after monomorphization.
         let src_w = comp.get(src.port).width;
         let dst_w = comp.get(dst.port).width;
-        let reason = comp.add(
-            ir::info::Reason::bundle_width_match(
-                dst_loc, src_loc, dst_w, src_w,
-            )
-            .into(),
-        );
-        let prop = src_w.equal(dst_w, comp);
-        cons.extend(comp.assert(prop, reason));
+        if !self.auto_resize {
+            let signed =
+                matches!(comp.get(src.port).interp, Some(ir::Interp::Signed));
+            let reason = comp.add(
+                ir::info::Reason::bundle_width_match(
+                    dst_loc, src_loc, dst_w, src_w, signed,
+                )
+                .into(),
+            );
+            let prop = src_w.equal(dst_w, comp);
+            cons.extend(comp.assert(prop, reason));
+        }
 
         let one = comp.num(1);
         let s_len = src
@@ -138,6 +160,31 @@ impl Visitor for TypeCheck {
         );
         cons.extend(comp.assert(prop, reason));
 
+        // Ports without an interpretation are raw bits and can be connected
+        // to anything.
+        if let (Some(src_i), Some(dst_i)) = (
+            comp.get(src.port).interp.clone(),
+            comp.get(dst.port).interp.clone(),
+        ) {
+            let prop = match (&src_i, &dst_i) {
+                (
+                    ir::Interp::Fixed { int: si, frac: sf },
+                    ir::Interp::Fixed { int: di, frac: df },
+                ) => {
+                    let int = si.equal(*di, comp);
+                    let frac = sf.equal(*df, comp);
+                    int.and(frac, comp)
+                }
+                (s, d) if s.same_kind(d) => comp.add(ir::Prop::True),
+                _ => comp.add(ir::Prop::False),
+            };
+            let reason = comp.add(
+                ir::info::Reason::interp_match(dst_loc, src_loc, dst_i, src_i)
+                    .into(),
+            );
+            cons.extend(comp.assert(prop, reason));
+        }
+
         Action::AddBefore(cons)
     }
 }
@@ -1,6 +1,7 @@
 pub mod ast_passes;
 pub mod ast_visitor;
 pub mod cmdline;
+pub mod interp;
 pub mod ir_passes;
 pub mod ir_visitor;
 pub mod resolver;
@@ -1,16 +1,24 @@
+use argh::FromArgs;
 use calyx_backend::Backend;
 use calyx_opt::pass_manager::PassManager;
-use fil_gen::GenConfig;
+use calyx_utils::OutputFile;
+use codespan_reporting::diagnostic::Diagnostic;
+use fil_ast as ast;
+use fil_gen::{GenConfig, GenExec};
 use fil_ir as ir;
+use fil_utils::{Diagnostics, MessageFormat};
 use filament::ir_passes::BuildDomination;
 use filament::{ast_pass_pipeline, ir_pass_pipeline, log_pass, log_time};
 use filament::{
     ast_passes as ap, ast_visitor::Visitor as AstVisitor, cmdline,
     ir_passes as ip, ir_visitor::Visitor as IrVisitor, resolver::Resolver,
 };
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::fs;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write as _;
+use std::path::Path;
+use std::{fmt, fs, io};
 
 #[derive(Deserialize, Default, Debug)]
 #[serde(default)]
@@ -20,6 +28,59 @@ pub struct ProvidedBindings {
     r#gen: GenConfig,
     /// Parameters to give to the components
     params: HashMap<String, Vec<u64>>,
+    /// Values of the parameters of the top-level component to compile the
+    /// program with when using `--sweep`
+    sweep: HashMap<String, HashMap<String, SweepValues>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+/// Values of a parameter in a sweep: either a list of values or a range like
+/// `{ start = 8, end = 64, step = 8 }` that does not include `end`.
+enum SweepValues {
+    List(Vec<u64>),
+    Range {
+        start: u64,
+        end: u64,
+        #[serde(default = "SweepValues::default_step")]
+        step: u64,
+    },
+}
+
+impl SweepValues {
+    fn default_step() -> u64 {
+        1
+    }
+
+    /// The values of the parameter
+    fn values(&self, param: &str) -> Result<Vec<u64>, String> {
+        let vals = match self {
+            SweepValues::List(vals) => vals.clone(),
+            SweepValues::Range { step: 0, .. } => {
+                return Err(format!(
+                    "Step of the range for parameter `{param}' must be positive"
+                ));
+            }
+            SweepValues::Range { start, end, step } => {
+                (*start..*end).step_by(*step as usize).collect()
+            }
+        };
+        if vals.is_empty() {
+            return Err(format!("No values provided for parameter `{param}'"));
+        }
+        Ok(vals)
+    }
+}
+
+#[derive(Serialize)]
+/// Result of compiling one point of a sweep
+struct SweepPoint {
+    /// Name of the directory containing the outputs
+    name: String,
+    params: BTreeMap<String, u64>,
+    ok: bool,
+    /// Messages of the errors reported for the point
+    errors: Vec<String>,
 }
 
 /// Helper function to add IR pass names to the collection
@@ -48,6 +109,7 @@ fn collect_known_pass_names() -> Vec<String> {
     add_ir_pass::<ip::BuildDomination>(&mut pass_names);
     add_ir_pass::<ip::TypeCheck>(&mut pass_names);
     add_ir_pass::<ip::IntervalCheck>(&mut pass_names);
+    add_ir_pass::<ip::TerminationCheck>(&mut pass_names);
     add_ir_pass::<ip::PhantomCheck>(&mut pass_names);
     add_ir_pass::<ip::InferAssumes>(&mut pass_names);
     add_ir_pass::<ip::Discharge>(&mut pass_names);
@@ -55,9 +117,11 @@ fn collect_known_pass_names() -> Vec<String> {
     add_ir_pass::<ip::Simplify>(&mut pass_names);
     add_ir_pass::<ip::AssignCheck>(&mut pass_names);
     add_ir_pass::<ip::BundleElim>(&mut pass_names);
+    add_ir_pass::<ip::AutoResize>(&mut pass_names);
 
     // AST pass names
     add_ast_pass::<ap::TopLevel>(&mut pass_names);
+    add_ast_pass::<ap::Records>(&mut pass_names);
 
     pass_names.sort();
     pass_names.dedup();
@@ -91,6 +155,16 @@ fn validate_dump_after_passes(opts: &cmdline::Opts) -> Result<(), String> {
     Ok(())
 }
 
+/// Report an error that is not attached to a source location
+fn report_error(e: impl fmt::Debug + fmt::Display) {
+    match Diagnostics::format() {
+        MessageFormat::Human => eprintln!("Error: {e:?}"),
+        MessageFormat::Json => {
+            Diagnostics::emit(Diagnostic::error().with_message(e.to_string()))
+        }
+    }
+}
+
 // Prints out the interface for main component in the input program.
 fn run(opts: &cmdline::Opts) -> Result<(), u64> {
     // enable tracing
@@ -101,10 +175,20 @@ fn run(opts: &cmdline::Opts) -> Result<(), u64> {
         .filter_level(opts.log_level)
         .target(env_logger::Target::Stderr)
         .init();
+    Diagnostics::set_format(opts.message_format);
 
     // Validate --dump-after pass names
     if let Err(e) = validate_dump_after_passes(opts) {
-        eprintln!("Error: {}", e);
+        report_error(format_args!("{e}"));
+        return Err(1);
+    }
+
+    if opts.report_format == cmdline::ReportFormat::Json
+        && opts.report.contains(&cmdline::Report::Timing)
+    {
+        report_error(format_args!(
+            "--report-format json is only supported by the resources report"
+        ));
         return Err(1);
     }
 
@@ -118,12 +202,16 @@ fn run(opts: &cmdline::Opts) -> Result<(), u64> {
     let mut ns = match Resolver::from(opts).parse_namespace() {
         Ok(ns) => ns,
         Err(e) => {
-            eprintln!("Error: {e:?}");
+            report_error(e);
             return Err(1);
         }
     };
 
-    ast_pass_pipeline! { opts, ns; ap::TopLevel };
+    ast_pass_pipeline! { opts, ns; ap::TopLevel, ap::Records };
+
+    if let Some(dir) = &opts.sweep {
+        return sweep(opts, ns, provided_bindings, dir);
+    }
 
     // Set the parameter bindings for the top-level component
     if let Some(main) = ns.toplevel() {
@@ -133,23 +221,263 @@ fn run(opts: &cmdline::Opts) -> Result<(), u64> {
             .cloned()
             .unwrap_or_default();
     }
+    compile(opts, ns, provided_bindings.r#gen, OutputFile::Stdout)
+}
 
-    // Initialize the generator
-    let mut gen_exec = if ns.requires_gen() {
-        if opts.out_dir.is_none()
-            && matches!(opts.backend, cmdline::Backend::Calyx)
-        {
-            log::warn!(concat!(
-                "Generated calyx program will NOT compile because it depends ",
-                "on generated files. Please provide an output directory using ",
-                "`--out-dir <dir>` to store the generated files."
-            ))
+/// Name of the file that [compile] writes its output to for the given options
+/// or `None` if it does not produce any output.
+fn output_name(opts: &cmdline::Opts, main: &str) -> Option<String> {
+    if opts.dump_interface {
+        Some("interface.json".to_string())
+    } else if !opts.report.is_empty() {
+        match opts.report_format {
+            cmdline::ReportFormat::Human => Some("report.txt".to_string()),
+            cmdline::ReportFormat::Json => Some("report.json".to_string()),
         }
-        Some(ns.init_gen(opts.out_dir.clone(), provided_bindings.r#gen))
-    } else {
+    } else if opts.interpret.is_some() {
+        Some("interpret.json".to_string())
+    } else if opts.testbench.is_some() {
+        Some(format!("{main}_tb.sv"))
+    } else if opts.check {
         None
+    } else {
+        match opts.backend {
+            cmdline::Backend::Verilog | cmdline::Backend::Sv => {
+                Some(format!("{main}.sv"))
+            }
+            cmdline::Backend::Calyx => Some(format!("{main}.futil")),
+        }
+    }
+}
+
+/// Compile the program for every combination of the values in the `sweep`
+/// table of the bindings for the top-level component. The outputs for each
+/// combination are written to a separate directory in `dir` along with a
+/// `summary.json` describing the combinations that failed. The parametric
+/// program is only checked once and then monomorphized for every combination.
+fn sweep(
+    opts: &cmdline::Opts,
+    ns: ast::Namespace,
+    bindings: ProvidedBindings,
+    dir: &Path,
+) -> Result<(), u64> {
+    let Some(idx) = ns.main_idx() else {
+        report_error(format_args!("--sweep requires a top-level component"));
+        return Err(1);
     };
+    let sig = &ns.components[idx].sig;
+    let main = sig.name.to_string();
+    let Some(sweep) = bindings.sweep.get(&main) else {
+        report_error(format_args!(
+            "Bindings do not contain a sweep for the top-level component `{main}'"
+        ));
+        return Err(1);
+    };
+    if let Some(p) = sweep
+        .keys()
+        .find(|p| !sig.params().any(|sp| sp.to_string() == **p))
+    {
+        report_error(format_args!(
+            "Component `{main}' does not have a parameter named `{p}'"
+        ));
+        return Err(1);
+    }
+
+    // The swept parameters must come before the parameters that use their
+    // default values because bindings are positional.
+    let mut params = vec![];
+    let mut unswept: Option<String> = None;
+    for pb in &sig.params {
+        let name = pb.name().to_string();
+        match (sweep.get(&name), &unswept) {
+            (Some(vals), None) => match vals.values(&name) {
+                Ok(vals) => params.push((name, vals)),
+                Err(e) => {
+                    report_error(format_args!("{e}"));
+                    return Err(1);
+                }
+            },
+            (Some(_), Some(prev)) => {
+                report_error(format_args!(
+                    "Cannot sweep parameter `{name}' without sweeping the earlier parameter `{prev}'"
+                ));
+                return Err(1);
+            }
+            (None, _) if pb.default.is_none() => {
+                report_error(format_args!(
+                    "Parameter `{name}' of `{main}' does not have a default value and must be swept"
+                ));
+                return Err(1);
+            }
+            (None, _) => unswept = unswept.or(Some(name)),
+        }
+    }
+
+    if let Err(e) = fs::create_dir_all(dir) {
+        report_error(format_args!("Failed to create {}: {e}", dir.display()));
+        return Err(1);
+    }
+    let points = params
+        .iter()
+        .map(|(_, vals)| vals.iter().copied())
+        .multi_cartesian_product()
+        .collect_vec();
+
+    // The parametric program is checked once for all the points
+    let mut checked = ns.clone();
+    checked.bindings = points.first().cloned().unwrap_or_default();
+    let mut ir = check(opts, checked)?;
+
+    let mut results = vec![];
+    let mut errors = 0;
+    for point in points {
+        let name = params
+            .iter()
+            .zip(&point)
+            .map(|((p, _), v)| format!("{p}{v}"))
+            .join("_");
+        let point_dir = dir.join(&name);
+        if let Err(e) = fs::create_dir_all(&point_dir) {
+            report_error(format_args!(
+                "Failed to create {}: {e}",
+                point_dir.display()
+            ));
+            return Err(1);
+        }
+
+        // Generated files are stored along with the output
+        let mut point_opts = opts.clone();
+        point_opts.out_dir = Some(point_dir.clone());
+        let out = output_name(opts, &main)
+            .map_or(OutputFile::Null, |f| OutputFile::File(point_dir.join(f)));
+        let (res, diags) = Diagnostics::capture(|| {
+            let gen_exec = init_gen(&point_opts, &ns, bindings.r#gen.clone())?;
+            if let Some(ep) = &mut ir.entrypoint {
+                ep.bindings = point_bindings(sig, &point)?;
+            }
+            lower(&point_opts, &ir, gen_exec, out)
+        });
+
+        // Report the errors for the point as they would be without a sweep
+        if !diags.is_empty() && Diagnostics::format() == MessageFormat::Human {
+            eprintln!("Errors for {name}:");
+        }
+        let messages = diags
+            .iter()
+            .map(|d| match d.labels.first() {
+                Some(l) if !l.message.is_empty() => {
+                    format!("{}: {}", d.message, l.message)
+                }
+                _ => d.message.clone(),
+            })
+            .collect_vec();
+        for diag in diags {
+            Diagnostics::emit(diag);
+        }
+        if let Err(n) = res {
+            errors += n;
+            println!("{name}: failed: {}", messages.join("; "));
+        } else {
+            println!("{name}: ok");
+        }
+        results.push(SweepPoint {
+            name,
+            params: params.iter().map(|(p, _)| p.clone()).zip(point).collect(),
+            ok: res.is_ok(),
+            errors: messages,
+        });
+    }
+
+    let summary = dir.join("summary.json");
+    if let Err(e) =
+        fs::write(&summary, serde_json::to_string_pretty(&results).unwrap())
+    {
+        report_error(format_args!(
+            "Failed to write {}: {e}",
+            summary.display()
+        ));
+        return Err(1);
+    }
+    if errors > 0 { Err(errors) } else { Ok(()) }
+}
+
+/// Values of all the parameters of the swept component for a point that binds
+/// a prefix of them. Like the bindings of the entrypoint, the remaining
+/// parameters use their default values resolved against the point.
+fn point_bindings(
+    sig: &ast::Signature,
+    point: &[u64],
+) -> Result<Vec<u64>, u64> {
+    let binding = ast::Binding::new(
+        sig.params()
+            .map(|p| *p.inner())
+            .zip(point.iter().map(|v| ast::Expr::Concrete(*v))),
+    );
+    sig.params
+        .iter()
+        .enumerate()
+        .map(|(i, pb)| match point.get(i) {
+            Some(v) => Ok(*v),
+            None => match pb.default.clone().map(|d| d.resolve(&binding)) {
+                Some(ast::Expr::Concrete(v)) => Ok(v),
+                _ => {
+                    report_error(format_args!(
+                        "Default value of parameter `{}' of `{}' is not concrete",
+                        pb.name(),
+                        sig.name
+                    ));
+                    Err(1)
+                }
+            },
+        })
+        .collect()
+}
+
+/// Check and compile the program with the parameters bound in the namespace
+/// and write the output to `out`.
+fn compile(
+    opts: &cmdline::Opts,
+    ns: ast::Namespace,
+    gen_config: GenConfig,
+    out: OutputFile,
+) -> Result<(), u64> {
+    let gen_exec = init_gen(opts, &ns, gen_config)?;
+    let ir = check(opts, ns)?;
+    lower(opts, &ir, gen_exec, out)
+}
 
+/// Initialize the generator if the program uses generated components
+fn init_gen(
+    opts: &cmdline::Opts,
+    ns: &ast::Namespace,
+    gen_config: GenConfig,
+) -> Result<Option<GenExec>, u64> {
+    if !ns.requires_gen() {
+        return Ok(None);
+    }
+    if opts.out_dir.is_none() && matches!(opts.backend, cmdline::Backend::Calyx)
+    {
+        log::warn!(concat!(
+            "Generated calyx program will NOT compile because it depends ",
+            "on generated files. Please provide an output directory using ",
+            "`--out-dir <dir>` to store the generated files."
+        ))
+    }
+    let mut gen_exec = ns.init_gen(opts.out_dir.clone(), gen_config)?;
+    // Outputs are only cached when they are stored in a user directory
+    let cache = opts
+        .gen_cache
+        .clone()
+        .or_else(|| opts.out_dir.as_ref().map(|dir| dir.join(".gen-cache")));
+    gen_exec.cache_dir(cache.filter(|_| !opts.no_gen_cache));
+    if let Some(jobs) = opts.gen_jobs {
+        gen_exec.jobs(jobs);
+    }
+    Ok(Some(gen_exec))
+}
+
+/// Convert the program to the IR and check the parametric program
+fn check(opts: &cmdline::Opts, ns: ast::Namespace) -> Result<ir::Context, u64> {
     // Transform AST to IR
     let mut ir = log_pass! { opts; ir::transform(ns)?, "astconv" };
     ir_pass_pipeline! {opts, ir;
@@ -157,6 +485,7 @@ fn run(opts: &cmdline::Opts) -> Result<(), u64> {
         ip::BuildDomination,
         ip::TypeCheck,
         ip::IntervalCheck,
+        ip::TerminationCheck,
         ip::PhantomCheck,
         ip::InferAssumes
     }
@@ -166,7 +495,25 @@ fn run(opts: &cmdline::Opts) -> Result<(), u64> {
     ir_pass_pipeline! { opts, ir;
         BuildDomination
     };
-    ir = log_pass! { opts; ip::Monomorphize::transform(&ir, &mut gen_exec), "monomorphize"};
+    Ok(ir)
+}
+
+/// Monomorphize the checked program using the bindings of its entrypoint and
+/// write the output to `out`.
+fn lower(
+    opts: &cmdline::Opts,
+    ir: &ir::Context,
+    mut gen_exec: Option<GenExec>,
+    out: OutputFile,
+) -> Result<(), u64> {
+    ip::Monomorphize::check_bindings(ir)?;
+    let keep: &[&str] = if opts.auto_resize {
+        &ip::AutoResize::PRIMITIVES
+    } else {
+        &[]
+    };
+    let mut ir = log_pass! { opts; ip::Monomorphize::transform(ir, &mut gen_exec, opts.recursion_limit, keep)?, "monomorphize"};
+
     ir_pass_pipeline! { opts, ir;
         ip::FSMAttributes,
         ip::Simplify,
@@ -174,12 +521,16 @@ fn run(opts: &cmdline::Opts) -> Result<(), u64> {
         ip::BundleElim,
         ip::AssignCheck
     }
+    if opts.auto_resize {
+        ir_pass_pipeline! { opts, ir; ip::AutoResize }
+    }
     // type check again before lowering
     ir_pass_pipeline! {opts, ir;
         ip::Assumptions,
         ip::BuildDomination,
         ip::TypeCheck,
         ip::IntervalCheck,
+        ip::TerminationCheck,
         ip::PhantomCheck,
         ip::InferAssumes
     }
@@ -189,24 +540,87 @@ fn run(opts: &cmdline::Opts) -> Result<(), u64> {
 
     // Return early if we're asked to dump the interface
     if opts.dump_interface {
-        ip::DumpInterface::print(&ir);
+        ip::DumpInterface::print(&ir, &mut out.get_write()).unwrap();
+        return Ok(());
+    }
+
+    // Print the requested reports instead of compiling the program
+    if !opts.report.is_empty() {
+        let mut w = out.get_write();
+        for report in &opts.report {
+            let report = match report {
+                cmdline::Report::Timing => ip::Report::timing(&ir),
+                cmdline::Report::Resources => match opts.report_format {
+                    cmdline::ReportFormat::Human => ip::Report::resources(&ir),
+                    cmdline::ReportFormat::Json => {
+                        ip::Report::resources_json(&ir)
+                    }
+                },
+            };
+            w.write_all(report.as_bytes()).unwrap();
+        }
         return Ok(());
     }
 
+    // Simulate the program instead of compiling it
+    if let Some(data) = &opts.interpret {
+        let res = filament::interp::Harness::new(&ir)
+            .and_then(|mut harness| harness.run(data));
+        return match res {
+            Ok(res) => {
+                writeln!(out.get_write(), "{res}").unwrap();
+                Ok(())
+            }
+            Err(e) => {
+                report_error(e);
+                Err(1)
+            }
+        };
+    }
+
+    // Generate a testbench instead of compiling the program
+    if let Some(data) = &opts.testbench {
+        return match ip::Testbench::emit(&ir, data) {
+            Ok(tb) => {
+                out.get_write().write_all(tb.as_bytes()).unwrap();
+                Ok(())
+            }
+            Err(e) => {
+                report_error(e);
+                Err(1)
+            }
+        };
+    }
+
     // Return if we are only checking
     if opts.check {
         return Ok(());
     }
-    let calyx =
-        log_time!(ip::Compile::compile(ir, !opts.no_preserve_names), "compile");
+
     match opts.backend {
         cmdline::Backend::Verilog => {
-            gen_verilog(calyx).unwrap();
+            let calyx = log_time!(
+                ip::Compile::compile(ir, !opts.no_preserve_names),
+                "compile"
+            );
+            gen_verilog(calyx, out).unwrap();
         }
         cmdline::Backend::Calyx => {
-            let out = &mut std::io::stdout();
+            let calyx = log_time!(
+                ip::Compile::compile(ir, !opts.no_preserve_names),
+                "compile"
+            );
+            let out = &mut out.get_write();
             calyx_ir::Printer::write_context(&calyx, false, out).unwrap();
         }
+        cmdline::Backend::Sv => {
+            // Emit SystemVerilog directly without going through calyx
+            let out = &mut out.get_write();
+            log_time!(
+                ip::EmitSv::emit(ir, !opts.no_preserve_names, out).unwrap(),
+                "compile"
+            );
+        }
     }
 
     // Drop the generator executor after the execution finishes to ensure that
@@ -215,7 +629,10 @@ fn run(opts: &cmdline::Opts) -> Result<(), u64> {
     Ok(())
 }
 
-fn gen_verilog(mut ctx: calyx_ir::Context) -> Result<(), calyx_utils::Error> {
+fn gen_verilog(
+    mut ctx: calyx_ir::Context,
+    out: OutputFile,
+) -> Result<(), calyx_utils::Error> {
     let pm = PassManager::default_passes()?;
     let backend_conf = calyx_ir::BackendConf {
         synthesis_mode: false,
@@ -231,13 +648,103 @@ fn gen_verilog(mut ctx: calyx_ir::Context) -> Result<(), calyx_utils::Error> {
         false,
     )?;
     let backend = calyx_backend::VerilogBackend;
-    backend.run(ctx, calyx_utils::OutputFile::Stdout)
+    backend.run(ctx, out)
+}
+
+/// Format the files in `opts` and return the number of files that failed to
+/// format or, with `--check`, are not formatted.
+fn fmt(opts: &cmdline::FmtOpts) -> Result<(), u64> {
+    let inputs = if opts.files.is_empty() {
+        vec![None]
+    } else {
+        opts.files.iter().map(Some).collect()
+    };
+    let mut errors = 0;
+    for path in inputs {
+        let (name, src) = match path {
+            Some(path) => {
+                (path.to_string_lossy().to_string(), fs::read_to_string(path))
+            }
+            None => ("<stdin>".to_string(), io::read_to_string(io::stdin())),
+        };
+        let src = match src {
+            Ok(src) => src,
+            Err(e) => {
+                eprintln!("Error: Failed to read {name}: {e}");
+                errors += 1;
+                continue;
+            }
+        };
+        let out = match ast::Formatter::format(&name, &src) {
+            Ok(out) => out,
+            Err(e) => {
+                eprintln!("Error: {e:?}");
+                errors += 1;
+                continue;
+            }
+        };
+        if opts.check {
+            if out != src {
+                println!("{name} is not formatted");
+                errors += 1;
+            }
+        } else if let (true, Some(path)) = (opts.write, path) {
+            if out != src {
+                if let Err(e) = fs::write(path, out) {
+                    eprintln!("Error: Failed to write {name}: {e}");
+                    errors += 1;
+                }
+            }
+        } else {
+            print!("{out}");
+        }
+    }
+    if errors > 0 { Err(errors) } else { Ok(()) }
+}
+
+/// Parse the arguments of a subcommand or exit after printing the help or
+/// error message.
+fn subcommand<T: FromArgs>(args: &[String]) -> T {
+    let rest = args[2..].iter().map(String::as_str).collect::<Vec<_>>();
+    T::from_args(&[&args[0], &args[1]], &rest).unwrap_or_else(|early| {
+        match early.status {
+            Ok(()) => println!("{}", early.output),
+            Err(()) => eprintln!("{}", early.output),
+        }
+        std::process::exit(early.status.map_or(1, |_| 0))
+    })
 }
 
 fn main() {
-    let opts: cmdline::Opts = argh::from_env();
+    // `filament fmt` is handled separately because it does not compile the
+    // program.
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.get(1).is_some_and(|a| a == "fmt") {
+        let opts: cmdline::FmtOpts = subcommand(&args);
+        if fmt(&opts).is_err() {
+            std::process::exit(1)
+        }
+        return;
+    }
+
+    // `filament testbench <file>` is a shorthand for
+    // `filament <file> --testbench <file>.data`
+    let opts: cmdline::Opts = if args.get(1).is_some_and(|a| a == "testbench") {
+        let mut opts: cmdline::Opts = subcommand(&args);
+        if opts.testbench.is_none() {
+            let mut data = opts.input.clone().into_os_string();
+            data.push(".data");
+            opts.testbench = Some(data.into());
+        }
+        opts
+    } else {
+        argh::from_env()
+    };
     match run(&opts) {
         Ok(_) => (),
+        Err(_) if opts.message_format == MessageFormat::Json => {
+            std::process::exit(1)
+        }
         Err(err) => {
             eprintln!("Compilation failed with {err} errors.");
             if !opts.show_models {
@@ -144,6 +144,8 @@ impl Resolver {
             let base = Self::parent(&path);
             imp.components.append(&mut ns.components);
             ns.components = imp.components;
+            ns.types.append(&mut imp.types);
+            ns.functions.append(&mut imp.functions);
             ns.externs.extend(
                 imp.externs
                     .into_iter()
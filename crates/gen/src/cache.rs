@@ -0,0 +1,217 @@
+use crate::{Instance, Module, Tool, ToolOutput};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A cached tool invocation
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// Name of the generated module
+    name: String,
+    /// Path of the file that defines the module relative to the output
+    /// directory
+    file: PathBuf,
+    /// Paths of the other generated files relative to the output directory
+    files: Vec<PathBuf>,
+    /// Bindings for the existential parameters produced by the tool
+    exist_params: HashMap<String, String>,
+    /// Filament signature of the module reported by the tool
+    signature: Option<String>,
+}
+
+/// On-disk cache of the modules generated by tools.
+///
+/// An invocation is identified by a hash of the tool's name, binary, and
+/// protocol, the definition of the module, and the values of the parameters
+/// and globals. Only the file at the path of the tool is hashed: if it is a
+/// wrapper script, changes to the programs it runs are not detected and the
+/// cache must be cleared after updating them.
+/// Each entry is stored in a directory named by the hash that contains copies
+/// of the generated files, at their paths relative to the output directory,
+/// along with the parsed outputs of the tool in `entry.toml`. Outputs with
+/// files outside of the output directory are not cached.
+pub(crate) struct GenCache {
+    dir: PathBuf,
+    /// Hashes of the tool binaries
+    binaries: HashMap<String, String>,
+}
+
+impl GenCache {
+    pub fn new(dir: PathBuf) -> Self {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            log::warn!(
+                "Failed to create generator cache directory {}: {e}",
+                dir.display()
+            );
+        }
+        log::info!("Generator cache directory: {}", dir.display());
+        Self {
+            dir,
+            binaries: HashMap::default(),
+        }
+    }
+
+    /// The key identifying the invocation of the tool
+    pub fn key(
+        &mut self,
+        tool: &Tool,
+        module: &Module,
+        instance: &Instance,
+    ) -> String {
+        // Changes to the tool can change the generated module
+        let binary =
+            self.binaries.entry(tool.path.clone()).or_insert_with(|| {
+                match fs::read(&tool.path) {
+                    Ok(bytes) => Self::hash([bytes.as_slice()]),
+                    Err(e) => {
+                        log::warn!(
+                            "Failed to read tool binary `{}': {e}",
+                            tool.path
+                        );
+                        String::new()
+                    }
+                }
+            });
+        let globals = tool
+            .globals
+            .iter()
+            .sorted()
+            .map(|(k, v)| format!("{k}={v}"))
+            .join("\n");
+        let outputs = module
+            .outputs
+            .iter()
+            .sorted()
+            .map(|(k, v)| format!("{k}={v}"))
+            .join("\n");
+        let protocol = format!("{:?}", tool.protocol);
+        Self::hash([
+            tool.name.as_bytes(),
+            binary.as_bytes(),
+            instance.name.as_bytes(),
+            module.name_format.as_bytes(),
+            module.cli_format.as_bytes(),
+            outputs.as_bytes(),
+            protocol.as_bytes(),
+            instance.parameters.join("\n").as_bytes(),
+            globals.as_bytes(),
+        ])
+    }
+
+    fn hash<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> String {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            // Separate the parts so that their boundaries are part of the hash
+            hasher.update(part.len().to_le_bytes());
+            hasher.update(part);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .join("")
+    }
+
+    /// Output of a cached invocation. The generated files are copied into
+    /// the output directory.
+    pub fn get(&self, key: &str, out_dir: &Path) -> Option<ToolOutput> {
+        let dir = self.dir.join(key);
+        let entry = fs::read_to_string(dir.join("entry.toml")).ok()?;
+        let Entry {
+            name,
+            file,
+            files,
+            exist_params,
+            signature,
+        } = match toml::from_str(&entry) {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!("Ignoring malformed cache entry `{key}': {e}");
+                return None;
+            }
+        };
+        let copy = |f: &PathBuf| -> Option<PathBuf> {
+            if !Self::is_relative(f) {
+                log::warn!(
+                    "Ignoring cache entry `{key}': `{}' is not relative to the output directory",
+                    f.display()
+                );
+                return None;
+            }
+            let dst = out_dir.join(f);
+            let res = fs::create_dir_all(dst.parent().unwrap_or(out_dir))
+                .and_then(|_| fs::copy(dir.join(f), &dst));
+            if let Err(e) = res {
+                log::warn!("Ignoring cache entry `{key}': {e}");
+                return None;
+            }
+            Some(dst)
+        };
+        Some(ToolOutput {
+            name,
+            file: copy(&file)?,
+            files: files.iter().map(copy).collect::<Option<_>>()?,
+            exist_params,
+            signature,
+        })
+    }
+
+    /// Whether the path stays within the directory it is relative to
+    fn is_relative(path: &Path) -> bool {
+        path.components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+    }
+
+    /// Record the output of an invocation whose files are in `out_dir`
+    pub fn insert(&self, key: &str, out_dir: &Path, out: &ToolOutput) {
+        let dir = self.dir.join(key);
+        let files = std::iter::once(&out.file)
+            .chain(&out.files)
+            .map(|f| {
+                f.strip_prefix(out_dir)
+                    .ok()
+                    .filter(|rel| Self::is_relative(rel))
+                    .map(|rel| (f, rel.to_path_buf()))
+            })
+            .collect::<Option<Vec<_>>>();
+        let Some(files) = files else {
+            log::info!(
+                "Not caching output of `{}': files are outside of {}",
+                out.name,
+                out_dir.display()
+            );
+            return;
+        };
+        let entry = Entry {
+            name: out.name.clone(),
+            file: files[0].1.clone(),
+            files: files[1..].iter().map(|(_, rel)| rel.clone()).collect(),
+            exist_params: out.exist_params.clone(),
+            signature: out.signature.clone(),
+        };
+        // The generated files are written first so that the entry is only
+        // visible once it is complete.
+        let res = files
+            .iter()
+            .try_for_each(|(f, rel)| {
+                let dst = dir.join(rel);
+                fs::create_dir_all(dst.parent().unwrap_or(&dir))
+                    .and_then(|_| fs::copy(f, dst))
+                    .map(|_| ())
+            })
+            .and_then(|_| {
+                fs::write(
+                    dir.join("entry.toml"),
+                    toml::to_string(&entry).unwrap(),
+                )
+            });
+        if let Err(e) = res {
+            log::warn!("Failed to cache output of `{}': {e}", out.name);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::fmt::Display;
+
+/// An error that occurred while registering a tool or generating a module
+/// using it.
+#[derive(Clone, Debug)]
+pub struct GenError {
+    /// Name of the tool that caused the error
+    pub tool: Option<String>,
+    /// Description of the error
+    pub msg: String,
+    /// Standard error of the failed tool invocation
+    pub stderr: Option<String>,
+}
+
+impl GenError {
+    /// An error that is not specific to a tool
+    pub fn new<S: ToString>(msg: S) -> Self {
+        Self {
+            tool: None,
+            msg: msg.to_string(),
+            stderr: None,
+        }
+    }
+
+    /// An error caused by the tool with the given name
+    pub fn tool<T: ToString, S: ToString>(tool: T, msg: S) -> Self {
+        Self {
+            tool: Some(tool.to_string()),
+            msg: msg.to_string(),
+            stderr: None,
+        }
+    }
+
+    /// Attach the standard error of the tool invocation unless it is empty
+    pub fn with_stderr(mut self, stderr: &[u8]) -> Self {
+        let stderr = String::from_utf8_lossy(stderr).trim_end().to_string();
+        self.stderr = (!stderr.is_empty()).then_some(stderr);
+        self
+    }
+}
+
+impl Display for GenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(tool) = &self.tool {
+            write!(f, "[tool `{tool}'] ")?;
+        }
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for GenError {}
+
+/// Result of an operation in the `gen` framework
+pub type GenResult<T> = Result<T, GenError>;
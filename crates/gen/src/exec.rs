@@ -1,10 +1,18 @@
-use crate::{GenConfig, Instance, Tool, ToolOutput};
+use crate::{
+    GenConfig, GenError, GenResult, Instance, Protocol, Tool, ToolOutput,
+    ToolResult, cache::GenCache,
+};
 use itertools::Itertools;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
-    process::Command,
+    process::{Command, ExitStatus, Output},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
 };
 use tempfile as tmp;
 
@@ -17,24 +25,31 @@ enum OutDir {
 
 impl OutDir {
     /// Creates a new, temporary directory to store generated files.
-    fn tmp() -> Self {
-        let tmp_dir = tmp::tempdir().unwrap();
+    fn tmp() -> GenResult<Self> {
+        let tmp_dir = tmp::tempdir().map_err(|e| {
+            GenError::new(format!("Failed to create output directory: {e}"))
+        })?;
         log::info!("Generator output directory: {}", tmp_dir.path().display());
-        Self::Tmp(tmp_dir)
+        Ok(Self::Tmp(tmp_dir))
     }
 
-    fn user(path: PathBuf) -> Self {
+    fn user(path: PathBuf) -> GenResult<Self> {
         // If the path doesn't exist, create it
         if !path.exists() {
             log::info!("Creating output directory: {}", path.display());
-            fs::create_dir_all(&path).unwrap();
+            fs::create_dir_all(&path).map_err(|e| {
+                GenError::new(format!(
+                    "Failed to create output directory `{}': {e}",
+                    path.display()
+                ))
+            })?;
         } else {
             log::info!("Generator output directory: {}", path.display());
         }
-        Self::User(path)
+        Ok(Self::User(path))
     }
 
-    fn opt(path: Option<PathBuf>) -> Self {
+    fn opt(path: Option<PathBuf>) -> GenResult<Self> {
         match path {
             Some(p) => Self::user(p),
             None => Self::tmp(),
@@ -61,6 +76,9 @@ pub struct GenExec {
         HashMap</*instance=*/ Instance, ToolOutput>,
     >,
 
+    /// Instances whose tool invocations failed while prefetching
+    failed: HashMap<(/*tool=*/ String, /*instance=*/ Instance), GenError>,
+
     /// Directory to store all the generated files
     output_dir: OutDir,
 
@@ -69,6 +87,12 @@ pub struct GenExec {
 
     /// Dry-run instead of executing commands
     dry_run: bool,
+
+    /// Outputs of previous runs
+    cache: Option<GenCache>,
+
+    /// Maximum number of tool invocations run concurrently
+    jobs: usize,
 }
 
 impl GenExec {
@@ -76,14 +100,28 @@ impl GenExec {
         dry_run: bool,
         out_dir: Option<PathBuf>,
         config: GenConfig,
-    ) -> Self {
-        GenExec {
+    ) -> GenResult<Self> {
+        Ok(GenExec {
             tools: HashMap::default(),
             generated: HashMap::default(),
-            output_dir: OutDir::opt(out_dir),
+            failed: HashMap::default(),
+            output_dir: OutDir::opt(out_dir)?,
             dry_run,
             config,
-        }
+            cache: None,
+            jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+        })
+    }
+
+    /// Run at most `jobs` tool invocations concurrently
+    pub fn jobs(&mut self, jobs: usize) {
+        self.jobs = jobs.max(1);
+    }
+
+    /// Reuse the outputs of tool invocations stored in the directory and store
+    /// new ones in it
+    pub fn cache_dir(&mut self, dir: Option<PathBuf>) {
+        self.cache = dir.map(GenCache::new);
     }
 
     /// Should we dry run instead of executing commands?
@@ -97,11 +135,25 @@ impl GenExec {
     }
 
     /// Register a tool by reading its definition from a file
-    pub fn register_tool_from_file(&mut self, path: PathBuf) -> &Tool {
+    pub fn register_tool_from_file(
+        &mut self,
+        path: PathBuf,
+    ) -> GenResult<&Tool> {
         log::info!("Registering tool from file: `{}`", path.display());
 
-        let desc = fs::read_to_string(path.clone()).unwrap();
-        let mut tool: Tool = toml::from_str(&desc).unwrap();
+        let desc = fs::read_to_string(&path).map_err(|e| {
+            GenError::new(format!(
+                "Failed to read tool definition `{}': {e}",
+                path.display()
+            ))
+        })?;
+        let mut tool: Tool = toml::from_str(&desc).map_err(|e| {
+            GenError::new(format!(
+                "Malformed tool definition `{}': {}",
+                path.display(),
+                e.message()
+            ))
+        })?;
         // Replace the globals with the ones from the config file if it exists
         if let Some(globals) = self.config.remove(&tool.name) {
             tool.globals = globals;
@@ -109,36 +161,48 @@ impl GenExec {
         // Get the absolute path to the binary if it is relative
         let tool_path = PathBuf::from(&tool.path);
         if !tool_path.is_absolute() {
-            tool.path = path
-                .parent()
-                .unwrap()
-                .join(tool_path)
-                .to_string_lossy()
-                .to_string();
+            let Some(dir) = path.parent() else {
+                return Err(GenError::tool(
+                    &tool.name,
+                    format!(
+                        "Cannot resolve `{}' relative to `{}'",
+                        tool.path,
+                        path.display()
+                    ),
+                ));
+            };
+            tool.path = dir.join(tool_path).to_string_lossy().to_string();
         }
         let name = tool.name.clone();
-        self.register_tool(tool);
-        self.tools.get(&name).unwrap()
+        self.register_tool(tool)?;
+        Ok(self.tools.get(&name).unwrap())
     }
 
     /// Register a new tool
-    pub fn register_tool(&mut self, tool: Tool) {
-        assert!(
-            !self.has_tool(&tool.name),
-            "Tool already registered: `{}`",
-            tool.name
-        );
-        tool.validate();
+    pub fn register_tool(&mut self, tool: Tool) -> GenResult<()> {
+        if self.has_tool(&tool.name) {
+            return Err(GenError::tool(
+                &tool.name,
+                "Tool is already registered",
+            ));
+        }
+        tool.validate()?;
         log::info!("Registering tool: `{}`", tool.name);
         self.tools.insert(tool.name.clone(), tool);
+        Ok(())
     }
 
     /// Generate a new file with the given name in the output directory
-    pub fn gen_file(&mut self, name: String) -> PathBuf {
+    pub fn gen_file(&mut self, name: String) -> GenResult<PathBuf> {
         let path = self.output_dir.path().join(name);
-        assert!(!path.exists(), "File already exists: `{}`", path.display());
+        if path.exists() {
+            return Err(GenError::new(format!(
+                "File already exists: `{}'",
+                path.display()
+            )));
+        }
         log::info!("Generating file: `{}`", path.display());
-        path
+        Ok(path)
     }
 
     /// Execute a particular manifest to generate instances
@@ -146,31 +210,132 @@ impl GenExec {
         &mut self,
         tool: &str,
         instance: &Instance,
-    ) -> ToolOutput {
-        assert!(self.has_tool(tool), "Unknown tool: `{tool}");
+    ) -> GenResult<ToolOutput> {
+        match self.prepare(tool, instance)? {
+            Prepared::Done(out) => Ok(out),
+            Prepared::Run(job) => {
+                let output = job.run()?;
+                self.finish(job, output)
+            }
+        }
+    }
+
+    /// Generate the instances by running up to [Self::jobs] tool invocations
+    /// concurrently. Later calls to [Self::gen_instance] for these instances
+    /// return the generated outputs or the errors that occurred while
+    /// generating them.
+    pub fn prefetch(
+        &mut self,
+        instances: impl IntoIterator<Item = (String, Instance)>,
+    ) {
+        let mut files = HashSet::new();
+        let jobs = instances
+            .into_iter()
+            .unique()
+            // Errors in preparing an invocation are reported by the later
+            // calls to [Self::gen_instance]
+            .filter_map(|(tool, instance)| {
+                match self.prepare(&tool, &instance) {
+                    Ok(Prepared::Run(job)) => Some(job),
+                    Ok(Prepared::Done(_)) | Err(_) => None,
+                }
+            })
+            // Instances that generate the same file are generated one by one
+            .filter(|job| files.insert(job.out_file.clone()))
+            .collect_vec();
+        if jobs.is_empty() {
+            return;
+        }
+        let workers = self.jobs.min(jobs.len());
+        log::info!(
+            "Running {} tool invocations using {workers} jobs",
+            jobs.len()
+        );
+
+        let next = AtomicUsize::new(0);
+        let outputs = Mutex::new(Vec::with_capacity(jobs.len()));
+        thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(job) = jobs.get(i) else {
+                            break;
+                        };
+                        let output = job.run();
+                        outputs.lock().unwrap().push((i, output));
+                    }
+                });
+            }
+        });
+
+        let outputs = outputs
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .sorted_by_key(|(i, _)| *i);
+        for (job, (_, output)) in jobs.into_iter().zip(outputs) {
+            let key = (job.tool.clone(), job.instance.clone());
+            if let Err(e) = output.and_then(|output| self.finish(job, output)) {
+                self.failed.insert(key, e);
+            }
+        }
+    }
+
+    /// Compute the invocation of a tool that generates the instance unless
+    /// its output is already available
+    fn prepare(
+        &mut self,
+        tool: &str,
+        instance: &Instance,
+    ) -> GenResult<Prepared> {
+        let Some(tool) = self.tools.get(tool).cloned() else {
+            return Err(GenError::tool(tool, "Tool is not registered"));
+        };
 
-        if let Some(output) = self.generated.get(tool) {
+        if let Some(output) = self.generated.get(&tool.name) {
             if let Some(out) = output.get(instance) {
                 log::info!("Using cached output for `{}`", instance);
-                return out.clone();
+                return Ok(Prepared::Done(out.clone()));
             }
         }
+        if let Some(e) = self.failed.get(&(tool.name.clone(), instance.clone()))
+        {
+            return Err(e.clone());
+        }
 
-        let tool = self.tools.get(tool).unwrap().clone();
         let Some(module) = tool.get_module(&instance.name) else {
-            unreachable!(
-                "Tool `{}' does not define module `{}`",
-                tool.name, instance.name
-            );
+            return Err(GenError::tool(
+                &tool.name,
+                format!("Tool does not define module `{}'", instance.name),
+            ));
         };
 
-        assert!(
-            module.parameters.len() == instance.parameters.len(),
-            "Module `{}' has {} parameters, but the manifest specifies {} parameters",
-            instance.name,
-            module.parameters.len(),
-            instance.parameters.len()
-        );
+        if module.parameters.len() != instance.parameters.len() {
+            return Err(GenError::tool(
+                &tool.name,
+                format!(
+                    "Module `{}' has {} parameters, but {} were provided",
+                    instance.name,
+                    module.parameters.len(),
+                    instance.parameters.len()
+                ),
+            ));
+        }
+
+        let key = self.cache.as_mut().map(|c| c.key(&tool, module, instance));
+        if let (Some(cache), Some(key), false) =
+            (&self.cache, &key, self.dry_run)
+        {
+            if let Some(out) = cache.get(key, self.output_dir.path()) {
+                log::info!("Using output of a previous run for `{}`", instance);
+                self.generated
+                    .entry(tool.name.clone())
+                    .or_default()
+                    .insert(instance.clone(), out.clone());
+                return Ok(Prepared::Done(out));
+            }
+        }
 
         let mut binding = module
             .parameters
@@ -180,71 +345,309 @@ impl GenExec {
             .chain(tool.globals.clone())
             .collect_vec();
 
-        let gen_name = module.name(&binding).unwrap();
+        let gen_name = module.name(&binding).map_err(|e| {
+            GenError::tool(
+                &tool.name,
+                format!("Invalid name for `{instance}': {e}"),
+            )
+        })?;
         binding.push(("NAME_FORMAT".to_string(), gen_name.clone()));
 
-        assert!(
-            tool.requires_out_file.is_some() && tool.requires_out_file.unwrap(),
-            "tool does not support $OUT_FILE"
-        );
-        let out_file = self.gen_file(format!("{}.v", gen_name));
+        // Files left in the output directory by a previous run are stale
+        let stale = self.output_dir.path().join(format!("{gen_name}.v"));
+        if let Some((other, _)) = self
+            .generated
+            .values()
+            .flatten()
+            .find(|(_, o)| o.file == stale)
+        {
+            return Err(GenError::tool(
+                &tool.name,
+                format!(
+                    "`{instance}' and `{other}' are both generated as `{gen_name}'"
+                ),
+            ));
+        }
+        let _ = fs::remove_file(stale);
+        let out_file = self.gen_file(format!("{}.v", gen_name))?;
         binding.push((
             "OUT_FILE".to_string(),
             out_file.to_string_lossy().to_string(),
         ));
+        let result_file =
+            self.output_dir.path().join(format!("{gen_name}.json"));
+        if tool.protocol == Protocol::Json {
+            let _ = fs::remove_file(&result_file);
+            binding.push((
+                "RESULT_FILE".to_string(),
+                result_file.to_string_lossy().to_string(),
+            ));
+        }
 
-        let args = module.cli(&binding).unwrap();
+        let args = module.cli(&binding).map_err(|e| {
+            GenError::tool(
+                &tool.name,
+                format!("Invalid CLI command for `{instance}': {e}"),
+            )
+        })?;
         log::info!("Executing: {} {}", tool.path, args);
 
         // Return early in dry-run mode
         if self.dry_run {
-            return ToolOutput::default();
+            return Ok(Prepared::Done(ToolOutput::default()));
         }
 
-        let output = Command::new(&tool.path)
-            .args(args.split_whitespace())
-            .output()
-            .expect("Failed to execute tool");
+        Ok(Prepared::Run(Job {
+            tool: tool.name.clone(),
+            path: tool.path.clone(),
+            args,
+            outputs: module.outputs.clone(),
+            instance: instance.clone(),
+            gen_name,
+            out_file,
+            key,
+            protocol: tool.protocol,
+            result_file,
+        }))
+    }
+
+    /// Parse the result written by a tool using [Protocol::Json]
+    fn parse_result(&self, job: &Job) -> GenResult<ToolOutput> {
+        let result = fs::read_to_string(&job.result_file).map_err(|e| {
+            job.error(format!(
+                "Failed to read result of generating `{}' from `{}': {e}",
+                job.instance,
+                job.result_file.display()
+            ))
+        })?;
+        let ToolResult {
+            name,
+            files,
+            outputs,
+            warnings,
+            signature,
+        } = serde_json::from_str(&result).map_err(|e| {
+            job.error(format!(
+                "Malformed result for `{}' in `{}': {e}",
+                job.instance,
+                job.result_file.display()
+            ))
+        })?;
+        for warning in warnings {
+            log::warn!("[tool `{}'] {}: {warning}", job.tool, job.instance);
+        }
+
+        let mut files = files
+            .into_iter()
+            .map(|f| self.output_dir.path().join(f))
+            .collect_vec();
+        if files.is_empty() {
+            files.push(job.out_file.clone());
+        }
+        if let Some(f) = files.iter().find(|f| !f.exists()) {
+            return Err(job.error(format!(
+                "Generated file `{}' for `{}' does not exist",
+                f.display(),
+                job.instance
+            )));
+        }
+        let Some(pos) = files.iter().position(|f| is_verilog(f)) else {
+            return Err(job.error(format!(
+                "No Verilog file was generated for `{}'",
+                job.instance
+            )));
+        };
+        let file = files.remove(pos);
+
+        let undeclared = outputs
+            .keys()
+            .filter(|o| !job.outputs.values().any(|n| n == *o))
+            .sorted()
+            .collect_vec();
+        if !undeclared.is_empty() {
+            return Err(job.error(format!(
+                "Generating `{}' produced undeclared outputs: {}",
+                job.instance,
+                undeclared.iter().join(", ")
+            )));
+        }
+        let outputs = outputs
+            .into_iter()
+            .map(|(k, v)| (k, v.to_string()))
+            .collect();
+
+        Ok(ToolOutput {
+            name: name.unwrap_or_else(|| job.gen_name.clone()),
+            file,
+            files,
+            // Outputs that are missing are inferred from the signature
+            exist_params: job.exist_params(&outputs, signature.is_some())?,
+            signature,
+        })
+    }
 
+    /// Parse the output of a tool invocation and record the generated module.
+    /// Errors include the standard error of the invocation.
+    fn finish(&mut self, job: Job, output: Output) -> GenResult<ToolOutput> {
+        let stdout = String::from_utf8_lossy(&output.stdout);
         log::info!(
             "Command exited with status: {}.\nSTDOUT:\n{}\nSTDERR:{}\n",
             output.status,
-            std::str::from_utf8(&output.stdout).unwrap(),
-            std::str::from_utf8(&output.stderr).unwrap()
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
         );
+        self.parse_output(&job, &output.status, &stdout)
+            .map_err(|e| e.with_stderr(&output.stderr))
+            .map(|out| self.record(job, out))
+    }
+
+    /// The generated module from the output of a tool invocation
+    fn parse_output(
+        &self,
+        job: &Job,
+        status: &ExitStatus,
+        stdout: &str,
+    ) -> GenResult<ToolOutput> {
+        if !status.success() {
+            return Err(job.error(format!(
+                "Generating `{}' failed with {status}",
+                job.instance
+            )));
+        }
 
-        // Parse bindings for existential parameters from the output
-        let mut key_map: HashMap<String, String> = HashMap::default();
-        for line in std::str::from_utf8(&output.stdout).unwrap().lines() {
-            let mut parts = line.split('=');
-            if let (Some(name), Some(val)) = (parts.next(), parts.next()) {
-                key_map.insert(name.trim().to_string(), val.trim().to_string());
+        let out = match job.protocol {
+            Protocol::Text => {
+                // Parse bindings for existential parameters from the output
+                let mut key_map: HashMap<String, String> = HashMap::default();
+                for line in stdout.lines() {
+                    let mut parts = line.split('=');
+                    if let (Some(name), Some(val)) =
+                        (parts.next(), parts.next())
+                    {
+                        key_map.insert(
+                            name.trim().to_string(),
+                            val.trim().to_string(),
+                        );
+                    }
+                }
+                log::info!("Parsed key-values: {:?}", key_map);
+                ToolOutput {
+                    exist_params: job.exist_params(&key_map, false)?,
+                    name: job.gen_name.clone(),
+                    file: job.out_file.clone(),
+                    files: vec![],
+                    signature: None,
+                }
             }
+            Protocol::Json => self.parse_result(job)?,
+        };
+        log::info!("Existential parameters: {:?}", out.exist_params);
+        Ok(out)
+    }
+
+    /// Record the module generated by a successful tool invocation
+    fn record(&mut self, job: Job, out: ToolOutput) -> ToolOutput {
+        if let (Some(cache), Some(key)) = (&self.cache, &job.key) {
+            cache.insert(key, self.output_dir.path(), &out);
         }
-        log::info!("Parsed key-values: {:?}", key_map);
-        let exist_params = module
+        self.generated
+            .entry(job.tool)
+            .or_default()
+            .insert(job.instance, out.clone());
+        out
+    }
+}
+
+/// Result of preparing to generate an instance
+enum Prepared {
+    /// The output is already available
+    Done(ToolOutput),
+    /// The tool must be run to generate the instance
+    Run(Job),
+}
+
+/// An invocation of a tool that generates an instance
+struct Job {
+    /// Name of the tool
+    tool: String,
+    /// Location of the tool binary
+    path: String,
+    /// Arguments passed to the tool
+    args: String,
+    /// Names of the outputs that bind the existential parameters
+    outputs: HashMap<String, String>,
+    /// The instance being generated
+    instance: Instance,
+    /// Name of the generated module
+    gen_name: String,
+    /// File containing the generated module
+    out_file: PathBuf,
+    /// Key of the invocation in the cache
+    key: Option<String>,
+    /// How the tool reports its result
+    protocol: Protocol,
+    /// File that the tool writes its result to when using [Protocol::Json]
+    result_file: PathBuf,
+}
+
+impl Job {
+    /// Bindings for the existential parameters of the module from the values
+    /// of its outputs. Unless `partial` is set, every output must have a
+    /// value.
+    fn exist_params(
+        &self,
+        values: &HashMap<String, String>,
+        partial: bool,
+    ) -> GenResult<HashMap<String, String>> {
+        let missing = self
             .outputs
+            .values()
+            .filter(|o| !values.contains_key(*o))
+            .sorted()
+            .collect_vec();
+        if !partial && !missing.is_empty() {
+            return Err(self.error(format!(
+                "Generating `{}' did not produce outputs: {}",
+                self.instance,
+                missing.iter().join(", ")
+            )));
+        }
+        self.outputs
             .iter()
-            .map(|(param, out_name)| {
-                let val = key_map
-                    .get(out_name)
-                    .unwrap_or_else(|| unreachable!("tool did not produce binding for existential parameter: {out_name}"));
-                (param.clone(), val.clone())
+            .sorted()
+            .filter_map(|(param, out_name)| {
+                values.get(out_name).map(|val| (param, out_name, val))
             })
-            .collect();
-        log::info!("Existential parameters: {:?}", exist_params);
+            .map(|(param, out_name, val)| {
+                if val.parse::<u64>().is_err() {
+                    return Err(self.error(format!(
+                        "Output `{out_name}' of `{}' is not a natural number: `{val}'",
+                        self.instance
+                    )));
+                }
+                Ok((param.clone(), val.clone()))
+            })
+            .collect()
+    }
 
-        // Generate the output and cache the result
-        let out = ToolOutput {
-            name: gen_name,
-            file: out_file,
-            exist_params,
-        };
-        self.generated
-            .entry(tool.name.clone())
-            .or_default()
-            .insert(instance.clone(), out.clone());
+    /// An error caused by the invocation
+    fn error<S: ToString>(&self, msg: S) -> GenError {
+        GenError::tool(&self.tool, msg)
+    }
 
-        out
+    /// Run the tool and wait for it to finish
+    fn run(&self) -> GenResult<Output> {
+        Command::new(&self.path)
+            .args(self.args.split_whitespace())
+            .output()
+            .map_err(|e| {
+                self.error(format!("Failed to execute `{}': {e}", self.path))
+            })
     }
 }
+
+/// Is the file a Verilog or SystemVerilog source?
+fn is_verilog(file: &Path) -> bool {
+    file.extension()
+        .is_some_and(|ext| ext == "v" || ext == "sv")
+}
@@ -1,9 +1,14 @@
+mod cache;
 mod cmdline;
 mod config_schema;
+mod error;
 mod exec;
 mod tool_schema;
 
 pub use cmdline::Opts;
 pub use config_schema::GenConfig;
+pub use error::{GenError, GenResult};
 pub use exec::GenExec;
-pub use tool_schema::{Instance, Manifest, Module, Tool, ToolOutput};
+pub use tool_schema::{
+    Instance, Manifest, Module, Protocol, Tool, ToolOutput, ToolResult,
+};
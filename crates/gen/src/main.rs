@@ -1,5 +1,5 @@
-use fil_gen::{GenConfig, GenExec};
-use std::fs;
+use fil_gen::{GenConfig, GenError, GenExec, GenResult};
+use std::{fs, process};
 
 fn main() {
     let opts: fil_gen::Opts = argh::from_env();
@@ -11,18 +11,36 @@ fn main() {
         .filter_level(opts.log_level)
         .target(env_logger::Target::Stderr)
         .init();
-    let mut g = GenExec::new(opts.dry_run, None, GenConfig::default());
+
+    if let Err(e) = run(opts) {
+        eprintln!("Error: {e}");
+        if let Some(stderr) = e.stderr {
+            eprintln!("Tool stderr:\n{stderr}");
+        }
+        process::exit(1);
+    }
+}
+
+fn run(opts: fil_gen::Opts) -> GenResult<()> {
+    let mut g = GenExec::new(opts.dry_run, None, GenConfig::default())?;
 
     // Deserialize the tool description
-    let desc = fs::read_to_string(opts.tool).unwrap();
-    let tool: fil_gen::Tool = toml::from_str(&desc).unwrap();
+    let tool: fil_gen::Tool = read_toml(&opts.tool)?;
     let name = tool.name.clone();
-    g.register_tool(tool);
+    g.register_tool(tool)?;
     // Deserialize the manifest file
-    let manifest_str = fs::read_to_string(opts.manifest).unwrap();
-    let manifest: fil_gen::Manifest = toml::from_str(&manifest_str).unwrap();
+    let manifest: fil_gen::Manifest = read_toml(&opts.manifest)?;
 
     for instance in &manifest.modules {
-        g.gen_instance(&name, instance);
+        g.gen_instance(&name, instance)?;
     }
+    Ok(())
+}
+
+/// Read and deserialize a TOML file
+fn read_toml<T: serde::de::DeserializeOwned>(path: &str) -> GenResult<T> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| GenError::new(format!("Failed to read `{path}': {e}")))?;
+    toml::from_str(&contents)
+        .map_err(|e| GenError::new(format!("Malformed `{path}': {e}")))
 }
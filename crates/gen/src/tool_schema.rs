@@ -1,4 +1,5 @@
 //! Defines the schema for a tool configuration file
+use crate::{GenError, GenResult};
 use itertools::Itertools;
 use serde::Deserialize;
 use std::{collections::HashMap, hash::Hash, path::PathBuf};
@@ -13,6 +14,9 @@ pub struct Tool {
     /// The tool can take ${OUT_FILE} parameter and use that generate the module
     /// in the given file.
     pub requires_out_file: Option<bool>,
+    /// How the tool reports the result of an invocation
+    #[serde(default)]
+    pub protocol: Protocol,
     /// Mapping that is globablly available to all modules
     pub globals: HashMap<String, String>,
     /// Definitions of modules
@@ -26,23 +30,30 @@ impl Tool {
     }
 
     /// Validate the definition of the tool
-    pub fn validate(&self) {
+    pub fn validate(&self) -> GenResult<()> {
         let path: PathBuf = self.path.as_str().into();
-        assert!(
-            path.exists(),
-            "tool `{}' does not exist at path `{}'",
-            self.name,
-            self.path
-        );
+        if !path.exists() {
+            return Err(GenError::tool(
+                &self.name,
+                format!("tool binary does not exist at path `{}'", self.path),
+            ));
+        }
+        if self.requires_out_file != Some(true) {
+            return Err(GenError::tool(
+                &self.name,
+                "tool does not support ${OUT_FILE}. Set `requires_out_file = true' in its definition",
+            ));
+        }
 
         let mut params = self.globals.clone().into_iter().collect_vec();
         // The NAME_FORMAT parameter is always available.
         params.push(("NAME_FORMAT".into(), "".into()));
-        if let Some(true) = self.requires_out_file {
-            params.push(("OUT_FILE".into(), "".into()));
+        params.push(("OUT_FILE".into(), "".into()));
+        if self.protocol == Protocol::Json {
+            params.push(("RESULT_FILE".into(), "".into()));
         }
 
-        for (name, m) in &self.modules {
+        for (name, m) in self.modules.iter().sorted_by_key(|(n, _)| *n) {
             // Fake bindings for parameters to make sure we can parse things
             let params = m
                 .parameters
@@ -50,23 +61,35 @@ impl Tool {
                 .map(|p| (p.clone(), "".to_string()))
                 .chain(params.clone())
                 .collect_vec();
-            if let Err(e) = m.name(&params) {
-                panic!(
-                    "[tool `{}'] Invalid name format for module `{name}': {e}",
-                    self.name
-                );
-            }
-
-            if let Err(e) = m.cli(&params) {
-                panic!(
-                    "[tool `{}'] Invalid CLI command for module `{name}': {e}",
-                    self.name
-                );
-            }
+            m.name(&params).map_err(|e| {
+                GenError::tool(
+                    &self.name,
+                    format!("Invalid name format for module `{name}': {e}"),
+                )
+            })?;
+            m.cli(&params).map_err(|e| {
+                GenError::tool(
+                    &self.name,
+                    format!("Invalid CLI command for module `{name}': {e}"),
+                )
+            })?;
         }
+        Ok(())
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// How a tool reports the result of an invocation
+pub enum Protocol {
+    /// Lines of the form `name = value` on the standard output that bind the
+    /// outputs of the module
+    #[default]
+    Text,
+    /// A [ToolResult] written as JSON to the file `${RESULT_FILE}`
+    Json,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 /// A module that can be generated by a tool
 pub struct Module {
@@ -99,19 +122,25 @@ impl Module {
             }
 
             // Next character should be {
-            assert!(
-                chars.by_ref().next() == Some('{'),
-                "Expected `{{' after `$'"
-            );
+            if chars.next() != Some('{') {
+                return Err(format!(
+                    "Expected `{{' after `$' in `{fmt_string}'"
+                ));
+            }
 
             // Parse the next word as the parameter name until we see '}'
             let mut param = String::new();
-            while let Some(c) = chars.by_ref().next() {
+            let mut closed = false;
+            for c in chars.by_ref() {
                 if c == '}' {
+                    closed = true;
                     break;
                 }
                 param.push(c);
             }
+            if !closed {
+                return Err(format!("Unterminated `${{' in `{fmt_string}'"));
+            }
 
             // No word after $
             if param.is_empty() {
@@ -169,6 +198,29 @@ impl std::fmt::Display for Instance {
     }
 }
 
+/// The result of a tool invocation reported using [Protocol::Json]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolResult {
+    /// Name of the generated module. Defaults to the module's `name_format`.
+    pub name: Option<String>,
+    /// The generated files. Relative paths are resolved in the output
+    /// directory. The first Verilog or SystemVerilog file must define the
+    /// module and defaults to `${OUT_FILE}`.
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    /// Values of the outputs of the module. Outputs may be omitted when
+    /// `signature` is provided.
+    #[serde(default)]
+    pub outputs: HashMap<String, u64>,
+    /// Warnings to report to the user
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Filament signature of the generated module, such as
+    /// `comp Rom_W8<'G:1>(clk: 1) -> (out: ['G+1, 'G+2] 8);`
+    pub signature: Option<String>,
+}
+
 /// The output from a tool invocation
 #[derive(Clone, Debug, Deserialize, Default)]
 pub struct ToolOutput {
@@ -176,6 +228,11 @@ pub struct ToolOutput {
     pub name: String,
     /// The file that contains the generated module
     pub file: PathBuf,
+    /// Other files generated along with the module, such as Verilog files
+    /// it depends on and memory initialization files
+    pub files: Vec<PathBuf>,
     /// The outputs generated by the module
     pub exist_params: HashMap<String, String>,
+    /// Filament signature of the generated module reported by the tool
+    pub signature: Option<String>,
 }
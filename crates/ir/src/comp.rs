@@ -1,8 +1,8 @@
 use super::{
     AddCtx, Cmp, CmpOp, Command, Ctx, Event, EventIdx, Expr, ExprIdx, Fact,
     IndexStore, Info, InfoIdx, InstIdx, Instance, InterfaceSrc, Interned,
-    InvIdx, Invoke, MutCtx, Param, ParamIdx, Port, PortIdx, Prop, PropIdx,
-    Time, TimeSub,
+    InvIdx, Invoke, MonoBinding, MutCtx, Param, ParamIdx, Port, PortIdx, Prop,
+    PropIdx, Time, TimeSub,
 };
 use crate::{DenseIndexInfo, ParamOwner, utils::Idx};
 use fil_ast as ast;
@@ -80,6 +80,12 @@ pub struct Component {
     /// Externally facing interface information, used to preserve interface in compilation.
     /// Must be `Some` for toplevel components and externals.
     pub src_info: Option<InterfaceSrc>,
+    /// Location of the component's name in the source program
+    pub pos: GPosIdx,
+    /// Definitions of the user-defined functions inlined into the component
+    pub fn_defs: Vec<GPosIdx>,
+    /// Parameter values this component was monomorphized with
+    pub mono_binding: MonoBinding,
     /// unannotated ports associated with this component
     pub unannotated_ports: Box<Vec<(ast::Id, u64)>>,
 
@@ -118,6 +124,17 @@ impl Component {
         self.src_info.as_ref().map(|si| si.name)
     }
 
+    /// Name of the component for messages to the user. Only the entrypoint
+    /// and external components track their source names so this falls back
+    /// to the location of the definition.
+    pub fn user_name(&self) -> Option<String> {
+        match (self.source_name(), self.pos.into_option()) {
+            (Some(name), _) => Some(name.to_string()),
+            (None, Some(pos)) => Some(pos.source().to_string()),
+            (None, None) => None,
+        }
+    }
+
     /// Add a number to the context and get handle to it.
     pub fn num(&mut self, n: u64) -> ExprIdx {
         self.exprs.intern(Expr::Concrete(n))
@@ -133,9 +133,9 @@ impl ExprIdx {
         C: Ctx<Expr> + Ctx<Prop>,
     {
         if let Expr::If { cond, .. } = ctx.get(*self) {
+            // Sub-propositions are defined before the propositions using them
+            cond.relevant_props_acc(ctx, props);
             props.push(*cond);
-            let inner_props = cond.relevant_props(ctx);
-            props.extend(inner_props);
         }
     }
 
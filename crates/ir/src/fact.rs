@@ -351,14 +351,14 @@ impl PropIdx {
             Prop::TimeCmp(_) => todo!(),
             Prop::TimeSubCmp(_) => todo!(),
             Prop::Not(p) => {
-                let inner_props = p.relevant_props(ctx);
-                props.extend(inner_props);
+                p.relevant_props_acc(ctx, props);
+                props.push(*p);
             }
             Prop::And(l, r) | Prop::Or(l, r) | Prop::Implies(l, r) => {
-                let l_props = l.relevant_props(ctx);
-                let r_props = r.relevant_props(ctx);
-                props.extend(l_props);
-                props.extend(r_props);
+                l.relevant_props_acc(ctx, props);
+                props.push(*l);
+                r.relevant_props_acc(ctx, props);
+                props.push(*r);
             }
         }
     }
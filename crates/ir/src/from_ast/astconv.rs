@@ -1,6 +1,6 @@
 //! Convert the frontend AST to the IR.
 use super::build_ctx::{OwnedParam, OwnedPort};
-use super::{BuildCtx, Sig, SigMap};
+use super::{BuildCtx, FnMap, Sig, SigMap};
 use crate::utils::Idx;
 use crate::{self as ir, EntryPoint};
 use crate::{
@@ -8,9 +8,9 @@ use crate::{
     ParamIdx, PortIdx, PropIdx, TimeIdx,
 };
 use fil_ast::{self as ast};
-use fil_utils::{Diagnostics, Error, GPosIdx};
+use fil_utils::{Diagnostics, Error, GPosIdx, Id};
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 pub type BuildRes<T> = Result<T, Diagnostics>;
@@ -278,13 +278,36 @@ impl BuildCtx<'_> {
                 // The .add call simplifies the expression if possible
                 self.comp().add(ir::Expr::Fn { op: func, args })
             }
+            ast::Expr::Call { func, args } => {
+                // Calls are inlined by substituting the arguments into the
+                // body of the function
+                let def = self.get_fn(&func)?;
+                if def.params.len() != args.len() {
+                    let diag = self.diag();
+                    let call = diag.add_info(
+                        format!("called with {} arguments", args.len()),
+                        func.pos(),
+                    );
+                    let defn =
+                        diag.add_info("function defined here", def.name.pos());
+                    return self.fail(
+                        Error::malformed(format!(
+                            "function `{func}' expects {} arguments",
+                            def.params.len()
+                        )),
+                        [call, defn],
+                    );
+                }
+                if !self.comp().fn_defs.contains(&def.pos) {
+                    self.comp().fn_defs.push(def.pos);
+                }
+                let binding = ast::Binding::new(
+                    def.params.iter().map(|p| p.copy()).zip(args),
+                );
+                self.expr(def.body.inner().clone().resolve(&binding))?
+            }
             ast::Expr::If { cond, then, alt } => {
-                let ast::OrderConstraint { left, right, op } = cond;
-                let cond = self.expr_cons(ast::OrderConstraint {
-                    left: *left,
-                    right: *right,
-                    op,
-                })?;
+                let cond = self.expr_prop(*cond)?;
                 let then = self.expr(*then)?;
                 let alt = self.expr(*alt)?;
                 self.comp().add(ir::Expr::If { cond, then, alt })
@@ -307,6 +330,23 @@ impl BuildCtx<'_> {
         Ok(self.comp().add(ir::Prop::Cmp(ir::CmpOp { lhs, op, rhs })))
     }
 
+    fn expr_prop(&mut self, prop: ast::Prop<ast::Expr>) -> BuildRes<PropIdx> {
+        Ok(match prop {
+            ast::Prop::Cmp(cons) => self.expr_cons(cons)?,
+            ast::Prop::Not(p) => self.expr_prop(*p)?.not(self.comp()),
+            ast::Prop::And(l, r) => {
+                let l = self.expr_prop(*l)?;
+                let r = self.expr_prop(*r)?;
+                l.and(r, self.comp())
+            }
+            ast::Prop::Or(l, r) => {
+                let l = self.expr_prop(*l)?;
+                let r = self.expr_prop(*r)?;
+                l.or(r, self.comp())
+            }
+        })
+    }
+
     fn event_cons(
         &mut self,
         cons: ast::OrderConstraint<ast::Time>,
@@ -327,9 +367,9 @@ impl BuildCtx<'_> {
         &mut self,
         i: ast::Implication<ast::Expr>,
     ) -> BuildRes<PropIdx> {
-        let cons = self.expr_cons(i.cons)?;
+        let cons = self.expr_prop(i.cons)?;
         if let Some(ante) = i.guard {
-            let ante = self.expr_cons(ante)?;
+            let ante = self.expr_prop(ante)?;
             Ok(ante.implies(cons, self.comp()))
         } else {
             Ok(cons)
@@ -439,6 +479,8 @@ impl BuildCtx<'_> {
                     len,
                     liveness,
                     bitwidth,
+                    interp,
+                    ..
                 },
             attrs,
         } = pd;
@@ -469,11 +511,21 @@ impl BuildCtx<'_> {
                 range: ctx.range(liveness.take())?,
             })
         })?;
+        let interp = match interp.map(|i| i.take()) {
+            None => None,
+            Some(ast::Interp::Unsigned) => Some(ir::Interp::Unsigned),
+            Some(ast::Interp::Signed) => Some(ir::Interp::Signed),
+            Some(ast::Interp::Fixed { int, frac }) => Some(ir::Interp::Fixed {
+                int: self.expr(int.take())?,
+                frac: self.expr(frac.take())?,
+            }),
+        };
         let p = ir::Port {
             width: self.expr(bitwidth.take())?,
             owner,
             live,
             info,
+            interp,
         };
 
         // Defines helper variable here due to lifetime issues
@@ -615,7 +667,7 @@ impl BuildCtx<'_> {
                         let assumes = cons
                             .iter()
                             .map(|pc| {
-                                self.expr_cons(pc.inner().clone())
+                                self.expr_prop(pc.inner().clone())
                                     .map(|p| (p, pc.pos()))
                             })
                             .collect::<BuildRes<Vec<_>>>()?;
@@ -672,7 +724,7 @@ impl BuildCtx<'_> {
             self.comp().add_event_assert([(prop, ec.pos())]);
         }
         for pc in &sig.param_constraints {
-            let prop = self.expr_cons(pc.inner().clone())?;
+            let prop = self.expr_prop(pc.inner().clone())?;
             self.comp().add_param_assert([(prop, pc.pos())]);
         }
 
@@ -915,7 +967,7 @@ impl BuildCtx<'_> {
                 ]
             }
             ast::Command::If(ast::If { cond, then, alt }) => {
-                let cond = self.expr_cons(cond)?;
+                let cond = self.expr_prop(cond)?;
                 let then = self.try_with_scope(|this| this.commands(then))?;
                 let alt = self.try_with_scope(|this| this.commands(alt))?;
                 vec![ir::If { cond, then, alt }.into()]
@@ -936,8 +988,8 @@ enum TypeInfo {
     Source(Vec<ast::Command>),
     /// Contains extern verilog module name
     External(String),
-    /// Contains extern tool name
-    Generated(String),
+    /// Contains extern tool name and the location of the generate definition
+    Generated(String, GPosIdx),
 }
 
 /// Temporary state used to store information from the component
@@ -965,7 +1017,7 @@ impl From<ast::Extern> for ComponentTransform {
         let typ = if ext.gen_tool.is_none() {
             TypeInfo::External(ext.path)
         } else {
-            TypeInfo::Generated(ext.gen_tool.unwrap())
+            TypeInfo::Generated(ext.gen_tool.unwrap(), ext.pos)
         };
         Self {
             typ,
@@ -974,6 +1026,70 @@ impl From<ast::Extern> for ComponentTransform {
     }
 }
 
+/// Collect the user-defined functions. Calls to functions are inlined so
+/// functions cannot be recursive.
+fn functions(defs: Vec<ast::FnDef>) -> BuildRes<FnMap> {
+    let mut diag = Diagnostics::default();
+    let mut failed = false;
+    let mut order = Vec::with_capacity(defs.len());
+    let mut fns = FnMap::new();
+    for def in defs {
+        let name = def.name.copy();
+        if let Some(prev) = fns.get(&name) {
+            let err = Error::already_bound(name, "function")
+                .add_note(
+                    diag.add_info("function redefined here", def.name.pos()),
+                )
+                .add_note(
+                    diag.add_info("previous definition here", prev.name.pos()),
+                );
+            diag.add_error(err);
+            failed = true;
+        } else {
+            order.push(name);
+            fns.insert(name, def);
+        }
+    }
+
+    /// Whether the body of `from` (transitively) calls `target`
+    fn reaches(
+        from: &ast::FnDef,
+        target: Id,
+        fns: &FnMap,
+        seen: &mut HashSet<Id>,
+    ) -> bool {
+        from.body.calls().into_iter().any(|c| {
+            *c.inner() == target
+                || (seen.insert(c.copy())
+                    && fns
+                        .get(c.inner())
+                        .is_some_and(|f| reaches(f, target, fns, seen)))
+        })
+    }
+
+    for name in order {
+        let def = &fns[&name];
+        let recursive = def.body.calls().into_iter().find(|c| {
+            *c.inner() == name
+                || fns.get(c.inner()).is_some_and(|f| {
+                    reaches(f, name, &fns, &mut HashSet::new())
+                })
+        });
+        if let Some(call) = recursive {
+            let err =
+                Error::malformed(format!("function `{name}' is recursive"))
+                    .add_note(diag.add_info("recursive call here", call.pos()))
+                    .add_note(
+                        diag.add_info("function defined here", def.name.pos()),
+                    );
+            diag.add_error(err);
+            failed = true;
+        }
+    }
+
+    if failed { Err(diag) } else { Ok(fns) }
+}
+
 fn try_transform(ns: ast::Namespace) -> BuildRes<ir::Context> {
     // creates an empty context with the main index.
     let mut ctx = ir::Context {
@@ -989,6 +1105,8 @@ fn try_transform(ns: ast::Namespace) -> BuildRes<ir::Context> {
     let toplevel_id =
         ns.main_idx().map(|pos| ns.components[pos].sig.name.clone());
 
+    let fns = functions(ns.functions)?;
+
     // Walk over signatures and compile signatures to build a SigMap
     // Contains a tuple containing three necessary bits of information:
     // 1. Component type (external, gen, or source)
@@ -1006,10 +1124,11 @@ fn try_transform(ns: ast::Namespace) -> BuildRes<ir::Context> {
                  comps,
                  gen_tool,
                  path,
+                 pos,
              }| {
                 comps.into_iter().map(move |comp| {
                     let typ = if let Some(name) = &gen_tool {
-                        TypeInfo::Generated(name.clone())
+                        TypeInfo::Generated(name.clone(), pos)
                     } else {
                         TypeInfo::External(path.clone())
                     };
@@ -1039,8 +1158,9 @@ fn try_transform(ns: ast::Namespace) -> BuildRes<ir::Context> {
                 match comp_ctx.typ {
                     TypeInfo::Source(_) => ir::CompType::Source,
                     TypeInfo::External(_) => ir::CompType::External,
-                    TypeInfo::Generated(_) => ir::CompType::Generated,
-                }, comp_ctx.sig.attributes.clone()), &sig_map);
+                    TypeInfo::Generated(..) => ir::CompType::Generated,
+                }, comp_ctx.sig.attributes.clone()), &sig_map, &fns);
+            builder.comp().pos = comp_ctx.sig.name.pos();
 
             // enable source information saving if this is main
             if ctx.is_main(idx) {
@@ -1054,8 +1174,10 @@ fn try_transform(ns: ast::Namespace) -> BuildRes<ir::Context> {
 
                     builder.comp().src_info = Some(InterfaceSrc::new(comp_ctx.sig.name.copy(), None));
                 }
-                TypeInfo::Generated(name) => {
-                    builder.comp().src_info = Some(InterfaceSrc::new(comp_ctx.sig.name.copy(), Some(name.clone())));
+                TypeInfo::Generated(name, pos) => {
+                    let mut src = InterfaceSrc::new(comp_ctx.sig.name.copy(), Some(name.clone()));
+                    src.gen_pos = *pos;
+                    builder.comp().src_info = Some(src);
                 }
                 _ => {}
 
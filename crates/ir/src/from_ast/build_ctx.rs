@@ -3,9 +3,13 @@ use super::{BuildRes, ScopeMap, SigMap};
 use crate::{self as ir, Ctx, DenseIndexInfo, PortIdx};
 use fil_ast as ast;
 use fil_utils::{self as utils, Error, Id};
+use std::collections::HashMap;
 use std::rc::Rc;
 use utils::InfoIdx;
 
+/// User-defined functions over parameters
+pub(super) type FnMap = HashMap<Id, ast::FnDef>;
+
 #[derive(Eq, PartialEq, Hash, Clone, Debug)]
 /// A custom struct used to index ports
 pub(super) enum OwnedPort {
@@ -100,6 +104,9 @@ pub(super) struct BuildCtx<'prog> {
     /// Map of currently defined signatures
     sigs: &'prog SigMap,
 
+    /// User-defined functions
+    fns: &'prog FnMap,
+
     // Mapping from names to IR nodes.
     event_map: ScopeMap<ir::EventIdx>,
     inst_map: ScopeMap<ir::InstIdx>,
@@ -114,10 +121,15 @@ pub(super) struct BuildCtx<'prog> {
 }
 
 impl<'prog> BuildCtx<'prog> {
-    pub fn new(comp: ir::Component, sigs: &'prog SigMap) -> Self {
+    pub fn new(
+        comp: ir::Component,
+        sigs: &'prog SigMap,
+        fns: &'prog FnMap,
+    ) -> Self {
         Self {
             comp,
             sigs,
+            fns,
             diag: utils::Diagnostics::default(),
             param_map: ScopeMap::new(),
             event_map: ScopeMap::new(),
@@ -203,6 +215,21 @@ impl<'prog> BuildCtx<'prog> {
         }
     }
 
+    /// Get the function if defined or return an error
+    pub fn get_fn(&mut self, id: &ast::Loc<Id>) -> BuildRes<&'prog ast::FnDef> {
+        let name = id.inner();
+        match self.fns.get(name) {
+            Some(f) => Ok(f),
+            None => {
+                let info = self.diag.add_info(
+                    format!("function `{id}' is not defined"),
+                    id.pos(),
+                );
+                self.fail(Error::undefined(*name, "function"), [info])
+            }
+        }
+    }
+
     /// Update the signature map
     pub fn set_sig_map(&mut self, sigs: &'prog SigMap) {
         self.sigs = sigs;
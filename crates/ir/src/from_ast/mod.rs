@@ -2,7 +2,7 @@ mod build_ctx;
 mod scope_map;
 mod sig_map;
 
-use build_ctx::BuildCtx;
+use build_ctx::{BuildCtx, FnMap};
 use scope_map::ScopeMap;
 use sig_map::{Sig, SigMap};
 
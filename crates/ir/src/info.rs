@@ -1,5 +1,6 @@
 use super::{
-    Component, Ctx, DisplayCtx, ExprIdx, InfoIdx, Range, TimeIdx, TimeSub,
+    Component, Ctx, DisplayCtx, ExprIdx, InfoIdx, Interp, Range, TimeIdx,
+    TimeSub,
 };
 use codespan_reporting::diagnostic::Diagnostic;
 use fil_ast as ast;
@@ -344,6 +345,15 @@ pub enum Reason {
         src_loc: GPosIdx,
         dst_width: ExprIdx,
         src_width: ExprIdx,
+        /// The source is a signed number and must be sign-extended
+        signed: bool,
+    },
+    /// Connected ports have the same numeric interpretation
+    InterpMatch {
+        dst_loc: GPosIdx,
+        src_loc: GPosIdx,
+        dst: Interp,
+        src: Interp,
     },
     /// An access is within bounds
     InBoundsAccess {
@@ -414,6 +424,12 @@ pub enum Reason {
         /// Location of the binding
         time_expr_loc: GPosIdx,
     },
+    // ========== Constraints from termination checking ==========
+    /// A recursive instantiation decreases the parameters
+    Termination {
+        /// Location of the instance
+        inst_loc: GPosIdx,
+    },
     // =============== Generic Constraints =======================
     /// A simple reason
     Misc { reason: String, def_loc: GPosIdx },
@@ -552,12 +568,28 @@ impl Reason {
         src_loc: GPosIdx,
         dst_width: ExprIdx,
         src_width: ExprIdx,
+        signed: bool,
     ) -> Self {
         Self::BundleWidthMatch {
             dst_loc,
             src_loc,
             dst_width,
             src_width,
+            signed,
+        }
+    }
+
+    pub fn interp_match(
+        dst_loc: GPosIdx,
+        src_loc: GPosIdx,
+        dst: Interp,
+        src: Interp,
+    ) -> Self {
+        Self::InterpMatch {
+            dst_loc,
+            src_loc,
+            dst,
+            src,
         }
     }
 
@@ -582,6 +614,10 @@ impl Reason {
         }
     }
 
+    pub fn termination(inst_loc: GPosIdx) -> Self {
+        Self::Termination { inst_loc }
+    }
+
     pub fn generated(reason: String, src: Reason) -> Self {
         Self::Generated {
             reason,
@@ -605,6 +641,7 @@ impl Reason {
             Reason::ExistsConstraint { .. } => "ExistsConstraint",
             Reason::BundleLenMatch { .. } => "BundleLenMatch",
             Reason::BundleWidthMatch { .. } => "BundleWidthMatch",
+            Reason::InterpMatch { .. } => "InterpMatch",
             Reason::InBoundsAccess { .. } => "InBoundsAccess",
             Reason::Liveness { .. } => "Liveness",
             Reason::BundleDelay { .. } => "BundleDelay",
@@ -612,11 +649,39 @@ impl Reason {
             Reason::EventLive { .. } => "EventLive",
             Reason::EventLiveDelay { .. } => "EventLiveDelay",
             Reason::EventTrig { .. } => "EventTrig",
+            Reason::Termination { .. } => "Termination",
             Reason::Misc { .. } => "Misc",
             Reason::Generated { .. } => "Generated",
         }
     }
 
+    /// The location that a diagnostic for this reason points to
+    pub fn loc(&self) -> GPosIdx {
+        match self {
+            Reason::ParamConstraint { constraint_loc, .. }
+            | Reason::EventConstraint { constraint_loc, .. } => *constraint_loc,
+            Reason::ExistsConstraint {
+                bind_loc,
+                constraint_loc,
+            } => constraint_loc.unwrap_or(*bind_loc),
+            Reason::BundleLenMatch { dst_loc, .. } => *dst_loc,
+            Reason::BundleWidthMatch { src_loc, .. }
+            | Reason::InterpMatch { src_loc, .. }
+            | Reason::Liveness { src_loc, .. } => *src_loc,
+            Reason::InBoundsAccess { access_loc, .. } => *access_loc,
+            Reason::BundleDelay {
+                bundle_range_loc, ..
+            } => *bundle_range_loc,
+            Reason::WellFormedInterval { range_loc, .. } => *range_loc,
+            Reason::EventLive { time_expr_loc, .. }
+            | Reason::EventTrig { time_expr_loc, .. } => *time_expr_loc,
+            Reason::EventLiveDelay { live_loc, .. } => *live_loc,
+            Reason::Termination { inst_loc } => *inst_loc,
+            Reason::Misc { def_loc, .. } => *def_loc,
+            Reason::Generated { src, .. } => src.loc(),
+        }
+    }
+
     /// Convert this reason into a diagnostic message
     pub fn diag(&self, ctx: &Component) -> Diagnostic<usize> {
         match self {
@@ -719,6 +784,7 @@ impl Reason {
                 src_loc,
                 dst_width,
                 src_width,
+                signed,
             } => {
                 let sw = ctx.display(*src_width);
                 let dw = ctx.display(*dst_width);
@@ -728,9 +794,59 @@ impl Reason {
                 let dst = dst_loc
                     .secondary()
                     .with_message(format!("destination has width {dw}",));
+                let extend = if *signed { "SignExtend" } else { "ZeroExtend" };
+                let extend = format!("`{extend}[{sw}, {dw}]'");
+                let (src_c, dst_c) =
+                    (src_width.as_concrete(ctx), dst_width.as_concrete(ctx));
+                let msb = dst_c.map_or(format!("{dw}-1"), |d| {
+                    d.saturating_sub(1).to_string()
+                });
+                let truncate = format!("`Slice[{sw}, {msb}, 0]'");
+                let fix = match (src_c, dst_c) {
+                    (Some(s), Some(d)) if s < d => {
+                        format!("extend the source using {extend}")
+                    }
+                    (Some(_), Some(_)) => {
+                        format!("truncate the source using {truncate}")
+                    }
+                    _ => format!(
+                        "extend the source using {extend} if it is narrower or truncate it using {truncate} if it is wider"
+                    ),
+                };
                 Diagnostic::error()
                     .with_message(format!("required bundle of width `{dw}' but found bundle of width `{sw}'"))
                     .with_labels(vec![src, dst])
+                    .with_notes(vec![
+                        fix,
+                        "use `--auto-resize` to insert these conversions automatically".to_string(),
+                    ])
+            }
+            Reason::InterpMatch {
+                dst_loc,
+                src_loc,
+                dst,
+                src,
+            } => {
+                let display = |i: &Interp| match i {
+                    Interp::Unsigned => "unsigned".to_string(),
+                    Interp::Signed => "signed".to_string(),
+                    Interp::Fixed { int, frac } => format!(
+                        "fixed<{}, {}>",
+                        ctx.display(*int),
+                        ctx.display(*frac)
+                    ),
+                };
+                let (si, di) = (display(src), display(dst));
+                let src = src_loc
+                    .primary()
+                    .with_message(format!("source is interpreted as {si}"));
+                let dst = dst_loc.secondary().with_message(format!(
+                    "destination is interpreted as {di}"
+                ));
+                Diagnostic::error()
+                    .with_message(format!("required `{di}' port but found `{si}' port"))
+                    .with_labels(vec![src, dst])
+                    .with_notes(vec!["use a conversion component like `ToSigned' or `ToFixed' to change the interpretation".to_string()])
             }
             Reason::Liveness {
                 src_loc,
@@ -855,6 +971,15 @@ impl Reason {
                     .with_message("event's delay must be greater than the instance's borrow length")
                     .with_labels(vec![live, ev])
             }
+            Reason::Termination { inst_loc } => {
+                let inst = inst_loc
+                    .primary()
+                    .with_message("cannot prove that the parameters decrease");
+                Diagnostic::error()
+                    .with_message("recursive instantiation may not terminate")
+                    .with_labels(vec![inst])
+                    .with_notes(vec!["parameters of recursive instances must be lexicographically smaller than those of the enclosing component".to_string()])
+            }
             Reason::Generated { reason, src } => {
                 src.diag(ctx).with_notes(vec![reason.clone()])
             }
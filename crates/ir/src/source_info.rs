@@ -2,6 +2,7 @@ use crate::ParamIdx;
 
 use super::{Event, Param, Port, utils::SparseInfoMap};
 use fil_ast as ast;
+use fil_utils::GPosIdx;
 
 #[derive(Clone)]
 /// Externally facing interface name information for components.
@@ -18,6 +19,8 @@ pub struct InterfaceSrc {
     pub interface_ports: SparseInfoMap<Event, ast::Id>,
     /// The external tool that generates this module during compilation
     pub gen_tool: Option<String>,
+    /// Location of the generate definition that uses the tool
+    pub gen_pos: GPosIdx,
 }
 
 impl InterfaceSrc {
@@ -29,6 +32,7 @@ impl InterfaceSrc {
             interface_ports: SparseInfoMap::default(),
             events: SparseInfoMap::default(),
             gen_tool,
+            gen_pos: GPosIdx::UNKNOWN,
         }
     }
 
@@ -45,3 +49,14 @@ impl InterfaceSrc {
         )
     }
 }
+
+#[derive(Clone, Default)]
+/// Values of the parameters that a monomorphized component was generated with.
+pub struct MonoBinding {
+    /// Name of the monomorphized component in the source program
+    pub name: Option<ast::Id>,
+    /// Parameters bound in the signature
+    pub params: Vec<(ast::Id, u64)>,
+    /// Existentially quantified parameters resolved during monomorphization
+    pub exists: Vec<(ast::Id, u64)>,
+}
@@ -134,6 +134,26 @@ pub struct Liveness {
     pub range: Range,
 }
 
+#[derive(PartialEq, Eq, Hash, Clone)]
+/// Numeric interpretation of the bits of a port
+pub enum Interp {
+    Unsigned,
+    Signed,
+    /// Fixed-point number with `int` integer bits and `frac` fractional bits
+    Fixed {
+        int: ExprIdx,
+        frac: ExprIdx,
+    },
+}
+
+impl Interp {
+    /// Do two interpretations have the same kind, ignoring the parameters of
+    /// fixed-point numbers?
+    pub fn same_kind(&self, other: &Interp) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
 #[derive(PartialEq, Eq, Clone)]
 /// A port tracks its definition and liveness.
 /// A port in the IR generalizes both bundles and normal ports.
@@ -142,6 +162,9 @@ pub struct Port {
     pub width: ExprIdx,
     pub live: Liveness,
     pub info: InfoIdx,
+    /// Numeric interpretation of the port's bits. Ports without one are raw
+    /// bits that can be connected to any port.
+    pub interp: Option<Interp>,
 }
 impl Port {
     /// Check if this is an invoke defined port
@@ -1,5 +1,5 @@
 use crate::{AddCtx, Ctx, utils, utils::Idx};
-use std::{collections::HashMap, fmt::Display, rc::Rc};
+use std::{collections::HashMap, fmt::Display, sync::Arc};
 
 #[derive(Clone)]
 /// An indexed storage for an interned type. Keeps a HashMap to provide faster reverse mapping
@@ -15,8 +15,8 @@ where
     T: Eq + std::hash::Hash,
     I: utils::IdxLike<T>,
 {
-    store: Vec<Rc<T>>,
-    map: HashMap<Rc<T>, I>,
+    store: Vec<Arc<T>>,
+    map: HashMap<Arc<T>, I>,
 }
 
 impl<T> Ctx<T> for Interned<T>
@@ -58,7 +58,7 @@ where
     /// Intern a value into the store and return the index.
     /// If the value is already in the store, return the existing index.
     pub fn intern(&mut self, val: T) -> I {
-        let v = Rc::new(val);
+        let v = Arc::new(val);
         if let Some(idx) = self.map.get(&v) {
             return *idx;
         }
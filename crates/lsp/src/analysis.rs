@@ -0,0 +1,179 @@
+use crate::index::{self, Index};
+use codespan_reporting::diagnostic::{self as cs, LabelStyle};
+use fil_ast as ast;
+use fil_ir as ir;
+use fil_utils::{Diagnostics, GlobalPositionTable};
+use filament::{
+    ast_pass_pipeline, ast_passes as ap, cmdline, ir_pass_pipeline,
+    ir_passes as ip, resolver::Resolver,
+};
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location,
+    Position, Range, Url,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+/// The result of checking a program
+pub struct Analysis {
+    /// Index of the names in the program. Only available if the program parsed.
+    pub index: Option<Index>,
+    /// Diagnostics for each file in the program
+    pub diags: HashMap<PathBuf, Vec<Diagnostic>>,
+}
+
+/// Run the checking passes of the compiler.
+fn check(opts: &cmdline::Opts, mut ns: ast::Namespace) -> Result<(), u64> {
+    ast_pass_pipeline! { opts, ns; ap::TopLevel, ap::Records };
+    let mut ir = ir::transform(ns)?;
+    ir_pass_pipeline! {opts, ir;
+        ip::Assumptions,
+        ip::BuildDomination,
+        ip::TypeCheck,
+        ip::IntervalCheck,
+        ip::TerminationCheck,
+        ip::PhantomCheck,
+        ip::InferAssumes
+    }
+    if !opts.unsafe_skip_discharge {
+        ir_pass_pipeline! {opts, ir; ip::Discharge }
+    }
+    Ok(())
+}
+
+impl Analysis {
+    /// Parse and check the input file in `opts`
+    pub fn run(opts: &cmdline::Opts) -> Self {
+        let input = fs::canonicalize(&opts.input).unwrap_or(opts.input.clone());
+        let mut analysis = Analysis {
+            index: None,
+            diags: HashMap::new(),
+        };
+        // Always publish diagnostics for the input so that stale ones are cleared
+        analysis.diags.insert(input.clone(), vec![]);
+
+        let ns = match Resolver::from(opts).parse_namespace() {
+            Ok(ns) => ns,
+            Err(e) => {
+                analysis.parse_error(input, &e.kind);
+                return analysis;
+            }
+        };
+
+        let index = Index::new(&ns);
+        let (res, diags) = Diagnostics::capture(|| {
+            panic::catch_unwind(AssertUnwindSafe(|| check(opts, ns)))
+        });
+        if let Err(e) = res {
+            let msg = e
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| e.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default();
+            analysis.push(
+                input.clone(),
+                Diagnostic::new_simple(
+                    Range::default(),
+                    format!("internal compiler error: {msg}"),
+                ),
+            );
+        }
+        for diag in diags {
+            analysis.diagnostic(&input, &index, diag);
+        }
+        analysis.index = Some(index);
+        analysis
+    }
+
+    fn push(&mut self, file: PathBuf, diag: Diagnostic) {
+        self.diags.entry(file).or_default().push(diag);
+    }
+
+    /// Report a parse error. The parser reports locations in the message as
+    /// `--> file:line:col`.
+    fn parse_error(&mut self, input: PathBuf, msg: &str) {
+        let loc = msg.lines().find_map(|l| {
+            let (_, loc) = l.split_once("--> ")?;
+            let mut parts = loc.rsplitn(3, ':');
+            let col: u32 = parts.next()?.parse().ok()?;
+            let line: u32 = parts.next()?.parse().ok()?;
+            let file = fs::canonicalize(parts.next()?).ok()?;
+            Some((file, Position::new(line - 1, col - 1)))
+        });
+        let (file, pos) = loc.unwrap_or((input, Position::default()));
+        let mut diag = Diagnostic::new_simple(Range::new(pos, pos), msg.into());
+        diag.severity = Some(DiagnosticSeverity::ERROR);
+        self.push(file, diag);
+    }
+
+    /// Convert a diagnostic reported by the compiler
+    fn diagnostic(
+        &mut self,
+        input: &Path,
+        index: &Index,
+        diag: cs::Diagnostic<usize>,
+    ) {
+        let table = GlobalPositionTable::get();
+        let location = |label: &cs::Label<usize>| {
+            let file = table.files().get(label.file_id).ok()?;
+            let path = fs::canonicalize(file.name()).ok()?;
+            let src = index.sources.get(&path).unwrap_or(file.source());
+            let range = Range::new(
+                index::position(src, label.range.start),
+                index::position(src, label.range.end),
+            );
+            Some((path, range))
+        };
+
+        let primary = diag
+            .labels
+            .iter()
+            .find(|l| l.style == LabelStyle::Primary)
+            .and_then(location);
+        let related = diag
+            .labels
+            .iter()
+            .filter(|l| l.style == LabelStyle::Secondary)
+            .filter_map(|l| {
+                let (path, range) = location(l)?;
+                Some(DiagnosticRelatedInformation {
+                    location: Location::new(
+                        Url::from_file_path(path).ok()?,
+                        range,
+                    ),
+                    message: l.message.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut message = diag.message;
+        if let Some(label) = diag
+            .labels
+            .iter()
+            .find(|l| l.style == LabelStyle::Primary && !l.message.is_empty())
+        {
+            message = format!("{message}: {}", label.message);
+        }
+        for note in diag.notes {
+            message = format!("{message}\n{note}");
+        }
+
+        let (file, range) =
+            primary.unwrap_or((input.to_path_buf(), Range::default()));
+        self.push(
+            file,
+            Diagnostic {
+                range,
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("filament".to_string()),
+                message,
+                related_information: (!related.is_empty()).then_some(related),
+                ..Default::default()
+            },
+        );
+    }
+}
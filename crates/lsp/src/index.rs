@@ -0,0 +1,644 @@
+use fil_ast as ast;
+use fil_utils::{GPosIdx, GlobalPositionTable};
+use itertools::Itertools;
+use lsp_types::Position;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A range of bytes in a source file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub file: PathBuf,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    fn contains(&self, file: &Path, offset: usize) -> bool {
+        self.file == file && self.start <= offset && offset <= self.end
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Component,
+    Param,
+    Event,
+    Port,
+    Instance,
+    Invoke,
+    Bundle,
+    Index,
+}
+
+/// A named entity in the program
+pub struct Symbol {
+    pub name: String,
+    pub kind: Kind,
+    /// Location of the definition
+    pub def: Option<Span>,
+    /// Markdown shown when hovering over the symbol
+    pub hover: String,
+}
+
+pub type SymIdx = usize;
+
+/// Symbols defined by the signature of a component
+#[derive(Default)]
+struct CompSyms {
+    sym: SymIdx,
+    params: Vec<SymIdx>,
+    /// Events, ports, and interface ports of the signature
+    sig: Vec<SymIdx>,
+    ports: Vec<SymIdx>,
+}
+
+/// Names visible in the body of a component
+struct Scope {
+    file: PathBuf,
+    /// Offset of the component's name
+    start: usize,
+    names: HashMap<String, SymIdx>,
+    /// Component instantiated by each instance and invocation
+    comps: HashMap<String, String>,
+}
+
+/// An index of all the definitions and uses of names in a program.
+#[derive(Default)]
+pub struct Index {
+    symbols: Vec<Symbol>,
+    /// Occurrences of the symbols, including their definitions
+    refs: Vec<(Span, SymIdx)>,
+    comps: HashMap<String, CompSyms>,
+    scopes: Vec<Scope>,
+    /// Canonical path for files in the [GlobalPositionTable]
+    paths: HashMap<usize, Option<PathBuf>>,
+    /// Contents of the files as they were analyzed
+    pub sources: HashMap<PathBuf, String>,
+}
+
+impl Index {
+    /// Build the index for a namespace produced by the resolver.
+    pub fn new(ns: &ast::Namespace) -> Self {
+        let mut idx = Index::default();
+        let sigs = ns
+            .externs
+            .iter()
+            .flat_map(|ext| &ext.comps)
+            .chain(ns.components.iter().map(|c| &c.sig));
+        for sig in sigs {
+            idx.signature(sig);
+        }
+        for comp in &ns.components {
+            idx.component(comp);
+        }
+        idx
+    }
+
+    /// Convert a position into a span in a file on disk
+    fn span(&mut self, pos: GPosIdx) -> Option<Span> {
+        let pos = pos.into_option()?;
+        let table = GlobalPositionTable::get();
+        let data = table.get_pos(pos.0);
+        let file = data.file.get();
+        let path = self
+            .paths
+            .entry(file)
+            .or_insert_with(|| {
+                let (name, src) = table.get_file_data(data.file);
+                let path = fs::canonicalize(name).ok()?;
+                self.sources.insert(path.clone(), src.to_string());
+                Some(path)
+            })
+            .clone()?;
+        Some(Span {
+            file: path,
+            start: data.start,
+            end: data.end,
+        })
+    }
+
+    fn add(
+        &mut self,
+        name: &ast::Loc<ast::Id>,
+        kind: Kind,
+        hover: String,
+    ) -> SymIdx {
+        let def = self.span(name.pos());
+        let idx = self.symbols.len();
+        if let Some(def) = &def {
+            self.refs.push((def.clone(), idx));
+        }
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            def,
+            hover,
+        });
+        idx
+    }
+
+    fn use_sym(&mut self, name: &ast::Loc<ast::Id>, sym: Option<SymIdx>) {
+        if let (Some(span), Some(sym)) = (self.span(name.pos()), sym) {
+            self.refs.push((span, sym));
+        }
+    }
+
+    fn signature(&mut self, sig: &ast::Signature) {
+        let sym = self.add(&sig.name, Kind::Component, code(fmt::sig(sig)));
+        let comp = sig.name.to_string();
+        let params = sig
+            .params
+            .iter()
+            .map(|p| &p.param)
+            .chain(sig.sig_bindings.iter().map(|sb| match sb.inner() {
+                ast::SigBind::Let { param, .. }
+                | ast::SigBind::Exists { param, .. } => param,
+            }))
+            .map(|p| {
+                let hover = format!("parameter of `{comp}`");
+                self.add(p, Kind::Param, code(format!("param {p}")) + &hover)
+            })
+            .collect();
+        let mut syms = vec![];
+        for ev in &sig.events {
+            let hover = code(fmt::event(ev)) + &format!("event of `{comp}`");
+            syms.push(self.add(&ev.event, Kind::Event, hover));
+        }
+        for int in &sig.interface_signals {
+            let hover =
+                code(format!("{}: interface['{}]", int.name, int.event))
+                    + &format!("interface port of `{comp}`");
+            syms.push(self.add(&int.name, Kind::Port, hover));
+        }
+        let inputs = sig.inputs().count();
+        let mut ports = vec![];
+        for (i, port) in sig.ports().iter().enumerate() {
+            let dir = if i < inputs { "input" } else { "output" };
+            let hover = code(fmt::bundle(port)) + &format!("{dir} of `{comp}`");
+            let p = self.add(&port.name, Kind::Port, hover);
+            syms.push(p);
+            ports.push(p);
+        }
+        self.comps.insert(
+            comp,
+            CompSyms {
+                sym,
+                params,
+                sig: syms,
+                ports,
+            },
+        );
+    }
+
+    fn component(&mut self, comp: &ast::Component) {
+        let Some(span) = self.span(comp.sig.name.pos()) else {
+            return;
+        };
+        let name = comp.sig.name.to_string();
+        let syms = &self.comps[&name];
+        let names = syms
+            .params
+            .iter()
+            .chain(&syms.sig)
+            .map(|&s| (self.symbols[s].name.clone(), s))
+            .collect();
+        self.scopes.push(Scope {
+            file: span.file,
+            start: span.start,
+            names,
+            comps: HashMap::new(),
+        });
+        self.define(&comp.body);
+
+        let sig = &comp.sig;
+        for p in &sig.params {
+            if let Some(e) = &p.default {
+                self.expr(e);
+            }
+        }
+        for sb in &sig.sig_bindings {
+            match sb.inner() {
+                ast::SigBind::Let { bind, .. } => self.expr(bind),
+                ast::SigBind::Exists { cons, .. } => {
+                    cons.iter().for_each(|c| self.cons(c))
+                }
+            }
+        }
+        for ev in &sig.events {
+            match ev.delay.inner() {
+                ast::TimeSub::Unit(e) => self.expr(e),
+                ast::TimeSub::Sym { l, r } => {
+                    self.time(l);
+                    self.time(r);
+                }
+            }
+        }
+        for c in &sig.param_constraints {
+            self.cons(c);
+        }
+        for port in sig.ports() {
+            self.bundle_type(&port.typ);
+        }
+        self.resolve(&comp.body);
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().unwrap()
+    }
+
+    fn lookup(&self, name: &ast::Id) -> Option<SymIdx> {
+        self.scopes.last()?.names.get(name.as_ref()).copied()
+    }
+
+    /// Define all the names bound in the commands
+    fn define(&mut self, cmds: &[ast::Command]) {
+        for cmd in cmds {
+            match cmd {
+                ast::Command::Instance(inst) => {
+                    let comp = inst.component.to_string();
+                    let hover = code(format!(
+                        "{} := new {comp}[{}]",
+                        inst.name,
+                        inst.params.iter().join(", ")
+                    )) + &self.comp_hover(&comp);
+                    let sym = self.add(&inst.name, Kind::Instance, hover);
+                    let scope = self.scope();
+                    scope.names.insert(inst.name.to_string(), sym);
+                    scope.comps.insert(inst.name.to_string(), comp);
+                }
+                ast::Command::Invoke(inv) => {
+                    let comp = self
+                        .scope()
+                        .comps
+                        .get(inv.instance.as_ref())
+                        .cloned()
+                        .unwrap_or_default();
+                    let hover = code(format!(
+                        "{} := {}<{}>",
+                        inv.name,
+                        inv.instance,
+                        inv.abstract_vars
+                            .iter()
+                            .map(|t| fmt::time(t))
+                            .join(", ")
+                    )) + &self.comp_hover(&comp);
+                    let sym = self.add(&inv.name, Kind::Invoke, hover);
+                    let scope = self.scope();
+                    scope.names.insert(inv.name.to_string(), sym);
+                    scope.comps.insert(inv.name.to_string(), comp);
+                }
+                ast::Command::Bundle(bl) => {
+                    let hover = code(format!("bundle {}", fmt::bundle(bl)));
+                    let sym = self.add(&bl.name, Kind::Bundle, hover);
+                    self.scope().names.insert(bl.name.to_string(), sym);
+                }
+                ast::Command::ParamLet(pl) => {
+                    let hover = code(format!("let {}", pl.name));
+                    let sym = self.add(&pl.name, Kind::Param, hover);
+                    self.scope().names.insert(pl.name.to_string(), sym);
+                }
+                ast::Command::Exists(ex) => {
+                    let hover = code(format!("exists {}", ex.param));
+                    let sym = self.add(&ex.param, Kind::Param, hover);
+                    self.scope().names.insert(ex.param.to_string(), sym);
+                }
+                ast::Command::ForLoop(fl) => {
+                    let hover = code(format!("for {}", fl.idx));
+                    let sym = self.add(&fl.idx, Kind::Index, hover);
+                    self.scope().names.insert(fl.idx.to_string(), sym);
+                    self.define(&fl.body);
+                }
+                ast::Command::If(i) => {
+                    self.define(&i.then);
+                    self.define(&i.alt);
+                }
+                ast::Command::Fact(_) | ast::Command::Connect(_) => (),
+            }
+        }
+    }
+
+    fn comp_hover(&self, comp: &str) -> String {
+        self.comps
+            .get(comp)
+            .map(|c| self.symbols[c.sym].hover.clone())
+            .unwrap_or_default()
+    }
+
+    /// Record the uses of names in the commands
+    fn resolve(&mut self, cmds: &[ast::Command]) {
+        for cmd in cmds {
+            match cmd {
+                ast::Command::Instance(inst) => {
+                    let comp = self.comps.get(inst.component.as_ref());
+                    let sym = comp.map(|c| c.sym);
+                    self.use_sym(&inst.component, sym);
+                    inst.params.iter().for_each(|p| self.expr(p));
+                    inst.lives.iter().for_each(|r| self.range(r));
+                }
+                ast::Command::Invoke(inv) => {
+                    let sym = self.lookup(inv.instance.inner());
+                    self.use_sym(&inv.instance, sym);
+                    inv.abstract_vars.iter().for_each(|t| self.time(t));
+                    inv.ports.iter().for_each(|p| self.port(p));
+                }
+                ast::Command::Fact(f) => {
+                    let imp = f.cons.inner();
+                    if let Some(g) = &imp.guard {
+                        self.cons(g);
+                    }
+                    self.cons(&imp.cons);
+                }
+                ast::Command::Connect(con) => {
+                    self.port(&con.dst);
+                    self.port(&con.src);
+                }
+                ast::Command::ForLoop(fl) => {
+                    self.expr(&fl.start);
+                    self.expr(&fl.end);
+                    self.resolve(&fl.body);
+                }
+                ast::Command::ParamLet(pl) => {
+                    if let Some(e) = &pl.expr {
+                        self.expr(e);
+                    }
+                }
+                ast::Command::If(i) => {
+                    self.cons(&i.cond);
+                    self.resolve(&i.then);
+                    self.resolve(&i.alt);
+                }
+                ast::Command::Bundle(bl) => self.bundle_type(&bl.typ),
+                ast::Command::Exists(ex) => self.expr(&ex.bind),
+            }
+        }
+    }
+
+    fn port(&mut self, port: &ast::Port) {
+        match &port.base {
+            ast::PortRef::This { port } => {
+                let sym = self.lookup(port.inner());
+                self.use_sym(port, sym);
+            }
+            ast::PortRef::Instance { instance, port } => {
+                let sym = self.lookup(instance.inner());
+                self.use_sym(instance, sym);
+                let sym = self.member(instance.inner(), port.inner(), false);
+                self.use_sym(port, sym);
+            }
+        }
+        for acc in &port.access {
+            self.expr(&acc.start);
+            self.expr(&acc.end);
+        }
+    }
+
+    /// Find the port or parameter `name` of the component used by the
+    /// instance or invocation `inst`.
+    fn member(
+        &self,
+        inst: &ast::Id,
+        name: &ast::Id,
+        param: bool,
+    ) -> Option<SymIdx> {
+        let comp = self.scopes.last()?.comps.get(inst.as_ref())?;
+        let syms = self.comps.get(comp)?;
+        let cands = if param { &syms.params } else { &syms.sig };
+        cands
+            .iter()
+            .find(|&&s| self.symbols[s].name == name.as_ref())
+            .copied()
+    }
+
+    fn bundle_type(&mut self, typ: &ast::BundleType) {
+        typ.len.iter().for_each(|e| self.expr(e));
+        self.range(&typ.liveness);
+        self.expr(&typ.bitwidth);
+    }
+
+    fn range(&mut self, r: &ast::Range) {
+        self.time(&r.start);
+        self.time(&r.end);
+    }
+
+    fn time(&mut self, t: &ast::Time) {
+        self.expr(&t.offset)
+    }
+
+    fn cons(&mut self, p: &ast::Prop<ast::Expr>) {
+        for c in p.cmps() {
+            self.expr(&c.left);
+            self.expr(&c.right);
+        }
+    }
+
+    fn expr(&mut self, e: &ast::Expr) {
+        match e {
+            ast::Expr::Concrete(_) => (),
+            ast::Expr::Abstract(p) => {
+                let sym = self.lookup(p.inner());
+                self.use_sym(p, sym);
+            }
+            ast::Expr::ParamAccess { inst, param } => {
+                let sym = self.lookup(inst.inner());
+                self.use_sym(inst, sym);
+                let sym = self.member(inst.inner(), param.inner(), true);
+                self.use_sym(param, sym);
+            }
+            ast::Expr::App { args, .. } | ast::Expr::Call { args, .. } => {
+                args.iter().for_each(|a| self.expr(a))
+            }
+            ast::Expr::Op { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ast::Expr::If { cond, then, alt } => {
+                self.cons(cond);
+                self.expr(then);
+                self.expr(alt);
+            }
+        }
+    }
+}
+
+/// Queries used by the language server
+impl Index {
+    pub fn symbol(&self, idx: SymIdx) -> &Symbol {
+        &self.symbols[idx]
+    }
+
+    /// The symbol at the given offset in a file
+    pub fn symbol_at(&self, file: &Path, offset: usize) -> Option<SymIdx> {
+        self.refs
+            .iter()
+            .filter(|(span, _)| span.contains(file, offset))
+            // Prefer the innermost span
+            .min_by_key(|(span, _)| span.end - span.start)
+            .map(|(_, sym)| *sym)
+    }
+
+    /// The scope of the component that contains the given offset
+    fn scope_at(&self, file: &Path, offset: usize) -> Option<&Scope> {
+        self.scopes
+            .iter()
+            .filter(|s| s.file == file && s.start <= offset)
+            .max_by_key(|s| s.start)
+    }
+
+    /// Names that can be completed at the given offset. If `inst` is
+    /// provided, returns the ports of the component it instantiates.
+    pub fn completions(
+        &self,
+        file: &Path,
+        offset: usize,
+        inst: Option<&str>,
+    ) -> Vec<SymIdx> {
+        let scope = self.scope_at(file, offset);
+        match inst {
+            Some(inst) => scope
+                .and_then(|s| s.comps.get(inst))
+                .and_then(|c| self.comps.get(c))
+                .map(|c| c.ports.clone())
+                .unwrap_or_default(),
+            None => scope
+                .into_iter()
+                .flat_map(|s| s.names.values().copied())
+                .chain(self.comps.values().map(|c| c.sym))
+                .sorted()
+                .collect(),
+        }
+    }
+}
+
+/// Wrap the text in a Filament code block
+fn code(s: String) -> String {
+    format!("```filament\n{s}\n```\n")
+}
+
+/// Position in LSP coordinates for a byte offset in the text
+pub fn position(text: &str, offset: usize) -> Position {
+    let offset = offset.min(text.len());
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character = before[start..].encode_utf16().count();
+    Position::new(line as u32, character as u32)
+}
+
+/// Byte offset in the text for a position in LSP coordinates
+pub fn offset(text: &str, pos: Position) -> usize {
+    let start: usize = text
+        .split_inclusive('\n')
+        .take(pos.line as usize)
+        .map(str::len)
+        .sum();
+    let mut units = 0;
+    for (i, c) in text[start..].char_indices() {
+        if units >= pos.character as usize || c == '\n' {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// Pretty printing for the parts of the AST shown on hover
+mod fmt {
+    use fil_ast as ast;
+    use itertools::Itertools;
+
+    pub fn time(t: &ast::Time) -> String {
+        match &t.offset {
+            ast::Expr::Concrete(0) => format!("'{}", t.event),
+            off => format!("'{}+{off}", t.event),
+        }
+    }
+
+    fn range(r: &ast::Range) -> String {
+        format!("[{}, {}]", time(&r.start), time(&r.end))
+    }
+
+    pub fn event(ev: &ast::EventBind) -> String {
+        let delay = match ev.delay.inner() {
+            ast::TimeSub::Unit(e) => e.to_string(),
+            ast::TimeSub::Sym { l, r } => format!("{}-({})", time(l), time(r)),
+        };
+        match &ev.default {
+            Some(t) => format!("?'{}: {delay}={}", ev.event, time(t)),
+            None => format!("'{}: {delay}", ev.event),
+        }
+    }
+
+    pub fn bundle(bl: &ast::Bundle) -> String {
+        let typ = &bl.typ;
+        // The parser adds sizes and indices without positions for ports
+        // that are not bundles
+        let lens = typ
+            .len
+            .iter()
+            .filter(|l| l.pos().into_option().is_some())
+            .map(|l| format!("[{l}]"))
+            .join("");
+        let idx = typ
+            .idx
+            .iter()
+            .filter(|i| i.pos().into_option().is_some())
+            .join(", ");
+        let idx = if idx.is_empty() {
+            idx
+        } else {
+            format!("for<{idx}> ")
+        };
+        format!(
+            "{}{lens}: {idx}{} {}",
+            bl.name,
+            range(&typ.liveness),
+            typ.bitwidth
+        )
+    }
+
+    pub fn sig(sig: &ast::Signature) -> String {
+        let params = if sig.params.is_empty() {
+            String::new()
+        } else {
+            let mut ps = sig.params.iter().map(|p| match &p.default {
+                Some(d) => format!("?{}={d}", p.param),
+                None => p.param.to_string(),
+            });
+            format!("[{}]", ps.join(", "))
+        };
+        let events = sig.events.iter().map(|ev| event(ev)).join(", ");
+        let inputs = sig
+            .unannotated_ports
+            .iter()
+            .map(|(n, w)| format!("{n}: {w}"))
+            .chain(
+                sig.interface_signals
+                    .iter()
+                    .map(|i| format!("{}: interface['{}]", i.name, i.event)),
+            )
+            .chain(sig.inputs().map(|p| bundle(p)))
+            .map(|p| format!("  {p},\n"))
+            .join("");
+        let outputs = sig
+            .outputs()
+            .map(|p| format!("  {},\n", bundle(p)))
+            .join("");
+        let mut out = format!(
+            "comp {}{params}<{events}>(\n{inputs}) -> (\n{outputs})",
+            sig.name
+        );
+        let cons = sig
+            .param_constraints
+            .iter()
+            .map(|c| c.to_string())
+            .collect_vec();
+        if !cons.is_empty() {
+            out += &format!(" where {}", cons.join(", "));
+        }
+        out
+    }
+}
@@ -0,0 +1,24 @@
+//! Language server for Filament.
+//! Communicates with the editor over stdin/stdout and reuses the frontend and
+//! checking passes of the compiler to provide diagnostics, go-to-definition,
+//! hover information, and completion.
+mod analysis;
+mod index;
+mod server;
+
+use lsp_server::Connection;
+
+fn main() -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+    // Logs go to stderr because stdout is used by the protocol
+    env_logger::Builder::from_default_env()
+        .format_timestamp(None)
+        .target(env_logger::Target::Stderr)
+        .init();
+
+    let (conn, io_threads) = Connection::stdio();
+    server::Server::start(&conn)?.run()?;
+    // The writer thread exits once the connection is closed
+    drop(conn);
+    io_threads.join()?;
+    Ok(())
+}
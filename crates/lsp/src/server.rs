@@ -0,0 +1,345 @@
+use crate::{
+    analysis::Analysis,
+    index::{self, Index, Kind, Span},
+};
+use argh::FromArgs;
+use filament::cmdline;
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::{
+    self as lt, notification::Notification as _, request::Request as _,
+};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+type Result<T> =
+    std::result::Result<T, Box<dyn std::error::Error + Sync + Send>>;
+
+/// Settings provided by the editor in `initializationOptions`
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Config {
+    /// Paths to search for imports. Defaults to the workspace root.
+    library: Vec<PathBuf>,
+    /// Solver used to discharge assertions
+    solver: Option<String>,
+    /// Only run the type checker and do not discharge assertions
+    skip_discharge: bool,
+}
+
+pub struct Server<'a> {
+    conn: &'a Connection,
+    config: Config,
+    /// Contents of open documents, including unsaved changes
+    docs: HashMap<lt::Url, String>,
+    /// The latest analysis for each checked file
+    analyses: HashMap<PathBuf, Analysis>,
+    /// Files with published diagnostics for each checked file
+    published: HashMap<PathBuf, HashSet<PathBuf>>,
+}
+
+impl<'a> Server<'a> {
+    /// Perform the initialization handshake with the editor
+    pub fn start(conn: &'a Connection) -> Result<Self> {
+        let caps = lt::ServerCapabilities {
+            text_document_sync: Some(lt::TextDocumentSyncCapability::Options(
+                lt::TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(lt::TextDocumentSyncKind::FULL),
+                    save: Some(lt::SaveOptions::default().into()),
+                    ..Default::default()
+                },
+            )),
+            definition_provider: Some(lt::OneOf::Left(true)),
+            hover_provider: Some(lt::HoverProviderCapability::Simple(true)),
+            completion_provider: Some(lt::CompletionOptions {
+                trigger_characters: Some(vec![".".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let params = conn.initialize(serde_json::to_value(caps)?)?;
+        let params: lt::InitializeParams = serde_json::from_value(params)?;
+
+        let mut config: Config = params
+            .initialization_options
+            .map(serde_json::from_value)
+            .transpose()?
+            .unwrap_or_default();
+        #[allow(deprecated)]
+        let root = params
+            .workspace_folders
+            .and_then(|ws| ws.into_iter().next().map(|w| w.uri))
+            .or(params.root_uri)
+            .and_then(|uri| uri.to_file_path().ok());
+        if config.library.is_empty() {
+            config.library.extend(root);
+        }
+        log::info!("Library paths: {:?}", config.library);
+
+        Ok(Self {
+            conn,
+            config,
+            docs: HashMap::new(),
+            analyses: HashMap::new(),
+            published: HashMap::new(),
+        })
+    }
+
+    /// Handle messages until the editor asks the server to shut down
+    pub fn run(mut self) -> Result<()> {
+        for msg in &self.conn.receiver {
+            match msg {
+                Message::Request(req) => {
+                    if self.conn.handle_shutdown(&req)? {
+                        return Ok(());
+                    }
+                    let resp = self.request(req);
+                    self.conn.sender.send(resp.into())?;
+                }
+                Message::Notification(not) => self.notification(not)?,
+                Message::Response(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, req: Request) -> Response {
+        let id = req.id.clone();
+        let res = match req.method.as_str() {
+            lt::request::GotoDefinition::METHOD => req
+                .extract(lt::request::GotoDefinition::METHOD)
+                .map(|(_, p)| serde_json::to_value(self.definition(p))),
+            lt::request::HoverRequest::METHOD => req
+                .extract(lt::request::HoverRequest::METHOD)
+                .map(|(_, p)| serde_json::to_value(self.hover(p))),
+            lt::request::Completion::METHOD => req
+                .extract(lt::request::Completion::METHOD)
+                .map(|(_, p)| serde_json::to_value(self.completion(p))),
+            method => {
+                return Response::new_err(
+                    id,
+                    lsp_server::ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request: {method}"),
+                );
+            }
+        };
+        match res {
+            Ok(Ok(v)) => Response::new_ok(id, v),
+            Ok(Err(e)) => Response::new_err(
+                id,
+                lsp_server::ErrorCode::InternalError as i32,
+                e.to_string(),
+            ),
+            Err(e) => Response::new_err(
+                id,
+                lsp_server::ErrorCode::InvalidParams as i32,
+                e.to_string(),
+            ),
+        }
+    }
+
+    fn notification(&mut self, not: Notification) -> Result<()> {
+        use lt::notification as n;
+        match not.method.as_str() {
+            n::DidOpenTextDocument::METHOD => {
+                let p: lt::DidOpenTextDocumentParams =
+                    not.extract(n::DidOpenTextDocument::METHOD)?;
+                let uri = p.text_document.uri;
+                self.docs.insert(uri.clone(), p.text_document.text);
+                self.check(&uri)?;
+            }
+            n::DidChangeTextDocument::METHOD => {
+                let p: lt::DidChangeTextDocumentParams =
+                    not.extract(n::DidChangeTextDocument::METHOD)?;
+                // We only ask for full document updates
+                if let Some(change) = p.content_changes.into_iter().last() {
+                    self.docs.insert(p.text_document.uri, change.text);
+                }
+            }
+            n::DidSaveTextDocument::METHOD => {
+                let p: lt::DidSaveTextDocumentParams =
+                    not.extract(n::DidSaveTextDocument::METHOD)?;
+                self.check(&p.text_document.uri)?;
+            }
+            n::DidCloseTextDocument::METHOD => {
+                let p: lt::DidCloseTextDocumentParams =
+                    not.extract(n::DidCloseTextDocument::METHOD)?;
+                self.docs.remove(&p.text_document.uri);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Command line options to check the given file
+    fn opts(&self, file: &Path) -> Result<cmdline::Opts> {
+        let mut args =
+            vec![file.to_string_lossy().to_string(), "--check".into()];
+        for lib in &self.config.library {
+            args.extend([
+                "--library".into(),
+                lib.to_string_lossy().to_string(),
+            ]);
+        }
+        if let Some(solver) = &self.config.solver {
+            args.extend(["--solver".into(), solver.clone()]);
+        }
+        if self.config.skip_discharge {
+            args.push("--unsafe-skip-discharge".into());
+        }
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        cmdline::Opts::from_args(&["filament"], &args)
+            .map_err(|e| e.output.into())
+    }
+
+    /// Check the file on disk and publish the diagnostics
+    fn check(&mut self, uri: &lt::Url) -> Result<()> {
+        let Ok(path) = uri.to_file_path() else {
+            return Ok(());
+        };
+        let path = fs::canonicalize(&path).unwrap_or(path);
+        log::info!("Checking {}", path.display());
+        let analysis = Analysis::run(&self.opts(&path)?);
+
+        // Clear diagnostics for files that no longer have errors
+        let prev = self.published.remove(&path).unwrap_or_default();
+        let mut cur = HashSet::new();
+        for file in prev.iter().chain(analysis.diags.keys()) {
+            if !cur.insert(file.clone()) {
+                continue;
+            }
+            let diags = analysis.diags.get(file).cloned().unwrap_or_default();
+            let Ok(uri) = lt::Url::from_file_path(file) else {
+                continue;
+            };
+            let params = lt::PublishDiagnosticsParams::new(uri, diags, None);
+            self.conn.sender.send(
+                Notification::new(
+                    lt::notification::PublishDiagnostics::METHOD.to_string(),
+                    params,
+                )
+                .into(),
+            )?;
+        }
+        cur.retain(|f| analysis.diags.get(f).is_some_and(|d| !d.is_empty()));
+        self.published.insert(path.clone(), cur);
+
+        // Keep the previous index around if the file failed to parse
+        if analysis.index.is_some() || !self.analyses.contains_key(&path) {
+            self.analyses.insert(path, analysis);
+        }
+        Ok(())
+    }
+
+    /// The index for a document and the path of the document
+    fn index(&mut self, uri: &lt::Url) -> Option<(&Index, PathBuf)> {
+        let path = uri.to_file_path().ok()?;
+        let path = fs::canonicalize(&path).unwrap_or(path);
+        if !self.analyses.contains_key(&path) {
+            // Files opened through go-to-definition may not have been checked
+            self.check(uri).ok()?;
+        }
+        let index = self.analyses.get(&path)?.index.as_ref()?;
+        Some((index, path))
+    }
+
+    /// Offset into the analyzed version of a document
+    fn offset(
+        &mut self,
+        pos: &lt::TextDocumentPositionParams,
+    ) -> Option<(&Index, PathBuf, usize)> {
+        let (index, path) = self.index(&pos.text_document.uri)?;
+        let offset = index::offset(index.sources.get(&path)?, pos.position);
+        Some((index, path, offset))
+    }
+
+    fn definition(
+        &mut self,
+        p: lt::GotoDefinitionParams,
+    ) -> Option<lt::GotoDefinitionResponse> {
+        let (index, path, offset) =
+            self.offset(&p.text_document_position_params)?;
+        let sym = index.symbol(index.symbol_at(&path, offset)?);
+        let loc = location(index, sym.def.as_ref()?)?;
+        Some(lt::GotoDefinitionResponse::Scalar(loc))
+    }
+
+    fn hover(&mut self, p: lt::HoverParams) -> Option<lt::Hover> {
+        let (index, path, offset) =
+            self.offset(&p.text_document_position_params)?;
+        let sym = index.symbol(index.symbol_at(&path, offset)?);
+        Some(lt::Hover {
+            contents: lt::HoverContents::Markup(lt::MarkupContent {
+                kind: lt::MarkupKind::Markdown,
+                value: sym.hover.clone(),
+            }),
+            range: None,
+        })
+    }
+
+    fn completion(
+        &mut self,
+        p: lt::CompletionParams,
+    ) -> Option<lt::CompletionResponse> {
+        let pos = p.text_document_position;
+        // Use the current contents of the document to find what is being typed
+        let text = self.docs.get(&pos.text_document.uri).cloned();
+        let (index, path) = self.index(&pos.text_document.uri)?;
+        let text = text.as_ref().or(index.sources.get(&path))?;
+        let offset = index::offset(text, pos.position);
+
+        // Skip the partially typed name and check if it follows `<inst>.`
+        let ident = |c: char| c.is_alphanumeric() || c == '_';
+        let start = text[..offset].trim_end_matches(ident).len();
+        let inst = text[..start].strip_suffix('.').map(|before| {
+            let name_start = before.trim_end_matches(ident).len();
+            &before[name_start..]
+        });
+
+        let items = index
+            .completions(&path, offset, inst)
+            .into_iter()
+            .map(|s| {
+                let sym = index.symbol(s);
+                lt::CompletionItem {
+                    label: sym.name.clone(),
+                    kind: Some(completion_kind(sym.kind)),
+                    documentation: Some(lt::Documentation::MarkupContent(
+                        lt::MarkupContent {
+                            kind: lt::MarkupKind::Markdown,
+                            value: sym.hover.clone(),
+                        },
+                    )),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        Some(lt::CompletionResponse::Array(items))
+    }
+}
+
+/// Location of a span in the editor
+fn location(index: &Index, span: &Span) -> Option<lt::Location> {
+    let src = index.sources.get(&span.file)?;
+    Some(lt::Location::new(
+        lt::Url::from_file_path(&span.file).ok()?,
+        lt::Range::new(
+            index::position(src, span.start),
+            index::position(src, span.end),
+        ),
+    ))
+}
+
+fn completion_kind(kind: Kind) -> lt::CompletionItemKind {
+    match kind {
+        Kind::Component => lt::CompletionItemKind::CLASS,
+        Kind::Param | Kind::Index => lt::CompletionItemKind::CONSTANT,
+        Kind::Event => lt::CompletionItemKind::EVENT,
+        Kind::Port | Kind::Bundle => lt::CompletionItemKind::FIELD,
+        Kind::Instance | Kind::Invoke => lt::CompletionItemKind::VARIABLE,
+    }
+}
@@ -18,6 +18,28 @@ where
     float_attrs: AttrStore<Float, f64>,
 }
 
+impl<Bool, Num, Float> Attributes<Bool, Num, Float>
+where
+    Bool: Hash + Eq + Copy,
+    Num: Hash + Eq + Copy,
+    Float: Hash + Eq + Copy,
+{
+    /// Boolean attributes and their values
+    pub fn bools(&self) -> impl Iterator<Item = (Bool, bool)> + '_ {
+        self.bool_attrs.iter().map(|(a, v)| (a, *v))
+    }
+
+    /// Numerical attributes and their values
+    pub fn nums(&self) -> impl Iterator<Item = (Num, u64)> + '_ {
+        self.num_attrs.iter().map(|(a, v)| (a, *v))
+    }
+
+    /// Float attributes and their values
+    pub fn floats(&self) -> impl Iterator<Item = (Float, f64)> + '_ {
+        self.float_attrs.iter().map(|(a, v)| (a, *v))
+    }
+}
+
 impl<Bool, Num, Float> AttrCtx<Num, u64> for Attributes<Bool, Num, Float>
 where
     Bool: Hash + Eq + Copy,
@@ -8,6 +8,18 @@ attr_set! {
             TopLevel: "toplevel",
             /// Use a counter based FSM design
             CounterFSM: "counter_fsm",
+            /// Estimate the primitive as a register whose width is its first
+            /// parameter
+            Register: "register",
+            /// Estimate the primitive as an adder whose width is its first
+            /// parameter
+            Adder: "adder",
+            /// Estimate the primitive as a multiplier whose width is its first
+            /// parameter
+            Multiplier: "multiplier",
+            /// Estimate the primitive as a two-input multiplexer whose width is
+            /// its first parameter
+            Mux: "mux",
         };
     };
     numeric {};
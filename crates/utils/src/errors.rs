@@ -23,6 +23,14 @@ impl std::fmt::Debug for Error {
     }
 }
 
+/// The notes of an error refer to source locations and are only shown when
+/// the error is reported using [crate::Diagnostics].
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
 impl Error {
     pub fn add_note(mut self, note: InfoIdx) -> Self {
         self.notes.push(note);
@@ -18,4 +18,4 @@ pub use id::Id;
 pub use math::{all_indices, flat_idx, nd_idx};
 pub use pool::Pool;
 pub use position::{FileIdx, GPosIdx, GlobalPositionTable, PosData};
-pub use reporter::{Diagnostics, InfoIdx};
+pub use reporter::{Diagnostics, InfoIdx, MessageFormat};
@@ -180,6 +180,12 @@ impl GlobalPositionTable {
 /// A position index backed by a global [PositionTable]
 pub struct GPosIdx(pub PosIdx);
 
+impl Default for GPosIdx {
+    fn default() -> Self {
+        Self::UNKNOWN
+    }
+}
+
 impl GPosIdx {
     /// Symbol for the unknown position.
     /// All [PositionTable] must allocate the first index for this use.
@@ -206,6 +212,14 @@ impl GPosIdx {
         Label::primary(pos.file.get(), pos.start..pos.end)
     }
 
+    /// The source text covered by this position
+    pub fn source(self) -> &'static str {
+        let table = GlobalPositionTable::get();
+        let pos = table.get_pos(self.0);
+        let (_, src) = table.get_file_data(pos.file);
+        &src[pos.start..pos.end]
+    }
+
     /// Convert this into a Secondary label
     pub fn secondary(self) -> Label<usize> {
         assert!(
@@ -1,10 +1,117 @@
 use crate::{Error, GPosIdx, GlobalPositionTable};
 use codespan_reporting::term::termcolor::ColorChoice;
 use codespan_reporting::{
-    diagnostic::{Diagnostic, Label, LabelStyle},
+    diagnostic::{Diagnostic, Label, LabelStyle, Severity},
+    files::Files,
     term::{self, termcolor::StandardStream},
 };
-use std::collections::BTreeMap;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{cell::RefCell, collections::BTreeMap, str::FromStr};
+
+thread_local! {
+    /// Diagnostics collected by [Diagnostics::capture] instead of being printed.
+    static CAPTURED: RefCell<Option<Vec<Diagnostic<usize>>>> =
+        const { RefCell::new(None) };
+}
+
+/// Are diagnostics reported as JSON records?
+static JSON: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+/// Format used to report diagnostics
+pub enum MessageFormat {
+    /// Rendered for humans by [codespan_reporting]
+    #[default]
+    Human,
+    /// One JSON object per diagnostic
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!(
+                "unknown message format: {s}. Expected one of: human, json"
+            )),
+        }
+    }
+}
+
+#[derive(Serialize)]
+/// A source location attached to a [Record]
+struct Span {
+    file: String,
+    /// Byte offsets of the span
+    start: usize,
+    end: usize,
+    /// One-indexed line and column of the start of the span
+    line: usize,
+    column: usize,
+    /// One-indexed line and column of the end of the span
+    end_line: usize,
+    end_column: usize,
+    primary: bool,
+    message: String,
+}
+
+#[derive(Serialize)]
+/// Machine-readable representation of a [Diagnostic]
+struct Record {
+    severity: &'static str,
+    message: String,
+    spans: Vec<Span>,
+    notes: Vec<String>,
+    /// Assignment to parameters that violates the constraint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    counterexample: Option<BTreeMap<String, String>>,
+}
+
+impl Record {
+    fn new(
+        diag: &Diagnostic<usize>,
+        counterexample: Option<BTreeMap<String, String>>,
+    ) -> Self {
+        let files = GlobalPositionTable::get().files();
+        let spans = diag
+            .labels
+            .iter()
+            .filter_map(|l| {
+                let start = files.location(l.file_id, l.range.start).ok()?;
+                let end = files.location(l.file_id, l.range.end).ok()?;
+                Some(Span {
+                    file: files.name(l.file_id).ok()?,
+                    start: l.range.start,
+                    end: l.range.end,
+                    line: start.line_number,
+                    column: start.column_number,
+                    end_line: end.line_number,
+                    end_column: end.column_number,
+                    primary: l.style == LabelStyle::Primary,
+                    message: l.message.clone(),
+                })
+            })
+            .collect();
+        let severity = match diag.severity {
+            Severity::Bug => "bug",
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        };
+        Self {
+            severity,
+            message: diag.message.clone(),
+            spans,
+            notes: diag.notes.clone(),
+            counterexample,
+        }
+    }
+}
 
 #[derive(PartialOrd, Ord, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 /// Index for information associated with a [Diagnostic] instance.
@@ -79,15 +186,75 @@ impl Diagnostics {
         }
     }
 
-    /// Report all errors and return the number of errors.
-    /// Returns None if there are no errors.
-    pub fn report_all(&mut self) -> Option<u64> {
+    /// Run `f` and return all the diagnostics reported on this thread while
+    /// it runs instead of printing them.
+    /// The labels of the diagnostics refer to files in the [GlobalPositionTable].
+    pub fn capture<T, F: FnOnce() -> T>(f: F) -> (T, Vec<Diagnostic<usize>>) {
+        let prev = CAPTURED.with(|c| c.replace(Some(vec![])));
+        let out = f();
+        let diags = CAPTURED.with(|c| c.replace(prev)).unwrap_or_default();
+        (out, diags)
+    }
+
+    /// Set the format used to print diagnostics.
+    pub fn set_format(format: MessageFormat) {
+        JSON.store(format == MessageFormat::Json, Ordering::Relaxed);
+    }
+
+    /// The format used to print diagnostics.
+    pub fn format() -> MessageFormat {
+        if JSON.load(Ordering::Relaxed) {
+            MessageFormat::Json
+        } else {
+            MessageFormat::Human
+        }
+    }
+
+    /// Print a diagnostic to stderr or record it if it is reported within
+    /// [Diagnostics::capture].
+    pub fn emit(diag: Diagnostic<usize>) {
+        Self::emit_with_counterexample(diag, None)
+    }
+
+    /// Report a diagnostic for a constraint that does not hold along with an
+    /// assignment to parameters that violates it. The assignment is only
+    /// printed separately with [MessageFormat::Json].
+    pub fn emit_with_counterexample(
+        diag: Diagnostic<usize>,
+        counterexample: Option<BTreeMap<String, String>>,
+    ) {
+        let Some(diag) = CAPTURED.with(|c| match &mut *c.borrow_mut() {
+            Some(captured) => {
+                captured.push(diag);
+                None
+            }
+            None => Some(diag),
+        }) else {
+            return;
+        };
+        if Self::format() == MessageFormat::Json {
+            let record = Record::new(&diag, counterexample);
+            eprintln!("{}", serde_json::to_string(&record).unwrap());
+            return;
+        }
         let is_tty = atty::is(atty::Stream::Stderr);
         let writer = StandardStream::stderr(if is_tty {
             ColorChoice::Always
         } else {
             ColorChoice::Never
         });
+        term::emit(
+            &mut writer.lock(),
+            &term::Config::default(),
+            GlobalPositionTable::get().files(),
+            &diag,
+        )
+        .unwrap();
+    }
+
+    /// Report all errors and return the number of errors.
+    /// Returns None if there are no errors.
+    pub fn report_all(&mut self) -> Option<u64> {
         if self.errors.is_empty() {
             return None;
         }
@@ -141,16 +308,12 @@ impl Diagnostics {
             };
 
             total += 1;
-            term::emit(
-                &mut writer.lock(),
-                &term::Config::default(),
-                table.files(),
-                &Diagnostic::error()
+            Self::emit(
+                Diagnostic::error()
                     .with_message(msg)
                     .with_labels(labels)
                     .with_notes(notes),
-            )
-            .unwrap();
+            );
         }
 
         Some(total)
@@ -0,0 +1,20 @@
+#!/bin/bash
+# Generates a module that produces a constant one cycle after it is invoked.
+# Usage: const-gen.sh <name> <output> <width> <value>
+set -euf -o pipefail
+
+if [ "$3" -gt 64 ]; then
+  echo "width $3 is larger than 64 bits" >&2
+  exit 1
+fi
+
+cat > "$2" <<VERILOG
+module $1 (
+  input wire clk,
+  output reg [$3-1:0] out
+);
+  always @(posedge clk) out <= $4;
+endmodule
+VERILOG
+
+echo "latency = 1"
@@ -0,0 +1,94 @@
+#!/bin/bash
+# Generates a ROM whose contents are read from a memory initialization file.
+# The ROM is split between two Verilog files and the result is reported using
+# the JSON protocol. Usage: rom-gen.sh <name> <output> <result> <width> <mode>
+set -euf -o pipefail
+
+NAME="$1"
+OUT="$2"
+RESULT="$3"
+WIDTH="$4"
+DIR="$(dirname "$OUT")"
+
+# Logs on the standard output are ignored
+echo "latency = 100"
+
+cat > "$DIR/${NAME}_mem.v" <<VERILOG
+module ${NAME}_mem (
+  input wire clk,
+  output reg [$WIDTH-1:0] out
+);
+  reg [$WIDTH-1:0] mem [0:0];
+  initial \$readmemh("${NAME}.mem", mem);
+  always @(posedge clk) out <= mem[0];
+endmodule
+VERILOG
+
+cat > "$OUT" <<VERILOG
+module $NAME (
+  input wire clk,
+  output wire [$WIDTH-1:0] out
+);
+  ${NAME}_mem m (.clk(clk), .out(out));
+endmodule
+VERILOG
+
+echo "1" > "$DIR/$NAME.mem"
+
+FILES="[\"$(basename "$OUT")\", \"${NAME}_mem.v\", \"$NAME.mem\"]"
+
+if [ "$5" = "good" ]; then
+  cat > "$RESULT" <<JSON
+{
+  "files": $FILES,
+  "outputs": { "latency": 1 },
+  "warnings": ["contents are not initialized"]
+}
+JSON
+elif [ "$5" = "nested" ]; then
+  # The initialization file is reported from a subdirectory
+  mkdir -p "$DIR/$NAME"
+  mv "$DIR/$NAME.mem" "$DIR/$NAME/$NAME.mem"
+  cat > "$RESULT" <<JSON
+{
+  "files": ["$(basename "$OUT")", "${NAME}_mem.v", "$NAME/$NAME.mem"],
+  "outputs": { "latency": 1 }
+}
+JSON
+elif [ "$5" = "signature" ]; then
+  # The latency is only reported by the signature
+  cat > "$RESULT" <<JSON
+{
+  "files": $FILES,
+  "signature": "comp $NAME<'G:1>(clk: 1) -> (out: ['G+2, 'G+3] $WIDTH);"
+}
+JSON
+elif [ "$5" = "exists" ]; then
+  # The latency is an existential parameter of the signature
+  cat > "$RESULT" <<JSON
+{
+  "files": $FILES,
+  "signature": "comp $NAME<'G:1>(clk: 1) -> (out: ['G+L, 'G+L+1] $WIDTH) with { some L where L == 2; };"
+}
+JSON
+elif [ "$5" = "mismatch" ]; then
+  cat > "$RESULT" <<JSON
+{
+  "files": $FILES,
+  "outputs": { "latency": 1 },
+  "signature": "comp $NAME<'G:1>(clk: 1) -> (out: ['G+2, 'G+3] $WIDTH);"
+}
+JSON
+elif [ "$5" = "malformed" ]; then
+  cat > "$RESULT" <<JSON
+{
+  "files": $FILES,
+  "outputs": { "latency": 1 },
+  "signature": "comp $NAME<'G:1>(clk: 1) -> (out: 'G+1 $WIDTH);"
+}
+JSON
+else
+  cat > "$RESULT" <<JSON
+{ "outputs": { "delay": 1 } }
+JSON
+fi
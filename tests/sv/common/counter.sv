@@ -0,0 +1,13 @@
+module counter_2 (
+  input wire [7:0] in,
+  output wire [7:0] out
+);
+  assign out = in;
+endmodule
+
+module fil_counter_2 (
+  input wire [7:0] in,
+  output wire [7:0] out
+);
+  assign out = in;
+endmodule